@@ -0,0 +1,209 @@
+//! Who may join: the whitelist and ban lists, in the same json files vanilla uses. They are
+//! shared between connection threads, which check them before LoginSuccess, and the tick
+//! thread, where commands change them.
+
+use std::net::IpAddr;
+use std::path::Path;
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::entity::{parse_uuid, uuid_string};
+use crate::permissions::{load_json, save_json, Operators, OPS_FILE};
+
+pub const WHITELIST_FILE: &str = "whitelist.json";
+pub const BANNED_PLAYERS_FILE: &str = "banned-players.json";
+pub const BANNED_IPS_FILE: &str = "banned-ips.json";
+/// How vanilla writes `created` and `expires`.
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+const FOREVER: &str = "forever";
+pub const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhitelistEntry {
+    pub uuid: String,
+    pub name: String,
+}
+/// What every kind of ban records.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanDetails {
+    pub created: String,
+    pub source: String,
+    /// A date in `DATE_FORMAT`, or `forever`.
+    pub expires: String,
+    pub reason: String,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerBan {
+    pub uuid: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpBan {
+    pub ip: String,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+
+impl BanDetails {
+    /// A ban starting now that lasts until `expires`, or forever.
+    pub fn new(source: &str, reason: Option<&str>, expires: Option<DateTime<FixedOffset>>) -> Self {
+        Self {
+            created: Local::now().format(DATE_FORMAT).to_string(),
+            source: source.to_string(),
+            expires: expires.map_or(FOREVER.to_string(), |d| d.format(DATE_FORMAT).to_string()),
+            reason: reason.unwrap_or(DEFAULT_BAN_REASON).to_string(),
+        }
+    }
+    fn expiry(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_str(&self.expires, DATE_FORMAT).ok()
+    }
+    /// Bans with an unreadable expiry count as permanent, like in vanilla.
+    pub fn expired(&self) -> bool {
+        self.expiry().is_some_and(|d| d < Local::now())
+    }
+    /// The kick message, using `key` for the part with the reason.
+    pub fn message(&self, key: &str) -> Value {
+        let mut message = json!({ "translate": key, "with": [self.reason] });
+        if let Some(expiry) = self.expiry() {
+            message["extra"] = json!([{
+                "translate": "multiplayer.disconnect.banned.expiration",
+                "with": [expiry.format(DATE_FORMAT).to_string()],
+            }]);
+        }
+        message
+    }
+}
+
+#[derive(Debug)]
+pub struct AccessLists {
+    /// Off by default like vanilla's `white-list` property. `/whitelist on` only lasts until
+    /// the server restarts.
+    pub whitelist_enabled: bool,
+    pub whitelist: Vec<WhitelistEntry>,
+    pub banned_players: Vec<PlayerBan>,
+    pub banned_ips: Vec<IpBan>,
+    /// Operators can join even when they aren't whitelisted.
+    pub ops: Operators,
+}
+impl AccessLists {
+    pub fn load() -> Self {
+        Self {
+            whitelist_enabled: false,
+            whitelist: load_json(Path::new(WHITELIST_FILE)),
+            banned_players: load_json(Path::new(BANNED_PLAYERS_FILE)),
+            banned_ips: load_json(Path::new(BANNED_IPS_FILE)),
+            ops: Operators::load(OPS_FILE),
+        }
+    }
+    pub fn reload_whitelist(&mut self) {
+        self.whitelist = load_json(Path::new(WHITELIST_FILE));
+    }
+    pub fn save(&self) {
+        save_json(Path::new(WHITELIST_FILE), &self.whitelist);
+        save_json(Path::new(BANNED_PLAYERS_FILE), &self.banned_players);
+        save_json(Path::new(BANNED_IPS_FILE), &self.banned_ips);
+    }
+    /// Why a player may not join, as a json text component, or `None` if they may.
+    pub fn check_login(&mut self, uuid: u128, ip: IpAddr) -> Option<String> {
+        self.remove_expired();
+        if let Some(ban) = self.player_ban(uuid) {
+            return Some(
+                ban.details
+                    .message("multiplayer.disconnect.banned.reason")
+                    .to_string(),
+            );
+        }
+        if let Some(ban) = self.ip_ban(ip) {
+            return Some(
+                ban.details
+                    .message("multiplayer.disconnect.banned_ip.reason")
+                    .to_string(),
+            );
+        }
+        if self.whitelist_enabled && !self.is_whitelisted(uuid) && self.ops.level(uuid) == 0 {
+            return Some(
+                json!({ "translate": "multiplayer.disconnect.not_whitelisted" }).to_string(),
+            );
+        }
+        None
+    }
+    fn remove_expired(&mut self) {
+        let (players, ips) = (self.banned_players.len(), self.banned_ips.len());
+        self.banned_players.retain(|b| !b.details.expired());
+        self.banned_ips.retain(|b| !b.details.expired());
+        if players != self.banned_players.len() || ips != self.banned_ips.len() {
+            self.save();
+        }
+    }
+    pub fn is_whitelisted(&self, uuid: u128) -> bool {
+        self.whitelist
+            .iter()
+            .any(|e| parse_uuid(&e.uuid) == Some(uuid))
+    }
+    /// Returns false if the player was already on the whitelist.
+    pub fn add_to_whitelist(&mut self, uuid: u128, name: &str) -> bool {
+        if self.is_whitelisted(uuid) {
+            return false;
+        }
+        self.whitelist.push(WhitelistEntry {
+            uuid: uuid_string(uuid),
+            name: name.to_string(),
+        });
+        self.save();
+        true
+    }
+    /// Returns false if the player wasn't on the whitelist.
+    pub fn remove_from_whitelist(&mut self, uuid: u128) -> bool {
+        let before = self.whitelist.len();
+        self.whitelist.retain(|e| parse_uuid(&e.uuid) != Some(uuid));
+        self.save();
+        before != self.whitelist.len()
+    }
+    pub fn player_ban(&self, uuid: u128) -> Option<&PlayerBan> {
+        self.banned_players
+            .iter()
+            .find(|b| parse_uuid(&b.uuid) == Some(uuid))
+    }
+    pub fn ip_ban(&self, ip: IpAddr) -> Option<&IpBan> {
+        self.banned_ips
+            .iter()
+            .find(|b| b.ip.parse::<IpAddr>() == Ok(ip))
+    }
+    pub fn ban_player(&mut self, uuid: u128, name: &str, details: BanDetails) {
+        self.banned_players
+            .retain(|b| parse_uuid(&b.uuid) != Some(uuid));
+        self.banned_players.push(PlayerBan {
+            uuid: uuid_string(uuid),
+            name: name.to_string(),
+            details,
+        });
+        self.save();
+    }
+    pub fn ban_ip(&mut self, ip: IpAddr, details: BanDetails) {
+        self.banned_ips.retain(|b| b.ip.parse::<IpAddr>() != Ok(ip));
+        self.banned_ips.push(IpBan {
+            ip: ip.to_string(),
+            details,
+        });
+        self.save();
+    }
+    /// Returns false if the player wasn't banned.
+    pub fn pardon_player(&mut self, uuid: u128) -> bool {
+        let before = self.banned_players.len();
+        self.banned_players
+            .retain(|b| parse_uuid(&b.uuid) != Some(uuid));
+        self.save();
+        before != self.banned_players.len()
+    }
+    /// Returns false if the address wasn't banned.
+    pub fn pardon_ip(&mut self, ip: IpAddr) -> bool {
+        let before = self.banned_ips.len();
+        self.banned_ips.retain(|b| b.ip.parse::<IpAddr>() != Ok(ip));
+        self.save();
+        before != self.banned_ips.len()
+    }
+}
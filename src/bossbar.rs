@@ -0,0 +1,147 @@
+//! Boss bars shown at the top of the screen, for anything from boss health to countdowns.
+//! Fields are changed directly, and the server turns the changes into packets once per tick.
+
+use std::collections::HashSet;
+
+use crate::packets::play::BossBar as BossBarPacket;
+use crate::packets::play::{BossBarAction, BossBarColor, BossBarDivision};
+
+/// Everything about a bar the clients keep a copy of.
+#[derive(Debug, Clone, PartialEq)]
+struct Sent {
+    title: String,
+    progress: f32,
+    color: BossBarColor,
+    division: BossBarDivision,
+    flags: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct BossBar {
+    pub uuid: u128,
+    /// A json text component.
+    pub title: String,
+    /// From 0 to 1.
+    pub progress: f32,
+    pub color: BossBarColor,
+    pub division: BossBarDivision,
+    pub darken_sky: bool,
+    /// Plays the boss music, which only the end has.
+    pub play_music: bool,
+    pub create_fog: bool,
+    players: HashSet<usize>,
+    /// Players that were added since the last update and still need the whole bar.
+    joined: HashSet<usize>,
+    /// Players that were removed since the last update and still have the bar.
+    left: HashSet<usize>,
+    sent: Option<Sent>,
+}
+impl BossBar {
+    pub fn new(title: String) -> Self {
+        Self {
+            uuid: rand::random(),
+            title,
+            progress: 1.0,
+            color: BossBarColor::Purple,
+            division: BossBarDivision::None,
+            darken_sky: false,
+            play_music: false,
+            create_fog: false,
+            players: HashSet::new(),
+            joined: HashSet::new(),
+            left: HashSet::new(),
+            sent: None,
+        }
+    }
+    pub fn players(&self) -> impl Iterator<Item = usize> + '_ {
+        self.players.iter().copied()
+    }
+    /// Returns false if the player already sees the bar.
+    pub fn add_player(&mut self, index: usize) -> bool {
+        if !self.players.insert(index) {
+            return false;
+        }
+        self.joined.insert(index);
+        true
+    }
+    /// Returns false if the player didn't see the bar.
+    pub fn remove_player(&mut self, index: usize) -> bool {
+        if !self.players.remove(&index) {
+            return false;
+        }
+        if !self.joined.remove(&index) {
+            self.left.insert(index);
+        }
+        true
+    }
+    pub fn remove_all_players(&mut self) {
+        for index in self.players.clone() {
+            self.remove_player(index);
+        }
+    }
+    /// Drops a player that disconnected, without telling anyone.
+    pub fn forget(&mut self, index: usize) {
+        self.players.remove(&index);
+        self.joined.remove(&index);
+        self.left.remove(&index);
+    }
+    fn state(&self) -> Sent {
+        Sent {
+            title: self.title.clone(),
+            progress: self.progress.clamp(0.0, 1.0),
+            color: self.color,
+            division: self.division,
+            flags: self.darken_sky as u8
+                | (self.play_music as u8) << 1
+                | (self.create_fog as u8) << 2,
+        }
+    }
+    /// The packets that bring every player up to date, and who they're for. New players get
+    /// the whole bar, the others only what changed since the last call.
+    pub fn updates(&mut self) -> Vec<(usize, BossBarPacket)> {
+        let state = self.state();
+        let mut actions = vec![];
+        if let Some(sent) = &self.sent {
+            if sent.title != state.title {
+                actions.push(BossBarAction::UpdateTitle(state.title.clone()));
+            }
+            if sent.progress != state.progress {
+                actions.push(BossBarAction::UpdateHealth(state.progress));
+            }
+            if (sent.color, sent.division) != (state.color, state.division) {
+                actions.push(BossBarAction::UpdateStyle(state.color, state.division));
+            }
+            if sent.flags != state.flags {
+                actions.push(BossBarAction::UpdateFlags(state.flags));
+            }
+        }
+        let packet = |action| BossBarPacket {
+            uuid: self.uuid,
+            action,
+        };
+        let mut packets = vec![];
+        for index in &self.left {
+            packets.push((*index, packet(BossBarAction::Remove)));
+        }
+        for index in &self.players {
+            if self.joined.contains(index) {
+                let add = BossBarAction::Add {
+                    title: state.title.clone(),
+                    health: state.progress,
+                    color: state.color,
+                    division: state.division,
+                    flags: state.flags,
+                };
+                packets.push((*index, packet(add)));
+            } else {
+                for action in &actions {
+                    packets.push((*index, packet(action.clone())));
+                }
+            }
+        }
+        self.joined.clear();
+        self.left.clear();
+        self.sent = Some(state);
+        packets
+    }
+}
@@ -0,0 +1,1359 @@
+//! The commands every server has.
+
+use std::net::IpAddr;
+
+use rand::Rng;
+use serde_json::{json, Value};
+
+use super::{
+    argument, command, literal, translate, ArgumentKind, CommandError, Context, Dispatcher,
+};
+use crate::access::BanDetails;
+use crate::bossbar::BossBar;
+use crate::entity::offline_uuid;
+use crate::packet::Slot;
+use crate::packets::play::{BossBarColor, BossBarDivision, ChatPosition, Title};
+use crate::permissions::DEFAULT_OP_LEVEL;
+use crate::player::{Gamemode, Player};
+use crate::scoreboard::{
+    DisplaySlot, COLORS as COLOR_NAMES, MAX_ENTRY_LENGTH, MAX_NAME_LENGTH, VISIBILITIES,
+};
+use crate::server::{Server, MAX_PLAYERS};
+use crate::world::time::TICKS_PER_DAY;
+use crate::world::{block, GameRules};
+
+/// Vanilla refuses to teleport further out than this.
+const MAX_COORDINATE: f64 = 30_000_000.0;
+/// Weather set without a duration lasts between 5 and 15 minutes, in ticks.
+const WEATHER_DURATION: std::ops::Range<i32> = 6000..18000;
+
+pub fn register(dispatcher: &mut Dispatcher) {
+    let coordinate = ArgumentKind::Float {
+        min: -MAX_COORDINATE,
+        max: MAX_COORDINATE,
+    };
+    let coordinates = |executor| {
+        argument("x", coordinate)
+            .then(argument("y", coordinate).then(argument("z", coordinate).executes(executor)))
+    };
+    dispatcher.register(
+        command("tp", 2)
+            .then(
+                argument("target", ArgumentKind::Player)
+                    .executes(tp)
+                    .then(argument("destination", ArgumentKind::Player).executes(tp))
+                    .then(coordinates(tp)),
+            )
+            .then(coordinates(tp)),
+    );
+    dispatcher.register(
+        command("gamemode", 2).then(
+            argument("mode", ArgumentKind::Word)
+                .executes(gamemode)
+                .then(argument("player", ArgumentKind::Player).executes(gamemode)),
+        ),
+    );
+    dispatcher.register(
+        command("give", 2).then(
+            argument("player", ArgumentKind::Player).then(
+                argument("item", ArgumentKind::BlockState)
+                    .executes(give)
+                    .then(
+                        argument("count", ArgumentKind::Integer { min: 1, max: 64 }).executes(give),
+                    ),
+            ),
+        ),
+    );
+    dispatcher.register(
+        command("kick", 3).then(
+            argument("player", ArgumentKind::Player)
+                .executes(kick)
+                .then(argument("reason", ArgumentKind::GreedyString).executes(kick)),
+        ),
+    );
+    dispatcher.register(command("list", 0).executes(list));
+    dispatcher.register(
+        command("whitelist", 3)
+            .then(literal("on").executes(whitelist_on))
+            .then(literal("off").executes(whitelist_off))
+            .then(literal("list").executes(whitelist_list))
+            .then(literal("add").then(argument("name", ArgumentKind::Word).executes(whitelist_add)))
+            .then(
+                literal("remove")
+                    .then(argument("name", ArgumentKind::Word).executes(whitelist_remove)),
+            )
+            .then(literal("reload").executes(whitelist_reload)),
+    );
+    dispatcher.register(
+        command("ban", 3).then(
+            argument("name", ArgumentKind::Word)
+                .executes(ban)
+                .then(argument("reason", ArgumentKind::GreedyString).executes(ban)),
+        ),
+    );
+    dispatcher.register(
+        command("ban-ip", 3).then(
+            argument("target", ArgumentKind::Word)
+                .executes(ban_ip)
+                .then(argument("reason", ArgumentKind::GreedyString).executes(ban_ip)),
+        ),
+    );
+    dispatcher
+        .register(command("pardon", 3).then(argument("name", ArgumentKind::Word).executes(pardon)));
+    dispatcher.register(
+        command("pardon-ip", 3).then(argument("address", ArgumentKind::Word).executes(pardon_ip)),
+    );
+    dispatcher
+        .register(command("op", 3).then(argument("player", ArgumentKind::Player).executes(op)));
+    dispatcher
+        .register(command("deop", 3).then(argument("player", ArgumentKind::Player).executes(deop)));
+    let time = ArgumentKind::Integer {
+        min: 0,
+        max: i32::MAX,
+    };
+    dispatcher.register(
+        command("time", 2)
+            .then(
+                literal("set")
+                    .then(literal("day").executes(|s, c| set_time(s, c, 1000)))
+                    .then(literal("noon").executes(|s, c| set_time(s, c, 6000)))
+                    .then(literal("night").executes(|s, c| set_time(s, c, 13000)))
+                    .then(literal("midnight").executes(|s, c| set_time(s, c, 18000)))
+                    .then(
+                        argument("time", time)
+                            .executes(|s, c| set_time(s, c, c.integer("time").unwrap() as i64)),
+                    ),
+            )
+            .then(literal("add").then(argument("time", time).executes(add_time)))
+            .then(
+                literal("query")
+                    .then(literal("daytime").executes(|s, c| query_time(s, c, TimeQuery::DayTime)))
+                    .then(
+                        literal("gametime").executes(|s, c| query_time(s, c, TimeQuery::GameTime)),
+                    )
+                    .then(literal("day").executes(|s, c| query_time(s, c, TimeQuery::Day))),
+            ),
+    );
+    let duration = ArgumentKind::Integer {
+        min: 1,
+        max: 1_000_000,
+    };
+    dispatcher.register(
+        command("weather", 2)
+            .then(
+                literal("clear")
+                    .executes(|s, c| weather(s, c, Forecast::Clear))
+                    .then(
+                        argument("duration", duration)
+                            .executes(|s, c| weather(s, c, Forecast::Clear)),
+                    ),
+            )
+            .then(
+                literal("rain")
+                    .executes(|s, c| weather(s, c, Forecast::Rain))
+                    .then(
+                        argument("duration", duration)
+                            .executes(|s, c| weather(s, c, Forecast::Rain)),
+                    ),
+            )
+            .then(
+                literal("thunder")
+                    .executes(|s, c| weather(s, c, Forecast::Thunder))
+                    .then(
+                        argument("duration", duration)
+                            .executes(|s, c| weather(s, c, Forecast::Thunder)),
+                    ),
+            ),
+    );
+    dispatcher.register(
+        command("gamerule", 2).executes(gamerule).then(
+            argument("rule", ArgumentKind::Word)
+                .executes(gamerule)
+                .then(argument("value", ArgumentKind::Word).executes(gamerule)),
+        ),
+    );
+    dispatcher.register(
+        command("setblock", 2).then(
+            argument("position", ArgumentKind::BlockPosition)
+                .then(argument("block", ArgumentKind::BlockState).executes(setblock)),
+        ),
+    );
+    let text = |executor| argument("text", ArgumentKind::GreedyString).executes(executor);
+    let ticks = || ArgumentKind::Integer {
+        min: 0,
+        max: i32::MAX,
+    };
+    dispatcher.register(
+        command("title", 2).then(
+            argument("player", ArgumentKind::Player)
+                .then(literal("clear").executes(|s, c| title(s, c, Player::clear_title)))
+                .then(literal("reset").executes(|s, c| title(s, c, Player::reset_title)))
+                .then(literal("title").then(text(|s, c| {
+                    title_text(s, c, |p, text| p.show_title(text, None, None))
+                })))
+                .then(literal("subtitle").then(text(|s, c| {
+                    title_text(s, c, |p, text| {
+                        p.titles.push(Title::Subtitle(text.to_string()))
+                    })
+                })))
+                .then(
+                    literal("actionbar")
+                        .then(text(|s, c| title_text(s, c, Player::show_action_bar))),
+                )
+                .then(literal("times").then(argument("fadeIn", ticks()).then(
+                    argument("stay", ticks()).then(argument("fadeOut", ticks()).executes(
+                        |s, c| {
+                            let times = Title::Times {
+                                fade_in: c.integer("fadeIn").unwrap(),
+                                stay: c.integer("stay").unwrap(),
+                                fade_out: c.integer("fadeOut").unwrap(),
+                            };
+                            title(s, c, |p| p.titles.push(times))
+                        },
+                    )),
+                ))),
+        ),
+    );
+    let bar = || argument("id", ArgumentKind::Word);
+    dispatcher.register(
+        command("bossbar", 2)
+            .then(literal("add").then(
+                bar().then(argument("name", ArgumentKind::GreedyString).executes(add_boss_bar)),
+            ))
+            .then(literal("remove").then(bar().executes(remove_boss_bar)))
+            .then(literal("list").executes(list_boss_bars))
+            .then(literal("get").then(bar().then(literal("players").executes(boss_bar_players))))
+            .then(
+                literal("set").then(
+                    bar()
+                        .then(literal("name").then(
+                            argument("name", ArgumentKind::GreedyString).executes(set_boss_bar),
+                        ))
+                        .then(
+                            literal("color")
+                                .then(argument("color", ArgumentKind::Word).executes(set_boss_bar)),
+                        )
+                        .then(
+                            literal("style")
+                                .then(argument("style", ArgumentKind::Word).executes(set_boss_bar)),
+                        )
+                        .then(
+                            literal("progress").then(
+                                argument("progress", ArgumentKind::Float { min: 0.0, max: 1.0 })
+                                    .executes(set_boss_bar),
+                            ),
+                        ),
+                ),
+            )
+            .then(
+                literal("players").then(
+                    bar()
+                        .then(
+                            literal("add").then(
+                                argument("player", ArgumentKind::Player)
+                                    .executes(|s, c| boss_bar_player(s, c, true)),
+                            ),
+                        )
+                        .then(
+                            literal("remove").then(
+                                argument("player", ArgumentKind::Player)
+                                    .executes(|s, c| boss_bar_player(s, c, false)),
+                            ),
+                        ),
+                ),
+            ),
+    );
+    let objective = || argument("objective", ArgumentKind::Word);
+    let entry = || argument("entry", ArgumentKind::Word);
+    let team = || argument("team", ArgumentKind::Word);
+    let score = |executor| {
+        entry().then(
+            objective().then(
+                argument(
+                    "score",
+                    ArgumentKind::Integer {
+                        min: i32::MIN,
+                        max: i32::MAX,
+                    },
+                )
+                .executes(executor),
+            ),
+        )
+    };
+    let display_name =
+        |executor| argument("displayName", ArgumentKind::GreedyString).executes(executor);
+    dispatcher.register(
+        command("scoreboard", 2)
+            .then(
+                literal("objectives")
+                    .then(literal("list").executes(list_objectives))
+                    .then(
+                        literal("add").then(
+                            objective().then(
+                                argument("criteria", ArgumentKind::Word)
+                                    .executes(add_objective)
+                                    .then(display_name(add_objective)),
+                            ),
+                        ),
+                    )
+                    .then(
+                        literal("modify").then(
+                            objective()
+                                .then(literal("displayname").then(display_name(rename_objective))),
+                        ),
+                    )
+                    .then(literal("remove").then(objective().executes(remove_objective)))
+                    .then(
+                        literal("setdisplay").then(
+                            argument("slot", ArgumentKind::Word)
+                                .executes(set_display)
+                                .then(objective().executes(set_display)),
+                        ),
+                    ),
+            )
+            .then(
+                literal("players")
+                    .then(literal("list").then(entry().executes(list_scores)))
+                    .then(literal("set").then(score(|s, c| change_score(s, c, ScoreChange::Set))))
+                    .then(literal("add").then(score(|s, c| change_score(s, c, ScoreChange::Add))))
+                    .then(
+                        literal("remove")
+                            .then(score(|s, c| change_score(s, c, ScoreChange::Remove))),
+                    )
+                    .then(
+                        literal("reset").then(
+                            entry()
+                                .executes(reset_scores)
+                                .then(objective().executes(reset_scores)),
+                        ),
+                    ),
+            )
+            .then(
+                literal("teams")
+                    .then(
+                        literal("list")
+                            .executes(list_teams)
+                            .then(team().executes(list_teams)),
+                    )
+                    .then(
+                        literal("add").then(team().executes(add_team).then(display_name(add_team))),
+                    )
+                    .then(literal("remove").then(team().executes(remove_team)))
+                    .then(
+                        literal("join")
+                            .then(team().executes(join_team).then(entry().executes(join_team))),
+                    )
+                    .then(
+                        literal("leave")
+                            .executes(leave_team)
+                            .then(entry().executes(leave_team)),
+                    )
+                    .then(
+                        literal("option").then(
+                            team().then(
+                                argument("option", ArgumentKind::Word).then(
+                                    argument("value", ArgumentKind::Word).executes(team_option),
+                                ),
+                            ),
+                        ),
+                    ),
+            ),
+    );
+    dispatcher.register(
+        command("say", 1).then(argument("message", ArgumentKind::GreedyString).executes(say)),
+    );
+    dispatcher.register(command("save-all", 4).executes(save_all));
+}
+
+fn name(server: &Server, index: usize) -> String {
+    server
+        .player(index)
+        .map(|p| p.name.clone())
+        .unwrap_or_default()
+}
+
+/// `/tp <destination>`, `/tp <x> <y> <z>`, and both of those with the player to move first.
+fn tp(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    // With a single player argument, that player is where the sender goes.
+    let (target, destination) = match (context.player("target"), context.player("destination")) {
+        (Some(destination), None) if context.float("x").is_none() => {
+            (context.sender, Some(destination))
+        }
+        (target, destination) => (target.unwrap_or(context.sender), destination),
+    };
+    let mut location = server.player(target).unwrap().location;
+    let message = match destination {
+        Some(destination) => {
+            location = server.player(destination).unwrap().location;
+            translate(
+                "commands.tp.success",
+                vec![
+                    name(server, target).into(),
+                    name(server, destination).into(),
+                ],
+            )
+        }
+        None => {
+            location.x = context.float("x").unwrap();
+            location.y = context.float("y").unwrap();
+            location.z = context.float("z").unwrap();
+            translate(
+                "commands.tp.success.coordinates",
+                vec![
+                    name(server, target).into(),
+                    format!("{:.2}", location.x).into(),
+                    format!("{:.2}", location.y).into(),
+                    format!("{:.2}", location.z).into(),
+                ],
+            )
+        }
+    };
+    server.teleport(target, location);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn gamemode(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let mode = context.string("mode").unwrap();
+    let gamemode = Gamemode::from_name(mode).ok_or_else(|| {
+        CommandError::translate("commands.generic.parameter.invalid", vec![mode.into()])
+    })?;
+    let target = context.player("player").unwrap_or(context.sender);
+    server.set_gamemode(target, gamemode);
+    let mode_name = json!({ "translate": format!("gameMode.{}", gamemode.name()) });
+    server.send_message(
+        target,
+        &translate("gameMode.changed", vec![mode_name.clone()]).to_string(),
+    );
+    if target != context.sender {
+        let message = translate(
+            "commands.gamemode.success.other",
+            vec![name(server, target).into(), mode_name],
+        );
+        server.send_message(context.sender, &message.to_string());
+    }
+    Ok(())
+}
+
+/// Gives items by block name or numeric id, the meta of the state is the item's damage.
+fn give(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let target = context.player("player").unwrap();
+    let state = context.block_state("item").unwrap();
+    let count = context.integer("count").unwrap_or(1);
+    let mut stack = Slot::new(
+        block::id(state) as i16,
+        count as i8,
+        block::meta(state) as i16,
+    );
+    server.give(target, &mut stack);
+    let message = translate(
+        "commands.give.success",
+        vec![
+            block::get(state).name.into(),
+            (count - stack.count.max(0) as i32).into(),
+            name(server, target).into(),
+        ],
+    );
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn kick(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let target = context.player("player").unwrap();
+    let target_name = name(server, target);
+    let message = match context.string("reason") {
+        Some(reason) => {
+            server.kick(target, &json!({ "text": reason }).to_string());
+            translate(
+                "commands.kick.success.reason",
+                vec![target_name.into(), reason.into()],
+            )
+        }
+        None => {
+            server.kick(
+                target,
+                &translate("multiplayer.disconnect.kicked", vec![]).to_string(),
+            );
+            translate("commands.kick.success", vec![target_name.into()])
+        }
+    };
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn list(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let mut names: Vec<String> = server.players().map(|(_, p)| p.name.clone()).collect();
+    names.sort();
+    let header = translate(
+        "commands.players.list",
+        vec![names.len().into(), MAX_PLAYERS.into()],
+    );
+    server.send_message(context.sender, &header.to_string());
+    server.send_message(
+        context.sender,
+        &json!({ "text": names.join(", ") }).to_string(),
+    );
+    Ok(())
+}
+
+fn op(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let target = context.player("player").unwrap();
+    server.op(target, DEFAULT_OP_LEVEL);
+    let message = translate("commands.op.success", vec![name(server, target).into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn deop(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let target = context.player("player").unwrap();
+    server.op(target, 0);
+    let message = translate("commands.deop.success", vec![name(server, target).into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn whitelist_on(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    server.access().whitelist_enabled = true;
+    server.send_message(
+        context.sender,
+        &translate("commands.whitelist.enabled", vec![]).to_string(),
+    );
+    Ok(())
+}
+
+fn whitelist_off(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    server.access().whitelist_enabled = false;
+    server.send_message(
+        context.sender,
+        &translate("commands.whitelist.disabled", vec![]).to_string(),
+    );
+    Ok(())
+}
+
+fn whitelist_list(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let names: Vec<String> = server
+        .access()
+        .whitelist
+        .iter()
+        .map(|e| e.name.clone())
+        .collect();
+    let header = translate(
+        "commands.whitelist.list",
+        vec![names.len().into(), names.len().into()],
+    );
+    server.send_message(context.sender, &header.to_string());
+    server.send_message(
+        context.sender,
+        &json!({ "text": names.join(", ") }).to_string(),
+    );
+    Ok(())
+}
+
+fn whitelist_add(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("name").unwrap();
+    if !server.access().add_to_whitelist(offline_uuid(name), name) {
+        return Err(CommandError::translate(
+            "commands.whitelist.add.failed",
+            vec![name.into()],
+        ));
+    }
+    let message = translate("commands.whitelist.add.success", vec![name.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn whitelist_remove(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("name").unwrap();
+    if !server.access().remove_from_whitelist(offline_uuid(name)) {
+        return Err(CommandError::translate(
+            "commands.whitelist.remove.failed",
+            vec![name.into()],
+        ));
+    }
+    let message = translate("commands.whitelist.remove.success", vec![name.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn whitelist_reload(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    server.access().reload_whitelist();
+    server.send_message(
+        context.sender,
+        &translate("commands.whitelist.reloaded", vec![]).to_string(),
+    );
+    Ok(())
+}
+
+/// Bans by name, so players that aren't online can be banned too.
+fn ban(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let banned = context.string("name").unwrap();
+    let details = BanDetails::new(
+        &name(server, context.sender),
+        context.string("reason"),
+        None,
+    );
+    server
+        .access()
+        .ban_player(offline_uuid(banned), banned, details);
+    if let Some(target) = server.player_by_name(banned) {
+        server.kick(
+            target,
+            &translate("multiplayer.disconnect.banned", vec![]).to_string(),
+        );
+    }
+    let message = translate("commands.ban.success", vec![banned.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// Bans an address, or the address an online player is connected from.
+fn ban_ip(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let target = context.string("target").unwrap();
+    let ip = match target.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => server
+            .player_by_name(target)
+            .and_then(|index| server.address(index))
+            .ok_or_else(|| CommandError::translate("commands.banip.invalid", vec![]))?,
+    };
+    let details = BanDetails::new(
+        &name(server, context.sender),
+        context.string("reason"),
+        None,
+    );
+    server.access().ban_ip(ip, details);
+    let banned: Vec<usize> = server
+        .players()
+        .map(|(index, _)| index)
+        .filter(|index| server.address(*index) == Some(ip))
+        .collect();
+    for index in &banned {
+        server.kick(
+            *index,
+            &translate("multiplayer.disconnect.ip_banned", vec![]).to_string(),
+        );
+    }
+    let message = translate("commands.banip.success", vec![ip.to_string().into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn pardon(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("name").unwrap();
+    if !server.access().pardon_player(offline_uuid(name)) {
+        return Err(CommandError::translate(
+            "commands.unban.failed",
+            vec![name.into()],
+        ));
+    }
+    let message = translate("commands.unban.success", vec![name.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn pardon_ip(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let address = context.string("address").unwrap();
+    let ip = address
+        .parse::<IpAddr>()
+        .map_err(|_| CommandError::translate("commands.unbanip.invalid", vec![]))?;
+    if !server.access().pardon_ip(ip) {
+        return Err(CommandError::translate("commands.unbanip.invalid", vec![]));
+    }
+    let message = translate("commands.unbanip.success", vec![address.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn say(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let message = translate(
+        "chat.type.announcement",
+        vec![
+            name(server, context.sender).into(),
+            context.string("message").unwrap().into(),
+        ],
+    );
+    server.broadcast_chat(&message.to_string(), ChatPosition::System);
+    Ok(())
+}
+
+fn save_all(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let start = translate("commands.save.start", vec![]);
+    server.send_message(context.sender, &start.to_string());
+    server.save_all();
+    let success = translate("commands.save.success", vec![]);
+    server.send_message(context.sender, &success.to_string());
+    Ok(())
+}
+
+/// Changes what the target of `/title` is shown.
+fn title(
+    server: &mut Server,
+    context: &Context,
+    show: impl FnOnce(&mut Player),
+) -> Result<(), CommandError> {
+    let player = server
+        .player_mut(context.player("player").unwrap())
+        .unwrap();
+    show(player);
+    let message = translate("commands.title.success", vec![]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+/// `/title <player> title|subtitle|actionbar <json>`, the text has to be a valid component.
+fn title_text(
+    server: &mut Server,
+    context: &Context,
+    show: fn(&mut Player, &str),
+) -> Result<(), CommandError> {
+    let text = context.string("text").unwrap();
+    if let Err(e) = serde_json::from_str::<serde_json::Value>(text) {
+        return Err(CommandError::translate(
+            "commands.tellraw.jsonException",
+            vec![e.to_string().into()],
+        ));
+    }
+    title(server, context, |p| show(p, text))
+}
+
+fn text(text: String) -> String {
+    json!({ "text": text }).to_string()
+}
+fn no_boss_bar(id: &str) -> CommandError {
+    CommandError::Failed(json!({ "text": format!("No bossbar exists with the ID '{}'", id) }))
+}
+const COLORS: [(&str, BossBarColor); 7] = [
+    ("pink", BossBarColor::Pink),
+    ("blue", BossBarColor::Blue),
+    ("red", BossBarColor::Red),
+    ("green", BossBarColor::Green),
+    ("yellow", BossBarColor::Yellow),
+    ("purple", BossBarColor::Purple),
+    ("white", BossBarColor::White),
+];
+const STYLES: [(&str, BossBarDivision); 5] = [
+    ("progress", BossBarDivision::None),
+    ("notched_6", BossBarDivision::Six),
+    ("notched_10", BossBarDivision::Ten),
+    ("notched_12", BossBarDivision::Twelve),
+    ("notched_20", BossBarDivision::Twenty),
+];
+/// Looks a name up in `COLORS` or `STYLES`, failing with the names there are.
+fn choose<T: Copy>(options: &[(&str, T)], name: &str) -> Result<T, CommandError> {
+    match options.iter().find(|(n, _)| *n == name) {
+        Some((_, value)) => Ok(*value),
+        None => Err(CommandError::Usage(
+            options.iter().map(|(n, _)| n.to_string()).collect(),
+        )),
+    }
+}
+
+fn add_boss_bar(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let id = context.string("id").unwrap();
+    let bar = BossBar::new(text(context.string("name").unwrap().to_string()));
+    if !server.add_boss_bar(id, bar) {
+        let message = format!("A bossbar already exists with the ID '{}'", id);
+        return Err(CommandError::Failed(json!({ "text": message })));
+    }
+    let message = format!("Created custom bossbar [{}]", id);
+    server.send_message(context.sender, &text(message));
+    Ok(())
+}
+
+fn remove_boss_bar(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let id = context.string("id").unwrap();
+    server.remove_boss_bar(id).ok_or_else(|| no_boss_bar(id))?;
+    let message = format!("Removed custom bossbar [{}]", id);
+    server.send_message(context.sender, &text(message));
+    Ok(())
+}
+
+fn list_boss_bars(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let mut ids: Vec<&str> = server.boss_bars().keys().map(|id| id.as_str()).collect();
+    ids.sort();
+    let message = match ids.len() {
+        0 => "There are no custom bossbars active".to_string(),
+        n => format!("There are {} custom bossbars active: {}", n, ids.join(", ")),
+    };
+    server.send_message(context.sender, &text(message));
+    Ok(())
+}
+
+/// `/bossbar set <id>` followed by one of `name`, `color`, `style` or `progress`.
+fn set_boss_bar(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let id = context.string("id").unwrap();
+    let bar = server.boss_bar(id).ok_or_else(|| no_boss_bar(id))?;
+    let change = if let Some(name) = context.string("name") {
+        bar.title = text(name.to_string());
+        "name"
+    } else if let Some(color) = context.string("color") {
+        bar.color = choose(&COLORS, color)?;
+        "color"
+    } else if let Some(style) = context.string("style") {
+        bar.division = choose(&STYLES, style)?;
+        "style"
+    } else {
+        bar.progress = context.float("progress").unwrap() as f32;
+        "progress"
+    };
+    let message = format!("Custom bossbar [{}] has changed {}", id, change);
+    server.send_message(context.sender, &text(message));
+    Ok(())
+}
+
+fn boss_bar_players(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let id = context.string("id").unwrap();
+    let bar = server.boss_bars().get(id).ok_or_else(|| no_boss_bar(id))?;
+    let mut names: Vec<String> = bar.players().map(|index| name(server, index)).collect();
+    names.sort();
+    let message = match names.len() {
+        0 => format!("Custom bossbar [{}] has no players currently online", id),
+        n => format!(
+            "Custom bossbar [{}] has {} players currently online: {}",
+            id,
+            n,
+            names.join(", ")
+        ),
+    };
+    server.send_message(context.sender, &text(message));
+    Ok(())
+}
+
+fn boss_bar_player(server: &mut Server, context: &Context, add: bool) -> Result<(), CommandError> {
+    let id = context.string("id").unwrap();
+    let player = context.player("player").unwrap();
+    let target = name(server, player);
+    let bar = server.boss_bar(id).ok_or_else(|| no_boss_bar(id))?;
+    let message = match add {
+        true if bar.add_player(player) => format!("Added {} to custom bossbar [{}]", target, id),
+        false if bar.remove_player(player) => {
+            format!("Removed {} from custom bossbar [{}]", target, id)
+        }
+        true => format!("{} already sees custom bossbar [{}]", target, id),
+        false => format!("{} doesn't see custom bossbar [{}]", target, id),
+    };
+    server.send_message(context.sender, &text(message));
+    Ok(())
+}
+
+fn set_time(server: &mut Server, context: &Context, time: i64) -> Result<(), CommandError> {
+    server.world().clock.day_time = time;
+    server.broadcast_time();
+    let message = translate("commands.time.set", vec![time.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn add_time(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let time = context.integer("time").unwrap();
+    server.world().clock.day_time += time as i64;
+    server.broadcast_time();
+    let message = translate("commands.time.added", vec![time.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+enum TimeQuery {
+    DayTime,
+    GameTime,
+    Day,
+}
+
+fn query_time(
+    server: &mut Server,
+    context: &Context,
+    query: TimeQuery,
+) -> Result<(), CommandError> {
+    let clock = server.world().clock;
+    let value = match query {
+        TimeQuery::DayTime => clock.day_time % TICKS_PER_DAY,
+        TimeQuery::GameTime => clock.age,
+        TimeQuery::Day => clock.day_time / TICKS_PER_DAY,
+    };
+    let message = translate("commands.time.query", vec![value.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+enum Forecast {
+    Clear,
+    Rain,
+    Thunder,
+}
+
+/// `/weather <clear|rain|thunder> [seconds]`.
+fn weather(server: &mut Server, context: &Context, forecast: Forecast) -> Result<(), CommandError> {
+    let duration = match context.integer("duration") {
+        Some(seconds) => seconds * 20,
+        None => rand::thread_rng().gen_range(WEATHER_DURATION),
+    };
+    let key = {
+        let weather = &mut server.world().weather;
+        match forecast {
+            Forecast::Clear => {
+                weather.set_clear(duration);
+                "commands.weather.clear"
+            }
+            Forecast::Rain => {
+                weather.set_rain(duration, false);
+                "commands.weather.rain"
+            }
+            Forecast::Thunder => {
+                weather.set_rain(duration, true);
+                "commands.weather.thunder"
+            }
+        }
+    };
+    server.send_message(context.sender, &translate(key, vec![]).to_string());
+    Ok(())
+}
+
+/// Lists the rules, shows one, or sets one to `true` or `false`.
+fn gamerule(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let Some(rule) = context.string("rule") else {
+        let names = GameRules::NAMES.join(", ");
+        server.send_message(context.sender, &json!({ "text": names }).to_string());
+        return Ok(());
+    };
+    let mut world = server.world();
+    let Some(current) = world.rules.get_mut(rule) else {
+        return Err(CommandError::translate(
+            "commands.gamerule.norule",
+            vec![rule.into()],
+        ));
+    };
+    let Some(value) = context.string("value") else {
+        let message = json!({ "text": format!("{} = {}", rule, current) });
+        drop(world);
+        server.send_message(context.sender, &message.to_string());
+        return Ok(());
+    };
+    *current = value.parse().map_err(|_| {
+        CommandError::translate("commands.generic.boolean.invalid", vec![value.into()])
+    })?;
+    drop(world);
+    if rule == "doDaylightCycle" {
+        // The client needs to know whether to keep advancing the time itself.
+        server.broadcast_time();
+    }
+    let message = translate("commands.gamerule.success", vec![rule.into(), value.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// `/setblock <x> <y> <z> <block>`, which replaces whatever was there without dropping it.
+fn setblock(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let position = context.position("position").unwrap();
+    let state = context.block_state("block").unwrap();
+    let loaded = server.world().is_loaded(position.x, position.y, position.z);
+    if !loaded {
+        return Err(CommandError::translate(
+            "commands.setblock.outOfWorld",
+            vec![],
+        ));
+    }
+    server.set_block(position, state);
+    let message = translate("commands.setblock.success", vec![]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn no_objective(name: &str) -> CommandError {
+    CommandError::translate("commands.scoreboard.objectiveNotFound", vec![name.into()])
+}
+fn no_team(name: &str) -> CommandError {
+    CommandError::translate("commands.scoreboard.teamNotFound", vec![name.into()])
+}
+
+fn list_objectives(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let objectives = server.scoreboard().objectives().to_vec();
+    if objectives.is_empty() {
+        return Err(CommandError::translate(
+            "commands.scoreboard.objectives.list.empty",
+            vec![],
+        ));
+    }
+    let header = translate(
+        "commands.scoreboard.objectives.list.count",
+        vec![objectives.len().into()],
+    );
+    server.send_message(context.sender, &header.to_string());
+    for objective in objectives {
+        let entry = translate(
+            "commands.scoreboard.objectives.list.entry",
+            vec![
+                objective.name.into(),
+                objective.display_name.into(),
+                objective.criteria.into(),
+            ],
+        );
+        server.send_message(context.sender, &entry.to_string());
+    }
+    Ok(())
+}
+
+/// Only `dummy` objectives can be added, since nothing but commands and plugins sets scores.
+fn add_objective(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("objective").unwrap();
+    let criteria = context.string("criteria").unwrap();
+    if criteria != "dummy" {
+        return Err(CommandError::translate(
+            "commands.scoreboard.objectives.add.wrongType",
+            vec![criteria.into()],
+        ));
+    }
+    let scoreboard = server.scoreboard();
+    if scoreboard.objective(name).is_some() {
+        return Err(CommandError::translate(
+            "commands.scoreboard.objectives.add.alreadyExists",
+            vec![name.into()],
+        ));
+    }
+    let display_name = context.string("displayName").unwrap_or(name);
+    if !scoreboard.add_objective(name, display_name, "integer") {
+        return Err(CommandError::translate(
+            "commands.scoreboard.objectives.add.tooLong",
+            vec![name.into(), MAX_NAME_LENGTH.into()],
+        ));
+    }
+    let message = translate(
+        "commands.scoreboard.objectives.add.success",
+        vec![name.into()],
+    );
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn rename_objective(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("objective").unwrap();
+    let display_name = context.string("displayName").unwrap();
+    let scoreboard = server.scoreboard();
+    if scoreboard.objective(name).is_none() {
+        return Err(no_objective(name));
+    }
+    scoreboard.set_objective_display_name(name, display_name);
+    let message = format!(
+        "Changed objective {} display name to {}",
+        name, display_name
+    );
+    server.send_message(context.sender, &text(message));
+    Ok(())
+}
+
+fn remove_objective(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("objective").unwrap();
+    if !server.scoreboard().remove_objective(name) {
+        return Err(no_objective(name));
+    }
+    let message = translate(
+        "commands.scoreboard.objectives.remove.success",
+        vec![name.into()],
+    );
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// `/scoreboard objectives setdisplay <slot> [objective]`, clearing the slot without one.
+fn set_display(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let slot_name = context.string("slot").unwrap();
+    let slot = match slot_name {
+        "list" => DisplaySlot::List,
+        "sidebar" => DisplaySlot::Sidebar,
+        "belowName" => DisplaySlot::BelowName,
+        _ => {
+            return Err(CommandError::translate(
+                "commands.scoreboard.objectives.setdisplay.invalidSlot",
+                vec![slot_name.into()],
+            ))
+        }
+    };
+    let objective = context.string("objective");
+    let scoreboard = server.scoreboard();
+    if let Some(name) = objective.filter(|name| scoreboard.objective(name).is_none()) {
+        return Err(no_objective(name));
+    }
+    scoreboard.set_display(slot, objective);
+    let message = match objective {
+        Some(name) => translate(
+            "commands.scoreboard.objectives.setdisplay.successSet",
+            vec![slot_name.into(), name.into()],
+        ),
+        None => translate(
+            "commands.scoreboard.objectives.setdisplay.successCleared",
+            vec![slot_name.into()],
+        ),
+    };
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// Lists every score an entry has.
+fn list_scores(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let entry = context.string("entry").unwrap();
+    let scoreboard = server.scoreboard();
+    let scores: Vec<(String, i32)> = scoreboard
+        .objectives()
+        .iter()
+        .filter_map(|o| Some((o.display_name.clone(), scoreboard.score(entry, &o.name)?)))
+        .collect();
+    if scores.is_empty() {
+        return Err(CommandError::translate(
+            "commands.scoreboard.players.list.player.empty",
+            vec![entry.into()],
+        ));
+    }
+    let header = translate(
+        "commands.scoreboard.players.list.player.count",
+        vec![scores.len().into(), entry.into()],
+    );
+    server.send_message(context.sender, &header.to_string());
+    for (display_name, value) in scores {
+        let line = translate(
+            "commands.scoreboard.players.list.player.entry",
+            vec![value.into(), display_name.into(), entry.into()],
+        );
+        server.send_message(context.sender, &line.to_string());
+    }
+    Ok(())
+}
+
+enum ScoreChange {
+    Set,
+    Add,
+    Remove,
+}
+
+fn change_score(
+    server: &mut Server,
+    context: &Context,
+    change: ScoreChange,
+) -> Result<(), CommandError> {
+    let entry = context.string("entry").unwrap();
+    let objective = context.string("objective").unwrap();
+    let amount = context.integer("score").unwrap();
+    let scoreboard = server.scoreboard();
+    if scoreboard.objective(objective).is_none() {
+        return Err(no_objective(objective));
+    }
+    let changed = match change {
+        ScoreChange::Set => scoreboard.set_score(entry, objective, amount),
+        ScoreChange::Add => scoreboard.add_score(entry, objective, amount),
+        ScoreChange::Remove => scoreboard.add_score(entry, objective, amount.saturating_neg()),
+    };
+    if !changed {
+        return Err(CommandError::translate(
+            "commands.scoreboard.players.name.tooLong",
+            vec![entry.into(), MAX_ENTRY_LENGTH.into()],
+        ));
+    }
+    let value = scoreboard.score(entry, objective).unwrap();
+    let message = translate(
+        "commands.scoreboard.players.set.success",
+        vec![objective.into(), entry.into(), value.into()],
+    );
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// Resets one score of an entry, or all of them.
+fn reset_scores(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let entry = context.string("entry").unwrap();
+    let scoreboard = server.scoreboard();
+    let message = match context.string("objective") {
+        Some(objective) => {
+            if scoreboard.objective(objective).is_none() {
+                return Err(no_objective(objective));
+            }
+            scoreboard.remove_score(entry, objective);
+            translate(
+                "commands.scoreboard.players.resetscore.success",
+                vec![objective.into(), entry.into()],
+            )
+        }
+        None => {
+            let names: Vec<String> = scoreboard
+                .objectives()
+                .iter()
+                .map(|o| o.name.clone())
+                .collect();
+            for objective in names {
+                scoreboard.remove_score(entry, &objective);
+            }
+            translate(
+                "commands.scoreboard.players.reset.success",
+                vec![entry.into()],
+            )
+        }
+    };
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// Lists the teams, or the entries on one team.
+fn list_teams(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let lines: Vec<Value> = match context.string("team") {
+        Some(name) => {
+            let team = server
+                .scoreboard()
+                .team(name)
+                .ok_or_else(|| no_team(name))?;
+            vec![
+                translate(
+                    "commands.scoreboard.teams.list.player.count",
+                    vec![team.entries.len().into(), name.into()],
+                ),
+                json!({ "text": team.entries.join(", ") }),
+            ]
+        }
+        None => {
+            let teams = server.scoreboard().teams();
+            if teams.is_empty() {
+                return Err(CommandError::translate(
+                    "commands.scoreboard.teams.list.empty",
+                    vec![],
+                ));
+            }
+            let header = translate(
+                "commands.scoreboard.teams.list.count",
+                vec![teams.len().into()],
+            );
+            let entries = teams.iter().map(|team| {
+                translate(
+                    "commands.scoreboard.teams.list.entry",
+                    vec![
+                        team.name.clone().into(),
+                        team.display_name.clone().into(),
+                        team.entries.len().into(),
+                    ],
+                )
+            });
+            std::iter::once(header).chain(entries).collect()
+        }
+    };
+    for line in lines {
+        server.send_message(context.sender, &line.to_string());
+    }
+    Ok(())
+}
+
+fn add_team(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("team").unwrap();
+    let scoreboard = server.scoreboard();
+    if scoreboard.team(name).is_some() {
+        return Err(CommandError::translate(
+            "commands.scoreboard.teams.add.alreadyExists",
+            vec![name.into()],
+        ));
+    }
+    if !scoreboard.add_team(name) {
+        return Err(CommandError::translate(
+            "commands.scoreboard.teams.add.tooLong",
+            vec![name.into(), MAX_NAME_LENGTH.into()],
+        ));
+    }
+    if let Some(display_name) = context.string("displayName") {
+        scoreboard.update_team(name, |team| team.display_name = display_name.to_string());
+    }
+    let message = translate("commands.scoreboard.teams.add.success", vec![name.into()]);
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+fn remove_team(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("team").unwrap();
+    if !server.scoreboard().remove_team(name) {
+        return Err(no_team(name));
+    }
+    let message = translate(
+        "commands.scoreboard.teams.remove.success",
+        vec![name.into()],
+    );
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// Puts an entry, or whoever runs the command, on a team.
+fn join_team(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let team = context.string("team").unwrap();
+    let entry = context
+        .string("entry")
+        .map_or_else(|| name(server, context.sender), str::to_string);
+    let scoreboard = server.scoreboard();
+    if scoreboard.team(team).is_none() {
+        return Err(no_team(team));
+    }
+    if !scoreboard.join_team(team, &entry) {
+        return Err(CommandError::translate(
+            "commands.scoreboard.teams.join.failure",
+            vec![1.into(), team.into(), entry.into()],
+        ));
+    }
+    let message = translate(
+        "commands.scoreboard.teams.join.success",
+        vec![1.into(), team.into(), entry.into()],
+    );
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// Takes an entry, or whoever runs the command, off their team.
+fn leave_team(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let entry = context
+        .string("entry")
+        .map_or_else(|| name(server, context.sender), str::to_string);
+    if !server.scoreboard().leave_team(&entry) {
+        return Err(CommandError::translate(
+            "commands.scoreboard.teams.leave.failure",
+            vec![1.into(), entry.into()],
+        ));
+    }
+    let message = translate(
+        "commands.scoreboard.teams.leave.success",
+        vec![1.into(), entry.into()],
+    );
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
+
+/// `/scoreboard teams option <team> <option> <value>`, with the options vanilla 1.12 has.
+fn team_option(server: &mut Server, context: &Context) -> Result<(), CommandError> {
+    let name = context.string("team").unwrap();
+    let option = context.string("option").unwrap();
+    let value = context.string("value").unwrap().to_string();
+    let collision_rules = VISIBILITIES.map(|v| v.replace("hideFor", "push"));
+    let allowed: Vec<String> = match option {
+        "color" => COLOR_NAMES.iter().chain(["reset"].iter()).map(|c| c.to_string()).collect(),
+        "friendlyfire" | "seeFriendlyInvisibles" => vec!["true".into(), "false".into()],
+        "nametagVisibility" | "deathMessageVisibility" => {
+            VISIBILITIES.iter().map(|v| v.to_string()).collect()
+        }
+        "collisionRule" => collision_rules.to_vec(),
+        _ => {
+            return Err(CommandError::Usage(vec![
+                "/scoreboard teams option <team> <color|friendlyfire|seeFriendlyInvisibles|nametagVisibility|deathMessageVisibility|collisionRule> <value>".to_string(),
+            ]))
+        }
+    };
+    if !allowed.contains(&value) {
+        return Err(CommandError::translate(
+            "commands.scoreboard.teams.option.noValue",
+            vec![option.into(), allowed.join(", ").into()],
+        ));
+    }
+    let changed = server.scoreboard().update_team(name, |team| match option {
+        "color" => team.color = value.clone(),
+        "friendlyfire" => team.friendly_fire = value == "true",
+        "seeFriendlyInvisibles" => team.see_friendly_invisibles = value == "true",
+        "nametagVisibility" => team.name_tag_visibility = value.clone(),
+        "deathMessageVisibility" => team.death_message_visibility = value.clone(),
+        _ => team.collision_rule = value.clone(),
+    });
+    if !changed {
+        return Err(no_team(name));
+    }
+    let message = translate(
+        "commands.scoreboard.teams.option.success",
+        vec![option.into(), name.into(), value.into()],
+    );
+    server.send_message(context.sender, &message.to_string());
+    Ok(())
+}
@@ -0,0 +1,600 @@
+//! Commands are trees of literal and argument nodes, built the way brigadier builds them. The
+//! same tree drives parsing, tab completion and the graph sent to 1.13+ clients.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use minecraft_varint::VarIntWrite;
+use serde_json::{json, Value};
+
+use crate::packet::Position;
+use crate::packets::play::{CommandNode, CommandNodeKind, DeclareCommands};
+use crate::server::Server;
+use crate::world::block::{self, BlockState, BLOCKS};
+
+pub mod builtin;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArgumentKind {
+    Integer {
+        min: i32,
+        max: i32,
+    },
+    Float {
+        min: f64,
+        max: f64,
+    },
+    /// The name of an online player, or `@s` for whoever runs the command.
+    Player,
+    /// Three coordinates, each of which can be relative to the sender with `~`.
+    BlockPosition,
+    /// A block name or numeric id, optionally followed by `:meta`.
+    BlockState,
+    /// A single word.
+    Word,
+    /// Everything up to the end of the line.
+    GreedyString,
+}
+
+#[derive(Debug, Clone)]
+pub enum Argument {
+    Integer(i32),
+    Float(f64),
+    /// Index of the player on the server.
+    Player(usize),
+    BlockPosition(Position),
+    BlockState(BlockState),
+    String(String),
+}
+
+/// Why a command didn't run. Each of these ends up as a red chat message.
+#[derive(Debug, Clone)]
+pub enum CommandError {
+    NotFound,
+    Permission,
+    /// All the ways the command can be used.
+    Usage(Vec<String>),
+    /// A text component explaining what went wrong.
+    Failed(Value),
+}
+impl CommandError {
+    pub fn translate(key: &str, with: Vec<Value>) -> Self {
+        Self::Failed(translate(key, with))
+    }
+    pub fn to_json(&self) -> String {
+        let mut component = match self {
+            Self::NotFound => json!({ "translate": "commands.generic.notFound" }),
+            Self::Permission => json!({ "translate": "commands.generic.permission" }),
+            Self::Usage(usages) => {
+                translate("commands.generic.usage", vec![usages.join(" OR ").into()])
+            }
+            Self::Failed(component) => component.clone(),
+        };
+        component["color"] = "red".into();
+        component.to_string()
+    }
+}
+/// A text component the client fills in from its translations.
+pub fn translate(key: &str, with: Vec<Value>) -> Value {
+    json!({ "translate": key, "with": with })
+}
+
+pub type Executor = fn(&mut Server, &Context) -> Result<(), CommandError>;
+
+/// Who ran a command, and the arguments parsed on the way to the node that runs it.
+#[derive(Debug)]
+pub struct Context {
+    pub sender: usize,
+    args: Vec<(&'static str, Argument)>,
+}
+impl Context {
+    pub fn get(&self, name: &str) -> Option<&Argument> {
+        self.args.iter().find(|(n, _)| *n == name).map(|(_, a)| a)
+    }
+    pub fn integer(&self, name: &str) -> Option<i32> {
+        match self.get(name)? {
+            Argument::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+    pub fn float(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            Argument::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+    pub fn player(&self, name: &str) -> Option<usize> {
+        match self.get(name)? {
+            Argument::Player(index) => Some(*index),
+            _ => None,
+        }
+    }
+    pub fn position(&self, name: &str) -> Option<Position> {
+        match self.get(name)? {
+            Argument::BlockPosition(position) => Some(*position),
+            _ => None,
+        }
+    }
+    pub fn block_state(&self, name: &str) -> Option<BlockState> {
+        match self.get(name)? {
+            Argument::BlockState(state) => Some(*state),
+            _ => None,
+        }
+    }
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            Argument::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum NodeKind {
+    Literal(&'static str),
+    Argument {
+        name: &'static str,
+        kind: ArgumentKind,
+    },
+}
+#[derive(Debug)]
+pub struct Node {
+    kind: NodeKind,
+    children: Vec<Node>,
+    executor: Option<Executor>,
+}
+pub fn literal(name: &'static str) -> Node {
+    Node {
+        kind: NodeKind::Literal(name),
+        children: vec![],
+        executor: None,
+    }
+}
+pub fn argument(name: &'static str, kind: ArgumentKind) -> Node {
+    Node {
+        kind: NodeKind::Argument { name, kind },
+        children: vec![],
+        executor: None,
+    }
+}
+impl Node {
+    pub fn then(mut self, child: Node) -> Self {
+        self.children.push(child);
+        self
+    }
+    /// Makes the command valid when it ends at this node.
+    pub fn executes(mut self, executor: Executor) -> Self {
+        self.executor = Some(executor);
+        self
+    }
+    fn name(&self) -> &'static str {
+        match self.kind {
+            NodeKind::Literal(name) | NodeKind::Argument { name, .. } => name,
+        }
+    }
+    /// How many space separated words this node reads, `None` for the rest of the line.
+    fn words(&self) -> Option<usize> {
+        match self.kind {
+            NodeKind::Argument {
+                kind: ArgumentKind::GreedyString,
+                ..
+            } => None,
+            NodeKind::Argument {
+                kind: ArgumentKind::BlockPosition,
+                ..
+            } => Some(3),
+            _ => Some(1),
+        }
+    }
+    /// `Err(None)` means the words just don't belong to this node, like a different literal.
+    fn parse(
+        &self,
+        server: &Server,
+        sender: usize,
+        words: &[&str],
+    ) -> Result<Option<Argument>, Option<CommandError>> {
+        match self.kind {
+            NodeKind::Literal(name) if words[0].eq_ignore_ascii_case(name) => Ok(None),
+            NodeKind::Literal(_) => Err(None),
+            NodeKind::Argument { kind, .. } => {
+                kind.parse(server, sender, words).map(Some).map_err(Some)
+            }
+        }
+    }
+    fn suggestions(
+        &self,
+        server: &Server,
+        word: usize,
+        prefix: &str,
+        looked_at: Option<Position>,
+    ) -> Vec<String> {
+        let lower = prefix.to_lowercase();
+        let candidates = match self.kind {
+            NodeKind::Literal(name) => vec![name.to_string()],
+            NodeKind::Argument { kind, .. } => match kind {
+                ArgumentKind::Player => server.players().map(|(_, p)| p.name.clone()).collect(),
+                ArgumentKind::BlockState => {
+                    return BLOCKS
+                        .iter()
+                        .filter(|b| {
+                            b.name.starts_with(&lower)
+                                || b.name["minecraft:".len()..].starts_with(&lower)
+                        })
+                        .map(|b| b.name.to_string())
+                        .collect()
+                }
+                ArgumentKind::BlockPosition => match looked_at {
+                    Some(p) => vec![[p.x, p.y, p.z][word].to_string()],
+                    None => vec!["~".to_string()],
+                },
+                _ => vec![],
+            },
+        };
+        candidates
+            .into_iter()
+            .filter(|c| c.to_lowercase().starts_with(&lower))
+            .collect()
+    }
+    /// Every way of continuing from this node, like `<x> <y> <z>`.
+    fn usages(&self) -> Vec<String> {
+        if self.children.is_empty() {
+            return vec![String::new()];
+        }
+        let mut out = vec![];
+        for child in &self.children {
+            let part = match child.kind {
+                NodeKind::Literal(name) => name.to_string(),
+                NodeKind::Argument { name, .. } => format!("<{}>", name),
+            };
+            for rest in child.usages() {
+                let usage = format!("{} {}", part, rest).trim_end().to_string();
+                // Whatever follows a node that can already run the command is optional.
+                out.push(match self.executor {
+                    Some(_) => format!("[{}]", usage),
+                    None => usage,
+                });
+            }
+        }
+        out
+    }
+    fn flatten(&self, nodes: &mut Vec<CommandNode>) -> u32 {
+        let index = nodes.len();
+        let kind = match self.kind {
+            NodeKind::Literal(name) => CommandNodeKind::Literal(name.to_string()),
+            NodeKind::Argument { name, kind } => CommandNodeKind::Argument {
+                name: name.to_string(),
+                parser: kind.parser().to_string(),
+                properties: kind.properties(),
+            },
+        };
+        nodes.push(CommandNode {
+            kind,
+            executable: self.executor.is_some(),
+            children: vec![],
+        });
+        let children = self.children.iter().map(|c| c.flatten(nodes)).collect();
+        nodes[index].children = children;
+        index as u32
+    }
+}
+/// Tries every child in order, so the first one that leads to a complete command wins.
+fn parse_children(
+    node: &Node,
+    server: &Server,
+    context: &mut Context,
+    words: &[&str],
+) -> Result<Executor, Option<CommandError>> {
+    if words.is_empty() {
+        return node.executor.ok_or(None);
+    }
+    let mut error = None;
+    for child in &node.children {
+        let count = child.words().unwrap_or(words.len());
+        if words.len() < count {
+            continue;
+        }
+        let (taken, rest) = words.split_at(count);
+        match child.parse(server, context.sender, taken) {
+            Ok(argument) => {
+                let parsed = context.args.len();
+                if let Some(argument) = argument {
+                    context.args.push((child.name(), argument));
+                }
+                match parse_children(child, server, context, rest) {
+                    Ok(executor) => return Ok(executor),
+                    Err(e) => {
+                        error = error.or(e);
+                        context.args.truncate(parsed);
+                    }
+                }
+            }
+            Err(e) => error = error.or(e),
+        }
+    }
+    Err(error)
+}
+fn suggest_children(
+    node: &Node,
+    server: &Server,
+    sender: usize,
+    words: &[&str],
+    looked_at: Option<Position>,
+    out: &mut Vec<String>,
+) {
+    for child in &node.children {
+        let Some(count) = child.words() else {
+            continue;
+        };
+        if words.len() <= count {
+            let last = words.len() - 1;
+            out.extend(child.suggestions(server, last, words[last], looked_at));
+        } else if child.parse(server, sender, &words[..count]).is_ok() {
+            suggest_children(child, server, sender, &words[count..], looked_at, out);
+        }
+    }
+}
+
+impl ArgumentKind {
+    fn parse(
+        &self,
+        server: &Server,
+        sender: usize,
+        words: &[&str],
+    ) -> Result<Argument, CommandError> {
+        let invalid =
+            |word: &str| CommandError::translate("commands.generic.num.invalid", vec![word.into()]);
+        match *self {
+            ArgumentKind::Integer { min, max } => {
+                let i = words[0].parse::<i32>().map_err(|_| invalid(words[0]))?;
+                check_range(i, min, max)?;
+                Ok(Argument::Integer(i))
+            }
+            ArgumentKind::Float { min, max } => {
+                let f = words[0]
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|f| f.is_finite())
+                    .ok_or_else(|| invalid(words[0]))?;
+                check_range(f, min, max)?;
+                Ok(Argument::Float(f))
+            }
+            ArgumentKind::Player => {
+                let index = match words[0] {
+                    "@s" => Some(sender),
+                    name => server.player_by_name(name),
+                };
+                index.map(Argument::Player).ok_or_else(|| {
+                    CommandError::translate(
+                        "commands.generic.player.notFound",
+                        vec![words[0].into()],
+                    )
+                })
+            }
+            ArgumentKind::BlockPosition => {
+                let base = server
+                    .player(sender)
+                    .map(|p| [p.location.x, p.location.y, p.location.z].map(|c| c.floor() as i32))
+                    .unwrap_or_default();
+                let mut coordinates = [0; 3];
+                for i in 0..3 {
+                    coordinates[i] =
+                        parse_coordinate(words[i], base[i]).ok_or_else(|| invalid(words[i]))?;
+                }
+                let [x, y, z] = coordinates;
+                Ok(Argument::BlockPosition(Position::new(x, y, z)))
+            }
+            ArgumentKind::BlockState => parse_block_state(words[0])
+                .map(Argument::BlockState)
+                .ok_or_else(|| {
+                    CommandError::translate("commands.give.block.notFound", vec![words[0].into()])
+                }),
+            ArgumentKind::Word => Ok(Argument::String(words[0].to_string())),
+            ArgumentKind::GreedyString => Ok(Argument::String(words.join(" "))),
+        }
+    }
+    /// The brigadier parser 1.13+ clients know this argument as.
+    fn parser(&self) -> &'static str {
+        match self {
+            ArgumentKind::Integer { .. } => "brigadier:integer",
+            ArgumentKind::Float { .. } => "brigadier:double",
+            ArgumentKind::Player => "minecraft:entity",
+            ArgumentKind::BlockPosition => "minecraft:block_pos",
+            ArgumentKind::BlockState => "minecraft:block_state",
+            ArgumentKind::Word | ArgumentKind::GreedyString => "brigadier:string",
+        }
+    }
+    fn properties(&self) -> Vec<u8> {
+        let mut v = vec![];
+        match *self {
+            ArgumentKind::Integer { min, max } => {
+                // Both bounds present
+                v.write_u8(0x03).unwrap();
+                v.write_i32::<BigEndian>(min).unwrap();
+                v.write_i32::<BigEndian>(max).unwrap();
+            }
+            ArgumentKind::Float { min, max } => {
+                v.write_u8(0x03).unwrap();
+                v.write_f64::<BigEndian>(min).unwrap();
+                v.write_f64::<BigEndian>(max).unwrap();
+            }
+            // A single player
+            ArgumentKind::Player => v.write_u8(0x01 | 0x02).unwrap(),
+            ArgumentKind::Word => {
+                v.write_var_u32(0).unwrap();
+            }
+            ArgumentKind::GreedyString => {
+                v.write_var_u32(2).unwrap();
+            }
+            ArgumentKind::BlockPosition | ArgumentKind::BlockState => {}
+        }
+        v
+    }
+}
+fn check_range<T: PartialOrd + Into<Value> + ToString>(
+    n: T,
+    min: T,
+    max: T,
+) -> Result<(), CommandError> {
+    if n < min {
+        Err(CommandError::translate(
+            "commands.generic.num.tooSmall",
+            vec![n.to_string().into(), min.to_string().into()],
+        ))
+    } else if n > max {
+        Err(CommandError::translate(
+            "commands.generic.num.tooBig",
+            vec![n.to_string().into(), max.to_string().into()],
+        ))
+    } else {
+        Ok(())
+    }
+}
+/// `~` and `~<offset>` are relative to `base`.
+fn parse_coordinate(word: &str, base: i32) -> Option<i32> {
+    match word.strip_prefix('~') {
+        Some("") => Some(base),
+        Some(offset) => base.checked_add(offset.parse().ok()?),
+        None => word.parse().ok(),
+    }
+}
+/// `stone`, `minecraft:stone`, `1`, and any of those followed by `:<meta>`.
+pub fn parse_block_state(word: &str) -> Option<BlockState> {
+    let (name, meta) = match word.rsplit_once(':') {
+        Some((name, meta)) if meta.parse::<u8>().is_ok() => (name, meta.parse::<u8>().unwrap()),
+        _ => (word, 0),
+    };
+    if meta > 15 {
+        return None;
+    }
+    let id = match name.parse::<u16>() {
+        Ok(id) if id < 4096 => id,
+        Ok(_) => return None,
+        Err(_) => block::by_name(name)?.id,
+    };
+    Some(block::state(id, meta))
+}
+
+pub struct Command {
+    pub name: &'static str,
+    /// Lowest operator level allowed to run the command.
+    pub level: u8,
+    node: Node,
+}
+pub fn command(name: &'static str, level: u8) -> Command {
+    Command {
+        name,
+        level,
+        node: literal(name),
+    }
+}
+impl Command {
+    pub fn then(mut self, child: Node) -> Self {
+        self.node = self.node.then(child);
+        self
+    }
+    pub fn executes(mut self, executor: Executor) -> Self {
+        self.node = self.node.executes(executor);
+        self
+    }
+    /// Permission node that grants this command, like `mycelium.command.tp`.
+    pub fn permission(&self) -> String {
+        format!("mycelium.command.{}", self.name)
+    }
+    pub fn usages(&self) -> Vec<String> {
+        self.node
+            .usages()
+            .into_iter()
+            .map(|usage| format!("/{} {}", self.name, usage).trim_end().to_string())
+            .collect()
+    }
+}
+
+/// Every registered command.
+#[derive(Default)]
+pub struct Dispatcher {
+    commands: Vec<Command>,
+}
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn register(&mut self, command: Command) {
+        self.commands.retain(|c| c.name != command.name);
+        self.commands.push(command);
+    }
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+    /// Parses a command line without the leading `/`, returning what to run and its arguments.
+    pub fn parse(
+        &self,
+        server: &Server,
+        sender: usize,
+        line: &str,
+    ) -> Result<(Executor, Context), CommandError> {
+        let words: Vec<&str> = line.trim_end().split(' ').collect();
+        let command = self.get(words[0]).ok_or(CommandError::NotFound)?;
+        if !server.can_use(sender, command) {
+            return Err(CommandError::Permission);
+        }
+        let mut context = Context {
+            sender,
+            args: vec![],
+        };
+        match parse_children(&command.node, server, &mut context, &words[1..]) {
+            Ok(executor) => Ok((executor, context)),
+            Err(Some(e)) => Err(e),
+            Err(None) => Err(CommandError::Usage(command.usages())),
+        }
+    }
+    /// Candidates for the last word of a partially typed command line, without the leading `/`.
+    pub fn complete(
+        &self,
+        server: &Server,
+        sender: usize,
+        line: &str,
+        looked_at: Option<Position>,
+    ) -> Vec<String> {
+        let words: Vec<&str> = line.split(' ').collect();
+        if words.len() == 1 {
+            let prefix = words[0].to_lowercase();
+            return self
+                .commands
+                .iter()
+                .filter(|c| c.name.starts_with(&prefix) && server.can_use(sender, c))
+                .map(|c| format!("/{}", c.name))
+                .collect();
+        }
+        let Some(command) = self.get(words[0]).filter(|c| server.can_use(sender, c)) else {
+            return vec![];
+        };
+        let mut out = vec![];
+        suggest_children(
+            &command.node,
+            server,
+            sender,
+            &words[1..],
+            looked_at,
+            &mut out,
+        );
+        out.sort();
+        out.dedup();
+        out
+    }
+    /// The command graph for a 1.13+ client, with only the commands the player may use.
+    pub fn graph(&self, server: &Server, sender: usize) -> DeclareCommands {
+        let mut nodes = vec![CommandNode {
+            kind: CommandNodeKind::Root,
+            executable: false,
+            children: vec![],
+        }];
+        let children = self
+            .commands
+            .iter()
+            .filter(|c| server.can_use(sender, c))
+            .map(|c| c.node.flatten(&mut nodes))
+            .collect();
+        nodes[0].children = children;
+        DeclareCommands { nodes, root: 0 }
+    }
+}
@@ -0,0 +1,247 @@
+//! Sounds and particles of protocol 340, for feedback that isn't chat.
+
+use crate::world::block::{self, BlockState};
+
+/// The volume slider a sound is played under.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundCategory {
+    Master = 0,
+    Music = 1,
+    Record = 2,
+    Weather = 3,
+    Block = 4,
+    Hostile = 5,
+    Neutral = 6,
+    Player = 7,
+    Ambient = 8,
+    Voice = 9,
+}
+
+/// Vanilla sounds that are useful as feedback. They are sent by name, which the client looks
+/// up itself, so `Custom` can play anything a resource pack adds as well.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sound {
+    AnvilLand,
+    AnvilUse,
+    ChestOpen,
+    ChestClose,
+    DoorOpen,
+    DoorClose,
+    LeverClick,
+    StoneButtonClick,
+    StoneButtonClickOff,
+    WoodButtonClickOn,
+    WoodButtonClickOff,
+    StonePressurePlateClickOn,
+    StonePressurePlateClickOff,
+    WoodPressurePlateClickOn,
+    WoodPressurePlateClickOff,
+    PistonExtend,
+    PistonContract,
+    RedstoneTorchBurnout,
+    LavaExtinguish,
+    NoteBass,
+    NoteBell,
+    NoteChime,
+    NoteHarp,
+    NotePling,
+    NoteSnare,
+    PortalTravel,
+    PortalTrigger,
+    ExperienceOrbPickup,
+    ItemPickup,
+    ItemBreak,
+    PlayerLevelUp,
+    PlayerHurt,
+    PlayerAttackStrong,
+    FireworkLaunch,
+    FireworkBlast,
+    FireworkTwinkle,
+    GenericExplode,
+    LightningThunder,
+    EndermanTeleport,
+    VillagerYes,
+    VillagerNo,
+    WitherSpawn,
+    EnderDragonGrowl,
+    UiButtonClick,
+    Custom(String),
+}
+impl Sound {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::AnvilLand => "block.anvil.land",
+            Self::AnvilUse => "block.anvil.use",
+            Self::ChestOpen => "block.chest.open",
+            Self::ChestClose => "block.chest.close",
+            Self::DoorOpen => "block.wooden_door.open",
+            Self::DoorClose => "block.wooden_door.close",
+            Self::LeverClick => "block.lever.click",
+            Self::StoneButtonClick => "block.stone_button.click_on",
+            Self::StoneButtonClickOff => "block.stone_button.click_off",
+            Self::WoodButtonClickOn => "block.wood_button.click_on",
+            Self::WoodButtonClickOff => "block.wood_button.click_off",
+            Self::StonePressurePlateClickOn => "block.stone_pressureplate.click_on",
+            Self::StonePressurePlateClickOff => "block.stone_pressureplate.click_off",
+            Self::WoodPressurePlateClickOn => "block.wood_pressureplate.click_on",
+            Self::WoodPressurePlateClickOff => "block.wood_pressureplate.click_off",
+            Self::PistonExtend => "block.piston.extend",
+            Self::PistonContract => "block.piston.contract",
+            Self::RedstoneTorchBurnout => "block.redstone_torch.burnout",
+            Self::LavaExtinguish => "block.lava.extinguish",
+            Self::NoteBass => "block.note.bass",
+            Self::NoteBell => "block.note.bell",
+            Self::NoteChime => "block.note.chime",
+            Self::NoteHarp => "block.note.harp",
+            Self::NotePling => "block.note.pling",
+            Self::NoteSnare => "block.note.snare",
+            Self::PortalTravel => "block.portal.travel",
+            Self::PortalTrigger => "block.portal.trigger",
+            Self::ExperienceOrbPickup => "entity.experience_orb.pickup",
+            Self::ItemPickup => "entity.item.pickup",
+            Self::ItemBreak => "entity.item.break",
+            Self::PlayerLevelUp => "entity.player.levelup",
+            Self::PlayerHurt => "entity.player.hurt",
+            Self::PlayerAttackStrong => "entity.player.attack.strong",
+            Self::FireworkLaunch => "entity.firework.launch",
+            Self::FireworkBlast => "entity.firework.blast",
+            Self::FireworkTwinkle => "entity.firework.twinkle",
+            Self::GenericExplode => "entity.generic.explode",
+            Self::LightningThunder => "entity.lightning.thunder",
+            Self::EndermanTeleport => "entity.endermen.teleport",
+            Self::VillagerYes => "entity.villager.yes",
+            Self::VillagerNo => "entity.villager.no",
+            Self::WitherSpawn => "entity.wither.spawn",
+            Self::EnderDragonGrowl => "entity.enderdragon.growl",
+            Self::UiButtonClick => "ui.button.click",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Particle {
+    Explode,
+    LargeExplode,
+    HugeExplosion,
+    FireworksSpark,
+    Bubble,
+    Splash,
+    Wake,
+    Suspended,
+    DepthSuspend,
+    Crit,
+    MagicCrit,
+    Smoke,
+    LargeSmoke,
+    Spell,
+    InstantSpell,
+    /// Colored by the offset, which is then an rgb color from 0 to 1 with a count of 0.
+    MobSpell,
+    MobSpellAmbient,
+    WitchMagic,
+    DripWater,
+    DripLava,
+    AngryVillager,
+    HappyVillager,
+    TownAura,
+    /// Colored by the x offset with a count of 0, from 0 to 1 around the color wheel.
+    Note,
+    Portal,
+    EnchantmentTable,
+    Flame,
+    Lava,
+    Footstep,
+    Cloud,
+    /// Colored by the offset like `MobSpell`.
+    RedDust,
+    SnowballPoof,
+    SnowShovel,
+    Slime,
+    Heart,
+    Barrier,
+    /// Pieces of an item, by item id and damage.
+    ItemCrack(i16, i16),
+    BlockCrack(BlockState),
+    BlockDust(BlockState),
+    Droplet,
+    Take,
+    MobAppearance,
+    DragonBreath,
+    EndRod,
+    DamageIndicator,
+    SweepAttack,
+    FallingDust(BlockState),
+    Totem,
+    Spit,
+}
+impl Particle {
+    pub fn id(self) -> i32 {
+        match self {
+            Self::Explode => 0,
+            Self::LargeExplode => 1,
+            Self::HugeExplosion => 2,
+            Self::FireworksSpark => 3,
+            Self::Bubble => 4,
+            Self::Splash => 5,
+            Self::Wake => 6,
+            Self::Suspended => 7,
+            Self::DepthSuspend => 8,
+            Self::Crit => 9,
+            Self::MagicCrit => 10,
+            Self::Smoke => 11,
+            Self::LargeSmoke => 12,
+            Self::Spell => 13,
+            Self::InstantSpell => 14,
+            Self::MobSpell => 15,
+            Self::MobSpellAmbient => 16,
+            Self::WitchMagic => 17,
+            Self::DripWater => 18,
+            Self::DripLava => 19,
+            Self::AngryVillager => 20,
+            Self::HappyVillager => 21,
+            Self::TownAura => 22,
+            Self::Note => 23,
+            Self::Portal => 24,
+            Self::EnchantmentTable => 25,
+            Self::Flame => 26,
+            Self::Lava => 27,
+            Self::Footstep => 28,
+            Self::Cloud => 29,
+            Self::RedDust => 30,
+            Self::SnowballPoof => 31,
+            Self::SnowShovel => 32,
+            Self::Slime => 33,
+            Self::Heart => 34,
+            Self::Barrier => 35,
+            Self::ItemCrack(..) => 36,
+            Self::BlockCrack(_) => 37,
+            Self::BlockDust(_) => 38,
+            Self::Droplet => 39,
+            Self::Take => 40,
+            Self::MobAppearance => 41,
+            Self::DragonBreath => 42,
+            Self::EndRod => 43,
+            Self::DamageIndicator => 44,
+            Self::SweepAttack => 45,
+            Self::FallingDust(_) => 46,
+            Self::Totem => 47,
+            Self::Spit => 48,
+        }
+    }
+    /// The varints that follow the particle, block states are sent with the meta in the high
+    /// bits unlike in chunks.
+    pub fn data(self) -> Vec<u32> {
+        let block = |state: BlockState| block::id(state) as u32 | (block::meta(state) as u32) << 12;
+        match self {
+            Self::ItemCrack(id, damage) => vec![id as u32, damage as u32],
+            Self::BlockCrack(state) | Self::BlockDust(state) | Self::FallingDust(state) => {
+                vec![block(state)]
+            }
+            _ => vec![],
+        }
+    }
+}
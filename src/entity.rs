@@ -0,0 +1,349 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::packet::{Position, Slot};
+use crate::world::{block, World};
+
+/// Entities further away than this from a player aren't sent to them.
+pub const TRACKING_RANGE: f64 = 128.0;
+/// Dropped items disappear after 5 minutes.
+pub const ITEM_LIFETIME: u64 = 6000;
+/// Entities that fall this far below the world are removed, like in vanilla.
+pub const VOID_Y: f64 = -64.0;
+/// Ticks before a player can pick up an item they threw, or one from a broken block.
+pub const THROWN_PICKUP_DELAY: u32 = 40;
+pub const BLOCK_DROP_PICKUP_DELAY: u32 = 10;
+
+static NEXT_ENTITY_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Hands out entity ids, which are unique for the lifetime of the server.
+pub fn next_entity_id() -> u32 {
+    NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The UUID vanilla gives a player in offline mode, a v3 UUID of `OfflinePlayer:<name>`.
+pub fn offline_uuid(name: &str) -> u128 {
+    let mut bytes = md5::compute(format!("OfflinePlayer:{}", name)).0;
+    bytes[6] = (bytes[6] & 0x0f) | 0x30;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    u128::from_be_bytes(bytes)
+}
+/// Formats a UUID with dashes, the way it appears in login packets and json files.
+pub fn uuid_string(uuid: u128) -> String {
+    let hex = format!("{:032x}", uuid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Parses a UUID with or without dashes.
+pub fn parse_uuid(s: &str) -> Option<u128> {
+    let hex = s.replace('-', "");
+    if hex.len() != 32 {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok()
+}
+
+/// Where an entity is and which way it is looking.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Location {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+impl Location {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self {
+            x,
+            y,
+            z,
+            ..Default::default()
+        }
+    }
+    /// The block this location is in.
+    pub fn block(&self) -> Position {
+        Position::new(
+            self.x.floor() as i32,
+            self.y.floor() as i32,
+            self.z.floor() as i32,
+        )
+    }
+    pub fn distance_squared(&self, other: &Location) -> f64 {
+        (self.x - other.x).powi(2) + (self.y - other.y).powi(2) + (self.z - other.z).powi(2)
+    }
+    /// Movement since `from` in the fixed point units of Entity Relative Move, if it fits.
+    pub fn delta(&self, from: &Location) -> Option<(i16, i16, i16)> {
+        // Rounding both ends instead of the difference keeps errors from adding up.
+        let fixed = |a: f64, b: f64| {
+            let d = (a * 4096.0).round() as i64 - (b * 4096.0).round() as i64;
+            i16::try_from(d).ok()
+        };
+        Some((
+            fixed(self.x, from.x)?,
+            fixed(self.y, from.y)?,
+            fixed(self.z, from.z)?,
+        ))
+    }
+}
+
+/// An axis aligned box, used for collisions with blocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: (f64, f64, f64),
+    pub max: (f64, f64, f64),
+}
+impl BoundingBox {
+    /// The box of an entity standing at `(x, y, z)`, centered on x and z.
+    pub fn at(x: f64, y: f64, z: f64, width: f64, height: f64) -> Self {
+        let half = width / 2.0;
+        Self {
+            min: (x - half, y, z - half),
+            max: (x + half, y + height, z + half),
+        }
+    }
+    pub fn shrink(&self, by: f64) -> Self {
+        Self {
+            min: (self.min.0 + by, self.min.1 + by, self.min.2 + by),
+            max: (self.max.0 - by, self.max.1 - by, self.max.2 - by),
+        }
+    }
+    pub fn offset(&self, dx: f64, dy: f64, dz: f64) -> Self {
+        Self {
+            min: (self.min.0 + dx, self.min.1 + dy, self.min.2 + dz),
+            max: (self.max.0 + dx, self.max.1 + dy, self.max.2 + dz),
+        }
+    }
+    pub fn grow(&self, x: f64, y: f64, z: f64) -> Self {
+        Self {
+            min: (self.min.0 - x, self.min.1 - y, self.min.2 - z),
+            max: (self.max.0 + x, self.max.1 + y, self.max.2 + z),
+        }
+    }
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        (0..3).all(|a| {
+            let ((min, max), (other_min, other_max)) = (self.axis(a), other.axis(a));
+            max > other_min && min < other_max
+        })
+    }
+    /// Every block position the box overlaps.
+    pub fn blocks(&self) -> impl Iterator<Item = (i32, i32, i32)> {
+        let (x0, y0, z0) = (
+            self.min.0.floor() as i32,
+            self.min.1.floor() as i32,
+            self.min.2.floor() as i32,
+        );
+        let (x1, y1, z1) = (
+            self.max.0.floor() as i32,
+            self.max.1.floor() as i32,
+            self.max.2.floor() as i32,
+        );
+        (x0..=x1).flat_map(move |x| (y0..=y1).flat_map(move |y| (z0..=z1).map(move |z| (x, y, z))))
+    }
+    /// The box grown in the direction of a movement, covering everything it passes.
+    pub fn expand_towards(&self, dx: f64, dy: f64, dz: f64) -> Self {
+        let (mut min, mut max) = (self.min, self.max);
+        for (d, lo, hi) in [
+            (dx, &mut min.0, &mut max.0),
+            (dy, &mut min.1, &mut max.1),
+            (dz, &mut min.2, &mut max.2),
+        ] {
+            if d < 0.0 {
+                *lo += d;
+            } else {
+                *hi += d;
+            }
+        }
+        Self { min, max }
+    }
+    fn axis(&self, axis: usize) -> (f64, f64) {
+        let pick = |t: (f64, f64, f64)| [t.0, t.1, t.2][axis];
+        (pick(self.min), pick(self.max))
+    }
+    /// How far this box can move by `d` along an axis before running into `other`.
+    fn clip(&self, other: &BoundingBox, axis: usize, d: f64) -> f64 {
+        // Only boxes that overlap on the other two axes are in the way.
+        let overlaps = (0..3).filter(|a| *a != axis).all(|a| {
+            let ((min, max), (other_min, other_max)) = (self.axis(a), other.axis(a));
+            max > other_min && min < other_max
+        });
+        if !overlaps {
+            return d;
+        }
+        let ((min, max), (other_min, other_max)) = (self.axis(axis), other.axis(axis));
+        if d > 0.0 && max <= other_min {
+            d.min(other_min - max)
+        } else if d < 0.0 && min >= other_max {
+            d.max(other_max - min)
+        } else {
+            d
+        }
+    }
+    /// The collision boxes of the blocks around this box that overlap it.
+    pub fn block_boxes(&self, world: &World) -> Vec<BoundingBox> {
+        // Fences stand half a block above their own, so the layer below is searched too.
+        let search = BoundingBox {
+            min: (self.min.0, self.min.1 - 0.5, self.min.2),
+            max: self.max,
+        };
+        search
+            .blocks()
+            .filter(|(_, y, _)| (0..256).contains(y))
+            .flat_map(|(x, y, z)| {
+                let boxes = if world.is_loaded(x, y, z) {
+                    let state = world.get_block(x, y, z);
+                    block::get(state).collision_boxes(block::meta(state), |dx, dz| {
+                        world.get_block(x + dx, y, z + dz)
+                    })
+                } else {
+                    vec![[0.0, 0.0, 0.0, 1.0, 1.0, 1.0]]
+                };
+                let (x, y, z) = (x as f64, y as f64, z as f64);
+                boxes.into_iter().map(move |b| BoundingBox {
+                    min: (x + b[0], y + b[1], z + b[2]),
+                    max: (x + b[3], y + b[4], z + b[5]),
+                })
+            })
+            .filter(|b| b.intersects(self))
+            .collect()
+    }
+    /// Whether any solid block overlaps the box. Unloaded chunks count as solid, so nothing
+    /// falls or walks into them.
+    pub fn collides(&self, world: &World) -> bool {
+        !self.block_boxes(world).is_empty()
+    }
+}
+
+/// How an entity moves on its own, with vanilla's values per tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Physics {
+    pub width: f64,
+    pub height: f64,
+    pub gravity: f64,
+    /// Share of its speed an entity loses to the air each tick.
+    pub drag: f64,
+}
+pub const ITEM_PHYSICS: Physics = Physics {
+    width: 0.25,
+    height: 0.25,
+    gravity: 0.04,
+    drag: 0.02,
+};
+
+#[derive(Debug, Clone)]
+pub enum EntityKind {
+    Item {
+        stack: Slot,
+        /// Ticks until players can pick it up.
+        pickup_delay: u32,
+    },
+}
+impl EntityKind {
+    pub fn physics(&self) -> Physics {
+        match self {
+            Self::Item { .. } => ITEM_PHYSICS,
+        }
+    }
+    /// The type id used in Spawn Object, and the data that goes with it.
+    pub fn object_type(&self) -> (u8, i32) {
+        match self {
+            Self::Item { .. } => (2, 1),
+        }
+    }
+}
+
+/// An entity that isn't a player, moved by the server each tick.
+#[derive(Debug)]
+pub struct Entity {
+    pub entity_id: u32,
+    pub uuid: u128,
+    pub kind: EntityKind,
+    /// Ticks since it was spawned.
+    pub age: u64,
+    pub location: Location,
+    /// In blocks per tick.
+    pub velocity: (f64, f64, f64),
+    pub on_ground: bool,
+    pub physics: Physics,
+    /// The location and velocity players last had sent to them.
+    pub last_sent: Location,
+    pub last_sent_velocity: (f64, f64, f64),
+}
+impl Entity {
+    pub fn new(kind: EntityKind, location: Location, velocity: (f64, f64, f64)) -> Self {
+        Self {
+            entity_id: next_entity_id(),
+            uuid: rand::random(),
+            physics: kind.physics(),
+            kind,
+            age: 0,
+            location,
+            velocity,
+            on_ground: false,
+            last_sent: location,
+            last_sent_velocity: velocity,
+        }
+    }
+    pub fn bounding_box(&self) -> BoundingBox {
+        let Location { x, y, z, .. } = self.location;
+        BoundingBox::at(x, y, z, self.physics.width, self.physics.height)
+    }
+    /// Runs one tick of physics: gravity, then moving as far as blocks allow, then drag and
+    /// friction from the block below.
+    pub fn step(&mut self, world: &World) {
+        self.velocity.1 -= self.physics.gravity;
+        let (dx, dy, dz) = self.velocity;
+        let mut bounds = self.bounding_box();
+        let obstacles = bounds.expand_towards(dx, dy, dz).block_boxes(world);
+        // Like vanilla, y is resolved first so entities land before sliding along the ground.
+        let mut moved = [0.0; 3];
+        for (axis, d) in [(1, dy), (0, dx), (2, dz)] {
+            let d = obstacles.iter().fold(d, |d, o| bounds.clip(o, axis, d));
+            moved[axis] = d;
+            let mut offset = [0.0; 3];
+            offset[axis] = d;
+            bounds = bounds.offset(offset[0], offset[1], offset[2]);
+        }
+        self.on_ground = dy < 0.0 && moved[1] != dy;
+        if moved[0] != dx {
+            self.velocity.0 = 0.0;
+        }
+        if moved[1] != dy {
+            self.velocity.1 = 0.0;
+        }
+        if moved[2] != dz {
+            self.velocity.2 = 0.0;
+        }
+        self.location.x += moved[0];
+        self.location.y += moved[1];
+        self.location.z += moved[2];
+
+        let keep = 1.0 - self.physics.drag;
+        let mut horizontal = keep;
+        if self.on_ground {
+            let Location { x, y, z, .. } = self.location;
+            let below =
+                world.get_block(x.floor() as i32, (y - 1.0).floor() as i32, z.floor() as i32);
+            horizontal *= block::get(below).slipperiness;
+        }
+        self.velocity.0 *= horizontal;
+        self.velocity.1 *= keep;
+        self.velocity.2 *= horizontal;
+        // Stop crawling once too slow to matter, like vanilla does below 0.003.
+        for v in [
+            &mut self.velocity.0,
+            &mut self.velocity.1,
+            &mut self.velocity.2,
+        ] {
+            if v.abs() < 0.003 {
+                *v = 0.0;
+            }
+        }
+    }
+}
@@ -0,0 +1,476 @@
+//! Player inventories and the click handling shared by every window.
+//!
+//! Clicks are simulated the same way the vanilla client predicts them. The client sends along
+//! what its own simulation returned; if ours disagrees, the click is rejected and the whole
+//! window is sent again.
+
+use crate::packet::Slot;
+use crate::packets::play::ClickWindow;
+use crate::world::block::{Tool, ToolKind};
+
+/// Window id of the player's own inventory, which is always open.
+pub const PLAYER_WINDOW: u8 = 0;
+pub const CRAFTING_OUTPUT: usize = 0;
+pub const CRAFTING: std::ops::Range<usize> = 1..5;
+pub const ARMOR: std::ops::Range<usize> = 5..9;
+pub const MAIN: std::ops::Range<usize> = 9..36;
+pub const HOTBAR: std::ops::Range<usize> = 36..45;
+/// Slot 45 is the off hand, which makes 46 slots in window 0.
+pub const OFFHAND: usize = 45;
+pub const PLAYER_INVENTORY_SIZE: usize = 46;
+
+/// Slot number used for clicks outside of the window.
+const OUTSIDE: i16 = -999;
+
+/// How many of an item fit in one stack.
+pub fn max_stack_size(id: i16) -> i8 {
+    match id {
+        256..=259 | 261 | 267..=279 | 282..=286 | 290..=294 | 298..=317 | 326..=329 | 333
+        | 335 | 342 | 343 | 346 | 354 | 355 | 359 | 373 | 386 | 387 | 398 | 403 | 407 | 408
+        | 417..=419 | 422 | 437 | 438 | 441..=449 | 2256..=2267 => 1,
+        323 | 325 | 332 | 344 | 368 | 416 | 425 => 16,
+        _ => 64,
+    }
+}
+/// The armor slot (0 for the head through 3 for the feet) an item can be worn in.
+pub fn armor_slot(id: i16) -> Option<usize> {
+    match id {
+        298..=317 => Some((id as usize - 298) % 4),
+        86 | 397 => Some(0),
+        443 => Some(1),
+        _ => None,
+    }
+}
+/// What an item is as a tool, if it is one.
+pub fn tool(id: i16) -> Option<Tool> {
+    let kind = match id {
+        256 | 269 | 273 | 277 | 284 => ToolKind::Shovel,
+        257 | 270 | 274 | 278 | 285 => ToolKind::Pickaxe,
+        258 | 271 | 275 | 279 | 286 => ToolKind::Axe,
+        _ => return None,
+    };
+    // Wood, stone, iron, diamond and then gold.
+    let (level, speed) = match id {
+        269..=271 => (0, 2.0),
+        273..=275 => (1, 4.0),
+        256..=258 => (2, 6.0),
+        277..=279 => (3, 8.0),
+        _ => (0, 12.0),
+    };
+    Some(Tool { kind, level, speed })
+}
+
+/// An in-progress drag across several slots, in click mode 5.
+#[derive(Debug, Clone)]
+pub struct Drag {
+    /// 0 spreads the cursor evenly, 1 puts one item in each slot, 2 fills every slot (creative).
+    pub mode: u8,
+    pub slots: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowKind {
+    Player,
+    /// A plain container like a chest, followed by the main inventory and hotbar.
+    Container { size: usize },
+}
+
+/// The slots of an open window, numbered the way the client numbers them.
+pub struct Window<'a> {
+    pub kind: WindowKind,
+    pub slots: Vec<&'a mut Slot>,
+}
+
+/// What a click did.
+#[derive(Debug, Default)]
+pub struct ClickOutcome {
+    /// Whether the result matches what the client predicted.
+    pub accepted: bool,
+    /// Stacks thrown out of the window.
+    pub dropped: Vec<Slot>,
+}
+
+impl<'a> Window<'a> {
+    /// Whether an item may be put into a slot by the player.
+    fn accepts(&self, index: usize, item: &Slot) -> bool {
+        match self.kind {
+            WindowKind::Player if index == CRAFTING_OUTPUT => false,
+            WindowKind::Player if ARMOR.contains(&index) => {
+                armor_slot(item.id) == Some(index - ARMOR.start)
+            }
+            _ => true,
+        }
+    }
+    fn limit(&self, index: usize, item: &Slot) -> i8 {
+        match self.kind {
+            WindowKind::Player if ARMOR.contains(&index) => 1,
+            _ => max_stack_size(item.id),
+        }
+    }
+    /// The range of slots the hotbar occupies in this window.
+    fn hotbar(&self) -> std::ops::Range<usize> {
+        match self.kind {
+            WindowKind::Player => HOTBAR,
+            WindowKind::Container { size } => size + 27..size + 36,
+        }
+    }
+    /// Moves as much of `stack` as possible into `range`, topping up existing stacks first.
+    fn merge(&mut self, stack: &mut Slot, range: std::ops::Range<usize>, reverse: bool) {
+        let order: Vec<usize> = if reverse {
+            range.rev().collect()
+        } else {
+            range.collect()
+        };
+        for &i in &order {
+            if stack.is_empty() {
+                return;
+            }
+            let limit = self.limit(i, stack);
+            let slot = &mut *self.slots[i];
+            if !slot.is_empty() && slot.same_item(stack) && slot.count < limit {
+                let moved = (limit - slot.count).min(stack.count);
+                slot.count += moved;
+                stack.split(moved);
+            }
+        }
+        for &i in &order {
+            if stack.is_empty() {
+                return;
+            }
+            if self.slots[i].is_empty() && self.accepts(i, stack) {
+                let limit = self.limit(i, stack);
+                *self.slots[i] = stack.split(limit);
+            }
+        }
+    }
+    /// Shift click, moving a stack between the two halves of the window.
+    fn quick_move(&mut self, index: usize) {
+        let mut stack = std::mem::take(&mut *self.slots[index]);
+        match self.kind {
+            WindowKind::Player => {
+                let armor = armor_slot(stack.id).map(|a| ARMOR.start + a);
+                let from_storage = MAIN.contains(&index) || HOTBAR.contains(&index);
+                match armor {
+                    Some(a) if from_storage && self.slots[a].is_empty() => {
+                        self.merge(&mut stack, a..a + 1, false);
+                    }
+                    _ => {}
+                }
+                if index < MAIN.start || index == OFFHAND {
+                    self.merge(&mut stack, MAIN.start..HOTBAR.end, index == CRAFTING_OUTPUT);
+                } else if MAIN.contains(&index) {
+                    self.merge(&mut stack, HOTBAR, false);
+                } else {
+                    self.merge(&mut stack, MAIN, false);
+                }
+            }
+            WindowKind::Container { size } => {
+                if index < size {
+                    self.merge(&mut stack, size..self.slots.len(), true);
+                } else {
+                    self.merge(&mut stack, 0..size, false);
+                }
+            }
+        }
+        *self.slots[index] = stack;
+    }
+    /// Left (button 0) or right (button 1) click on a slot.
+    fn pickup(&mut self, index: usize, button: i8, cursor: &mut Slot) {
+        let accepts = self.accepts(index, cursor);
+        let limit = self.limit(index, cursor);
+        let slot = &mut *self.slots[index];
+        if slot.is_empty() {
+            if !cursor.is_empty() && accepts {
+                let count = if button == 0 { cursor.count } else { 1 };
+                *slot = cursor.split(count.min(limit));
+            }
+        } else if cursor.is_empty() {
+            let count = if button == 0 {
+                slot.count
+            } else {
+                (slot.count + 1) / 2
+            };
+            *cursor = slot.split(count);
+        } else if accepts {
+            if slot.same_item(cursor) {
+                let count = if button == 0 { cursor.count } else { 1 };
+                let moved = count.min(limit - slot.count).max(0);
+                slot.count += moved;
+                cursor.split(moved);
+            } else if cursor.count <= limit {
+                std::mem::swap(slot, cursor);
+            }
+        } else if slot.same_item(cursor) && max_stack_size(cursor.id) > 1 {
+            // Taking from a slot that can't be put into, like the crafting output.
+            if slot.count + cursor.count <= max_stack_size(cursor.id) {
+                cursor.count += slot.count;
+                *slot = Slot::empty();
+            }
+        }
+    }
+    /// Number keys, swapping a slot with one in the hotbar.
+    fn swap(&mut self, index: usize, button: i8) {
+        let Some(hotbar) = self.hotbar().nth(button as usize) else {
+            return;
+        };
+        if hotbar == index {
+            return;
+        }
+        let (a, b) = (self.slots[index].clone(), self.slots[hotbar].clone());
+        if (b.is_empty() || self.accepts(index, &b)) && (a.is_empty() || self.accepts(hotbar, &a)) {
+            *self.slots[index] = b;
+            *self.slots[hotbar] = a;
+        }
+    }
+    /// Double click, collecting every matching item into the cursor.
+    fn collect(&mut self, index: usize, button: i8, cursor: &mut Slot) {
+        if cursor.is_empty() || !self.slots[index].is_empty() {
+            return;
+        }
+        let max = max_stack_size(cursor.id);
+        let order: Vec<usize> = if button == 0 {
+            (0..self.slots.len()).collect()
+        } else {
+            (0..self.slots.len()).rev().collect()
+        };
+        // Partial stacks are taken before full ones.
+        for full_pass in [false, true] {
+            for &i in &order {
+                if cursor.count >= max {
+                    return;
+                }
+                if self.kind == WindowKind::Player && i == CRAFTING_OUTPUT {
+                    continue;
+                }
+                let slot = &mut *self.slots[i];
+                if slot.is_empty() || !slot.same_item(cursor) {
+                    continue;
+                }
+                if !full_pass && slot.count == max {
+                    continue;
+                }
+                let taken = slot.split(max - cursor.count);
+                cursor.count += taken.count;
+            }
+        }
+    }
+    /// Drag clicks: a start event, one event per slot dragged over, and an end event that
+    /// spreads the cursor over the collected slots.
+    fn drag(&mut self, slot: i16, button: i8, cursor: &mut Slot, drag: &mut Option<Drag>, creative: bool) {
+        let event = button & 3;
+        let mode = ((button >> 2) & 3) as u8;
+        match (event, drag.as_mut()) {
+            (0, None) if !cursor.is_empty() && (mode < 2 || (mode == 2 && creative)) => {
+                *drag = Some(Drag {
+                    mode,
+                    slots: vec![],
+                });
+            }
+            (1, Some(d)) if d.mode == mode && slot >= 0 => {
+                let index = slot as usize;
+                if index >= self.slots.len() || d.slots.contains(&index) {
+                    return;
+                }
+                let target = &*self.slots[index];
+                let fits = target.is_empty()
+                    || (target.same_item(cursor) && target.count < max_stack_size(cursor.id));
+                if fits
+                    && self.accepts(index, cursor)
+                    && (mode == 2 || cursor.count as usize > d.slots.len())
+                {
+                    d.slots.push(index);
+                }
+            }
+            (2, Some(d)) if d.mode == mode => {
+                let d = drag.take().unwrap();
+                if d.slots.is_empty() {
+                    return;
+                }
+                let mut remaining = cursor.count;
+                for &i in &d.slots {
+                    let existing = if self.slots[i].is_empty() {
+                        0
+                    } else {
+                        self.slots[i].count
+                    };
+                    let fits = existing == 0 || self.slots[i].same_item(cursor);
+                    if !fits || (mode != 2 && (cursor.count as usize) < d.slots.len()) {
+                        continue;
+                    }
+                    let per_slot = match mode {
+                        0 => cursor.count / d.slots.len() as i8,
+                        1 => 1,
+                        _ => max_stack_size(cursor.id),
+                    };
+                    let limit = self.limit(i, cursor);
+                    let count = (per_slot as i16 + existing as i16).min(limit as i16) as i8;
+                    if mode != 2 {
+                        remaining -= count - existing;
+                    }
+                    *self.slots[i] = Slot {
+                        count,
+                        ..cursor.clone()
+                    };
+                }
+                cursor.count = remaining;
+                if cursor.count <= 0 {
+                    *cursor = Slot::empty();
+                }
+            }
+            _ => *drag = None,
+        }
+    }
+    /// Applies a click, and checks the result against the item the client sent.
+    pub fn click(
+        &mut self,
+        click: &ClickWindow,
+        cursor: &mut Slot,
+        drag: &mut Option<Drag>,
+        creative: bool,
+    ) -> ClickOutcome {
+        let mut outcome = ClickOutcome::default();
+        let in_window = click.slot >= 0 && (click.slot as usize) < self.slots.len();
+        let index = click.slot.max(0) as usize;
+        // Vanilla returns the clicked stack for normal and shift clicks, and nothing otherwise.
+        let mut expected = Slot::empty();
+        if click.mode != 5 {
+            *drag = None;
+        }
+        match click.mode {
+            0 if click.slot == OUTSIDE && !cursor.is_empty() => {
+                let count = if click.button == 0 { cursor.count } else { 1 };
+                outcome.dropped.push(cursor.split(count));
+            }
+            0 if in_window => {
+                expected = self.slots[index].clone();
+                self.pickup(index, click.button, cursor);
+            }
+            1 if in_window => {
+                expected = self.slots[index].clone();
+                self.quick_move(index);
+            }
+            2 if in_window => self.swap(index, click.button),
+            3 if in_window && creative && cursor.is_empty() && !self.slots[index].is_empty() => {
+                let max = max_stack_size(self.slots[index].id);
+                *cursor = Slot {
+                    count: max,
+                    ..self.slots[index].clone()
+                };
+            }
+            4 if in_window && cursor.is_empty() && !self.slots[index].is_empty() => {
+                let count = if click.button == 0 {
+                    1
+                } else {
+                    self.slots[index].count
+                };
+                outcome.dropped.push(self.slots[index].split(count));
+            }
+            5 => self.drag(click.slot, click.button, cursor, drag, creative),
+            6 if in_window => self.collect(index, click.button, cursor),
+            _ => {}
+        }
+        outcome.accepted = expected == click.clicked_item
+            || (expected.is_empty() && click.clicked_item.is_empty());
+        outcome
+    }
+}
+
+/// The 46 slots of a player's own inventory, plus whatever they are holding with the cursor.
+#[derive(Debug)]
+pub struct Inventory {
+    pub slots: Vec<Slot>,
+    pub cursor: Slot,
+    /// Selected hotbar slot, 0-8.
+    pub selected: usize,
+    pub drag: Option<Drag>,
+    /// Set after rejecting a click; clicks are ignored until the client apologises with a
+    /// Confirm Transaction for this window.
+    pub awaiting_apology: Option<u8>,
+}
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Inventory {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![Slot::empty(); PLAYER_INVENTORY_SIZE],
+            cursor: Slot::empty(),
+            selected: 0,
+            drag: None,
+            awaiting_apology: None,
+        }
+    }
+    pub fn held_item(&self) -> &Slot {
+        &self.slots[HOTBAR.start + self.selected]
+    }
+    pub fn held_item_mut(&mut self) -> &mut Slot {
+        &mut self.slots[HOTBAR.start + self.selected]
+    }
+    /// Clicks in the player's own inventory window.
+    pub fn click(&mut self, click: &ClickWindow, creative: bool) -> ClickOutcome {
+        let mut window = Window {
+            kind: WindowKind::Player,
+            slots: self.slots.iter_mut().collect(),
+        };
+        window.click(click, &mut self.cursor, &mut self.drag, creative)
+    }
+    /// Clicks in a container window, which shows the container's slots above the main
+    /// inventory and hotbar.
+    pub fn click_container(
+        &mut self,
+        container: &mut [Slot],
+        click: &ClickWindow,
+        creative: bool,
+    ) -> ClickOutcome {
+        let mut window = Window {
+            kind: WindowKind::Container {
+                size: container.len(),
+            },
+            slots: container
+                .iter_mut()
+                .chain(self.slots[MAIN.start..HOTBAR.end].iter_mut())
+                .collect(),
+        };
+        window.click(click, &mut self.cursor, &mut self.drag, creative)
+    }
+    /// The slots of a container window below the container's own.
+    pub fn container_slots(&self) -> &[Slot] {
+        &self.slots[MAIN.start..HOTBAR.end]
+    }
+    /// Adds a stack to the hotbar and main inventory, returning the indices of changed slots.
+    /// Whatever doesn't fit is left in `stack`.
+    pub fn add(&mut self, stack: &mut Slot) -> Vec<usize> {
+        let before = self.slots.clone();
+        let mut window = Window {
+            kind: WindowKind::Player,
+            slots: self.slots.iter_mut().collect(),
+        };
+        window.merge(stack, HOTBAR, false);
+        window.merge(stack, MAIN, false);
+        (0..PLAYER_INVENTORY_SIZE)
+            .filter(|&i| before[i] != self.slots[i])
+            .collect()
+    }
+    /// Moves everything in the crafting grid and the cursor back into the inventory when the
+    /// window is closed. Returns whatever didn't fit.
+    pub fn close(&mut self) -> Vec<Slot> {
+        let mut leftover = vec![];
+        let mut stacks: Vec<Slot> = CRAFTING
+            .map(|i| std::mem::take(&mut self.slots[i]))
+            .collect();
+        stacks.push(std::mem::take(&mut self.cursor));
+        for mut stack in stacks {
+            if stack.is_empty() {
+                continue;
+            }
+            self.add(&mut stack);
+            if !stack.is_empty() {
+                leftover.push(stack);
+            }
+        }
+        self.drag = None;
+        leftover
+    }
+}
@@ -1,37 +1,63 @@
 use std::cell::RefCell;
-use std::io::{Cursor, Error, Read, Write};
-use std::net::{SocketAddr, TcpListener, TcpStream};
-use std::sync::mpsc::{Receiver, Sender};
+use std::io::{Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant};
 
 // use byteorder::{BigEndian, WriteBytesExt};
 use log::{debug, error, info, warn};
 use minecraft_varint::{VarIntRead, VarIntWrite};
-use nbt::Map;
 
 use crate::packets::handshake::*;
-use crate::packets::login::{LoginStart, LoginSuccess};
+use crate::access::AccessLists;
+use crate::packets::login::{LoginDisconnect, LoginStart, LoginSuccess};
 use crate::packets::play::{
-    ChunkData, ClientBoundChat, ClientBoundKeepAlive, ClientBoundPlayerPositionAndRotation,
-    ClientSettings, HeldItemChange, JoinGame, PlayerPosition, PluginMessageS, ServerBoundKeepAlive,
-    ServerBoundPlayerPositionAndRotation, ChatPosition,
+    ChunkData, ClickWindow, ClientSettings, CloseWindow,
+    CreativeInventoryAction, EntityAction, JoinGame, PlayerBlockPlacement, PlayerDigging, PlayerLook,
+    PlayerOnGround, PlayerPosition, PluginMessageS, ServerBoundChat, ServerBoundConfirmTransaction, ServerBoundTabComplete, ServerBoundHeldItemChange, ServerBoundKeepAlive,
+    ServerBoundPlayerAbilities, ServerBoundPlayerPositionAndRotation, TeleportConfirm, UpdateSign,
 };
+use crate::player::Gamemode;
+use crate::playerdata::PlayerData;
+use crate::server::{Clients, Message, Server, MAX_PLAYERS};
+use crate::throttle::{Admission, ConnectionLimits, Deadline, Throttle};
+use crate::world::World;
 use crate::{
     packet::Packet,
     response_data::{Description, Players, ResponseData, Sample, Version},
 };
 use std::collections::HashMap;
+mod access;
+mod bossbar;
+mod command;
+mod effects;
 mod packet;
+// Packet structs mirror the protocol and carry fields we never read.
+#[allow(dead_code)]
 mod packets;
+mod permissions;
+mod entity;
+mod inventory;
+mod movement;
+mod player;
+mod playerdata;
+mod plugin;
 mod response_data;
-#[derive(Debug)]
-enum Message {
-    PlayerJoined(String),
-    ConnectionClosed,
-}
+mod scoreboard;
+mod server;
+mod throttle;
+mod world;
+
+/// Radius in chunks of the area around spawn that is sent to joining players.
+const SPAWN_RADIUS: i32 = 8;
+/// How long a status request waits for the tick thread before giving up.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+/// The longest packet the protocol allows, anything longer is a broken or hostile client.
+const MAX_PACKET_LEN: u32 = 2 << 20;
+
 fn main() {
-    flexi_logger::Logger::try_with_str("debug")
+    flexi_logger::Logger::try_with_str("debug, cranelift_codegen=info, wasmtime=info")
         .unwrap()
         .start()
         .unwrap();
@@ -39,17 +65,44 @@ fn main() {
 
     let listener = TcpListener::bind("127.0.0.1:8001").unwrap();
     let favicon = Arc::new(base64::encode(std::fs::read("./favicon.png").unwrap()));
-    let clients: Arc<Mutex<HashMap<usize, (Receiver<Message>, RefCell<TcpStream>)>>> =
-        Arc::new(Mutex::new(HashMap::new()));
+    let clients: Clients = Arc::new(Mutex::new(HashMap::new()));
     let clients2 = clients.clone();
 
+    let mut world = World::new();
+    world.load_area((0, 0), SPAWN_RADIUS);
+    info!("Generated {} chunks.", world.chunks.len());
+    let world = Arc::new(Mutex::new(world));
+    let tick_world = world.clone();
+    let access = Arc::new(Mutex::new(AccessLists::load()));
+    let tick_access = access.clone();
+
+    let throttle = Throttle::new(ConnectionLimits::load());
     std::thread::spawn(move || {
         for (index, client) in listener.incoming().enumerate() {
+            let stream = match client {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let Ok(peer) = stream.peer_addr() else {
+                continue;
+            };
+            let admission = match throttle.admit(peer.ip()) {
+                Ok(admission) => admission,
+                Err(rejection) => {
+                    warn!("Rejected connection from {}: {:?}", peer, rejection);
+                    continue;
+                }
+            };
+            let deadline = Instant::now() + throttle.limits.handshake_timeout;
             let (tx, rx) = std::sync::mpsc::channel::<Message>();
             let favicon = favicon.clone();
             let clients = clients.clone();
+            let world = world.clone();
+            let access = access.clone();
             std::thread::spawn(move || {
-                let stream = client.unwrap();
                 // let i = {clients.lock().unwrap()}.len();
                 // dbg!(index);
                 clients
@@ -61,6 +114,10 @@ fn main() {
                     favicon,
                     stream,
                     tx,
+                    world,
+                    access,
+                    admission,
+                    deadline,
                 };
 
                 handler.handle_client();
@@ -71,61 +128,11 @@ fn main() {
         }
     });
     std::thread::spawn(move || {
-        let mut is_running = true;
-        let mut last_tick = SystemTime::now();
-        let mut last_keep_alive = SystemTime::now();
-
-        while is_running {
-            let this_tick = SystemTime::now();
-
-            if this_tick.duration_since(last_tick).unwrap().as_millis() > (1000) {
-                last_tick = this_tick;
-                let mut should_keep_alive = SystemTime::now()
-                    .duration_since(last_keep_alive)
-                    .unwrap()
-                    .as_millis()
-                    > 2500;
-                let mut to_delete = Vec::new();
-                {
-                    let mutex_guard = clients2.lock().unwrap();
-                    for (index, (rx, client)) in mutex_guard.iter() {
-                        match rx.try_recv() {
-                            Ok(Message::PlayerJoined(name)) => {
-                                info!("Player `{}` joined!", name);
-                                for x in mutex_guard.iter() {
-                                    ClientBoundChat(
-                                        format!("{{\"text\": \"+{} joined.\"}}", name),
-                                        ChatPosition::Chat,
-                                    ).write(&mut *x.1.1.borrow_mut());
-                                }
-                            }
-                            Ok(Message::ConnectionClosed) => {
-                                // mutex_guard.remove(index);
-                                to_delete.push(*index);
-                                // continue;
-                                should_keep_alive = false;
-                            }
-                            _ => {}
-                        }
-                        // if client.peek(&mut []).is_err() {
-                        //     clients2.lock().unwrap().remove(index);
-                        // }
-                        if should_keep_alive {
-                            ClientBoundKeepAlive::new().write(&mut *client.borrow_mut());
-                            last_keep_alive = SystemTime::now();
-                        }
-                    }
-                }
-                for entry in to_delete {
-                    clients2.lock().unwrap().remove(&entry);
-                    info!("Removed {}", entry);
-                }
-                println!("Ticking. {:?}", clients2.lock().unwrap());
-            }
-            // std::sync::mpsc::
-
-            // std::thread::sleep_ms(200);
+        let mut server = Server::new(clients2, tick_world, tick_access);
+        for plugin in plugin::builtin() {
+            server.load_plugin(plugin);
         }
+        server.run()
     })
     .join()
     .unwrap();
@@ -135,11 +142,31 @@ struct ConnectionHandler {
     stream: TcpStream,
     favicon: Arc<String>,
     tx: Sender<Message>,
+    world: Arc<Mutex<World>>,
+    access: Arc<Mutex<AccessLists>>,
+    /// Released when the handler is dropped, which frees this connection's slots.
+    admission: Admission,
+    /// When the connection has to be logged in, or done with its status ping, by.
+    deadline: Instant,
+}
+impl Drop for ConnectionHandler {
+    /// However the connection ends, even if reading it panicked, the tick thread hears of it.
+    fn drop(&mut self) {
+        self.tx.send(Message::ConnectionClosed).ok();
+        info!("Sent connection closed info.")
+    }
 }
 impl ConnectionHandler {
+    /// The stream, for reads that have to be done before the deadline.
+    fn before_deadline(&mut self) -> Deadline<'_> {
+        Deadline {
+            stream: &mut self.stream,
+            deadline: self.deadline,
+        }
+    }
     fn handle_client(&mut self) {
         info!("New client.");
-        match Handshake::read(&mut self.stream) {
+        match Handshake::read(&mut self.before_deadline()) {
             Some(hs) => match hs.next_state {
                 1 => self.handle_ping(&hs),
                 2 => self.handle_play(&hs),
@@ -149,41 +176,55 @@ impl ConnectionHandler {
             },
             None => {
                 warn!("Failed to read handshake. Killing client.");
-                self.tx.send(Message::ConnectionClosed).unwrap();
             }
         }
     }
     fn handle_ping(&mut self, hs: &Handshake) {
-        let _req: Request = read_packet(&mut self.stream).unwrap();
+        let Some(_req): Option<Request> = read_packet(&mut self.before_deadline()) else {
+            warn!("Status request never arrived.");
+            return;
+        };
 
+        // Plugins get a say in what the list shows, which happens on the tick thread.
+        let (reply, answer) = std::sync::mpsc::channel();
+        self.tx
+            .send(Message::StatusRequest {
+                protocol: hs.protocol,
+                reply,
+            })
+            .unwrap();
+        let Ok(ping) = answer.recv_timeout(STATUS_TIMEOUT) else {
+            warn!("The server took too long to answer a status request.");
+            return;
+        };
         let response = Response {
             data: ResponseData {
                 version: Version {
                     name: "1.18.1".to_string(),
                     protocol: hs.protocol,
                 },
-                description: Description {
-                    text: "§l§nMycelium Server 0.0".to_string(),
-                },
+                description: Description { text: ping.motd },
                 players: Players {
-                    max: i32::MAX,
-                    online: 10,
-                    sample: vec![Sample {
-                        id: "00000000-0000-0000-0000-000000000000".to_string(),
-                        name: "aa".to_string(),
-                    }],
+                    max: ping.max_players,
+                    online: ping.online,
+                    sample: ping
+                        .sample
+                        .into_iter()
+                        .map(|(name, uuid)| Sample {
+                            id: entity::uuid_string(uuid),
+                            name,
+                        })
+                        .collect(),
                 },
                 favicon: Some(format!("data:image/png;base64,{}", self.favicon)),
             },
         };
         response.write(&mut self.stream).unwrap();
-        let ping: Option<Ping> = read_packet(&mut self.stream);
+        let ping: Option<Ping> = read_packet(&mut self.before_deadline());
         if let Some(p) = ping {
             Pong { payload: p.payload }.write(&mut self.stream);
             self.stream.shutdown(std::net::Shutdown::Both).unwrap();
             info!("Finished ping, exiting.");
-            self.tx.send(Message::ConnectionClosed).unwrap();
-            return;
         }
     }
     fn handle_play(&mut self, hs: &Handshake) {
@@ -191,28 +232,58 @@ impl ConnectionHandler {
         // let root = quartz_nbt::snbt::parse(include_str!("../default.snbt")).unwrap();
         // h.insert("minecraft:dimension_type", nbt::Value::Compound());
         // let c = nbt::Value::Compound(h);
-        let k: LoginStart = read_packet(&mut self.stream).unwrap();
-        self.tx
-            .send(Message::PlayerJoined(k.username.clone()))
-            .unwrap();
+        let Some(k): Option<LoginStart> = read_packet(&mut self.before_deadline()) else {
+            warn!("Login start never arrived.");
+            return;
+        };
+        let uuid = entity::offline_uuid(&k.username);
+        let entity_id = entity::next_entity_id();
 
         dbg!(&k);
+        let ip = self.stream.peer_addr().unwrap().ip();
+        let mut access = self.access.lock().unwrap();
+        let mut refusal = access.check_login(uuid, ip);
+        let bypasses_limit = access.ops.bypasses_player_limit(uuid);
+        drop(access);
+        if refusal.is_none() && !self.admission.join(MAX_PLAYERS as usize, bypasses_limit) {
+            let reason = serde_json::json!({ "translate": "multiplayer.disconnect.server_full" });
+            refusal = Some(reason.to_string());
+        }
+        if let Some(reason) = refusal {
+            info!("Refused {} ({}): {}", k.username, ip, reason);
+            LoginDisconnect(reason).write(&mut self.stream);
+            self.stream.shutdown(std::net::Shutdown::Both).ok();
+            return;
+        }
         LoginSuccess {
             as_string: hs.protocol <= 572,
             username: k.username.clone(),
-            uuid: 1293876,
+            uuid,
         }
         .write(&mut self.stream);
+        self.admission.logged_in();
+        // Players can go quiet for a while once they are in.
+        self.stream.set_read_timeout(None).unwrap();
         // std::thread::sleep(Duration::from_millis(200));
 
+        // New players start at spawn in creative.
+        let data = PlayerData::load(uuid);
+        let gamemode = data.as_ref().map_or(Gamemode::Creative, |d| d.gamemode());
+        let location = data.as_ref().map_or_else(
+            || {
+                let (x, y, z) = world::SPAWN;
+                entity::Location::new(x, y, z)
+            },
+            |d| d.location(),
+        );
         JoinGame {
             difficulty: 0,
             dimension: 0,
-            entity_id: 0,
-            gamemode: 1,
+            entity_id,
+            gamemode: gamemode as u8,
             is_hardcore: false,
             level_type: "default".to_string(),
-            max_players: 10,
+            max_players: MAX_PLAYERS,
             reduced_debug_info: false,
         }
         .write(&mut self.stream)
@@ -223,35 +294,71 @@ impl ConnectionHandler {
         //     .write(&mut self.stream)
         //     .unwrap();\
         // std::thread::sleep(Duration::from_millis(200));
-        ClientBoundPlayerPositionAndRotation {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-            flags: 0,
-            dismount_veicle: false,
-            pitch: 0.0,
-            yaw: 0.0,
-            teleport_id: 0,
-        }
-        .write(&mut self.stream);
-        for x in 0..16 {
-            for y in 0..16 {
-                ChunkData {
-                    chunk_x: x,
-                    chunk_z: y,
+        // The spawn position comes from the tick thread, with a teleport id it keeps track of.
+        // The chunks are written out before sending, so a slow client doesn't hold up the
+        // tick thread while it has the world locked.
+        let mut chunks = Cursor::new(vec![]);
+        {
+            let mut world = self.world.lock().unwrap();
+            // Players who left somewhere else need the ground around them too.
+            let (center_x, center_z) =
+                ((location.x.floor() as i32) >> 4, (location.z.floor() as i32) >> 4);
+            world.load_area((center_x, center_z), SPAWN_RADIUS);
+            for chunk_x in center_x - SPAWN_RADIUS..=center_x + SPAWN_RADIUS {
+                for chunk_z in center_z - SPAWN_RADIUS..=center_z + SPAWN_RADIUS {
+                    let Some(column) = world.get_chunk(chunk_x, chunk_z) else {
+                        continue;
+                    };
+                    ChunkData {
+                        chunk_x,
+                        chunk_z,
+                        column,
+                        block_entities: column
+                            .block_entities
+                            .iter()
+                            .map(|(position, block_entity)| block_entity.to_nbt(*position))
+                            .collect(),
+                    }
+                    .write(&mut chunks)
+                    .unwrap();
                 }
-                .write(&mut self.stream)
-                .unwrap();
             }
         }
+        if self.stream.write_all(chunks.get_ref()).is_err() {
+            return;
+        }
+        // From here on the tick thread does all the writing.
+        self.tx
+            .send(Message::PlayerJoined {
+                name: k.username.clone(),
+                uuid,
+                entity_id,
+                protocol: hs.protocol,
+                data,
+            })
+            .unwrap();
 
-        let mut expected_keepalive = 0;
-        while self.stream.peek(&mut [0; 16]).unwrap() > 0 {
-            let len = self.stream.read_var_u32().unwrap();
-            if len == 0 {
-                continue;
+        while matches!(self.stream.peek(&mut [0; 16]), Ok(n) if n > 0) {
+            let Ok(len) = self.stream.read_var_u32() else {
+                break;
+            };
+            if len > MAX_PACKET_LEN {
+                warn!("Packet of {} bytes is over the limit, disconnecting.", len);
+                break;
             }
-            let id = self.stream.read_var_u32().unwrap();
+            let Ok(id) = self.stream.read_var_u32() else {
+                break;
+            };
+            let Some(body_len) = (len as usize).checked_sub(varuint_size(id)) else {
+                warn!("Packet {:#X} is shorter than its id, disconnecting.", id);
+                break;
+            };
+            // Read the whole frame first so a packet we parse wrong can't desync the stream.
+            let mut body = vec![0; body_len];
+            if self.stream.read_exact(&mut body).is_err() {
+                break;
+            }
+            let mut body = Cursor::new(body);
             // let c = ClientBoundKeepAlive::new();
             // expected_keepalive = c.0;
             // dbg!(&c);
@@ -268,49 +375,71 @@ impl ConnectionHandler {
             // self.stream.write_var_u32((try_into + stone.len()).try_into().unwrap()).unwrap();
             // self.stream.write_var_u32(0x22).unwrap();
             // self.stream.write_all(stone).unwrap();
-            match id {
-                0x04 => {
-                    let settings = ClientSettings::read(&mut self.stream).unwrap();
-                    dbg!(settings);
-                }
+            let message = match id {
+                0x01 => ServerBoundTabComplete::read(&mut body).map(Message::TabComplete),
+                0x02 => ServerBoundChat::read(&mut body).map(|chat| Message::Chat(chat.0)),
+                0x04 => ClientSettings::read(&mut body).map(Message::ClientSettings),
                 0x09 => {
-                    let p: PluginMessageS =
-                        PluginMessageS::read_with_len(&mut self.stream, id, len).unwrap();
+                    let p: Option<PluginMessageS> =
+                        PluginMessageS::read_with_len(&mut body, id, len);
                     dbg!(p);
+                    continue;
                 }
-                0x0d => {
-                    let pos: PlayerPosition = PlayerPosition::read(&mut self.stream).unwrap();
-                    // dbg!(pos);
-                }
-                0x0e => {
-                    let posros: ServerBoundPlayerPositionAndRotation =
-                        ServerBoundPlayerPositionAndRotation::read(&mut self.stream).unwrap();
-                    // dbg!(posros);
-                }
-                0x0 => {
-                    println!("TEleport confirm?");
-                    let id = self.stream.read_var_u32().unwrap();
-                    dbg!(id);
-                }
-                0xb => {
-                    let keep_alive = ServerBoundKeepAlive::read(&mut self.stream).unwrap();
-                    // assert_eq!(keep_alive.0, expected_keepalive);
-                }
+                0x0c => PlayerOnGround::read(&mut body).map(|p| Message::Movement {
+                    position: None,
+                    rotation: None,
+                    on_ground: p.on_ground,
+                }),
+                0x0d => PlayerPosition::read(&mut body).map(|pos| Message::Movement {
+                    position: Some((pos.x, pos.y, pos.z)),
+                    rotation: None,
+                    on_ground: pos.on_ground,
+                }),
+                0x0e => ServerBoundPlayerPositionAndRotation::read(&mut body).map(|posros| {
+                    Message::Movement {
+                        position: Some((posros.x, posros.y, posros.z)),
+                        rotation: Some((posros.yaw, posros.pitch)),
+                        on_ground: posros.on_ground,
+                    }
+                }),
+                0x0f => PlayerLook::read(&mut body).map(|look| Message::Movement {
+                    position: None,
+                    rotation: Some((look.yaw, look.pitch)),
+                    on_ground: look.on_ground,
+                }),
+                0x00 => TeleportConfirm::read(&mut body).map(|c| Message::TeleportConfirm(c.0)),
+                0xb => ServerBoundKeepAlive::read(&mut body).map(|keep_alive| Message::KeepAlive {
+                    id: keep_alive.0,
+                    received: Instant::now(),
+                }),
+                0x13 => ServerBoundPlayerAbilities::read(&mut body).map(Message::PlayerAbilities),
+                0x15 => EntityAction::read(&mut body).map(Message::EntityAction),
+                0x14 => PlayerDigging::read(&mut body).map(Message::Digging),
+                0x1f => PlayerBlockPlacement::read(&mut body).map(Message::BlockPlacement),
+                0x1a => ServerBoundHeldItemChange::read(&mut body).map(Message::HeldItemChange),
+                0x07 => ClickWindow::read(&mut body).map(Message::ClickWindow),
+                0x1b => CreativeInventoryAction::read(&mut body).map(Message::CreativeInventoryAction),
+                0x08 => CloseWindow::read(&mut body).map(Message::CloseWindow),
+                0x05 => ServerBoundConfirmTransaction::read(&mut body).map(Message::ConfirmTransaction),
+                0x1c => UpdateSign::read(&mut body).map(Message::UpdateSign),
                 _ => {
-                    let x = (TryInto::<usize>::try_into(len).unwrap()) - varuint_size(len);
-                    let mut buf = vec![0; x];
-                    self.stream.read_exact(&mut buf).unwrap();
                     warn!(
                         "Packet with ID {:#X?} and length {} has been thrown away.",
                         id, len
                     );
                     // error!("Didn't recognize packet, breaking out!");
                     // break;
+                    continue;
                 }
+            };
+            let Some(message) = message else {
+                warn!("Couldn't read packet {:#X}, disconnecting.", id);
+                break;
+            };
+            if self.tx.send(message).is_err() {
+                break;
             }
         }
-        self.tx.send(Message::ConnectionClosed).unwrap();
-        info!("Sent connection closed info.")
         //TODO: recipes
         //TODO: Tags
         //TODO: entity status
@@ -324,9 +453,12 @@ pub fn varuint_size(i: u32) -> usize {
     cursor.into_inner().len()
 }
 fn read_packet<S: Read + Write, P: Packet<S>>(s: &mut S) -> Option<P> {
-    let len = s.read_var_u32().unwrap();
-    let id = s.read_var_u32().unwrap();
-    assert_eq!(id, P::get_id());
+    let len = s.read_var_u32().ok()?;
+    let id = s.read_var_u32().ok()?;
+    if id != P::get_id() {
+        warn!("Expected packet {:X}, got {:X}.", P::get_id(), id);
+        return None;
+    }
     debug!("<- Packet {:X} with len {}.", id, len);
     P::read(s)
 }
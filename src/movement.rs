@@ -0,0 +1,76 @@
+//! Checks the movement packets clients send before the server believes them. Limits are per
+//! packet, since clients send at most one position each tick, and loose enough for ice,
+//! jumping and a bit of lag.
+
+use crate::entity::{BoundingBox, Location};
+use crate::player::{Gamemode, Player};
+use crate::world::World;
+
+pub const PLAYER_WIDTH: f64 = 0.6;
+pub const PLAYER_HEIGHT: f64 = 1.8;
+pub const PLAYER_EYE_HEIGHT: f64 = 1.62;
+/// Vanilla refuses positions this far out, the world border can't go further.
+const MAX_COORDINATE: f64 = 3.2e7;
+/// How far the box is shrunk before checking for blocks, so standing against a wall or on the
+/// floor doesn't count as being inside it.
+const COLLISION_MARGIN: f64 = 1e-3;
+/// Longest step taken when checking the path of a move for blocks.
+const PATH_STEP: f64 = 0.5;
+/// Falling speed is capped at 3.92 blocks per tick.
+const MAX_FALL: f64 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Accept,
+    /// The move is refused and the player is sent back to where the server has them.
+    RubberBand(&'static str),
+    /// Nothing a working client would send.
+    Kick,
+}
+
+/// How far a player may move in one packet, horizontally and up.
+fn limits(player: &Player) -> (f64, f64) {
+    match player.gamemode {
+        Gamemode::Spectator => (5.0, 5.0),
+        Gamemode::Creative if player.flying => (2.5, 2.5),
+        _ if player.sprinting => (1.2, 0.6),
+        _ if player.sneaking => (0.5, 0.6),
+        _ => (0.8, 0.6),
+    }
+}
+
+pub fn check(player: &Player, to: &Location, world: &World) -> Verdict {
+    let values = [to.x, to.y, to.z, to.yaw as f64, to.pitch as f64];
+    if values.iter().any(|v| !v.is_finite())
+        || to.x.abs() > MAX_COORDINATE
+        || to.z.abs() > MAX_COORDINATE
+    {
+        return Verdict::Kick;
+    }
+    let from = &player.location;
+    let (dx, dy, dz) = (to.x - from.x, to.y - from.y, to.z - from.z);
+    let (horizontal, up) = limits(player);
+    if dx * dx + dz * dz > horizontal * horizontal {
+        return Verdict::RubberBand("moved too quickly");
+    }
+    if dy > up || -dy > MAX_FALL.max(up) {
+        return Verdict::RubberBand("moved too quickly vertically");
+    }
+    if player.gamemode != Gamemode::Spectator {
+        let start = BoundingBox::at(from.x, from.y, from.z, PLAYER_WIDTH, PLAYER_HEIGHT)
+            .shrink(COLLISION_MARGIN);
+        // Players stuck in a block, say after one was placed on them, may still walk out.
+        if !start.collides(world) {
+            let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+            let steps = (distance / PATH_STEP).ceil().max(1.0);
+            let inside = (1..=steps as u32).any(|i| {
+                let t = i as f64 / steps;
+                start.offset(dx * t, dy * t, dz * t).collides(world)
+            });
+            if inside {
+                return Verdict::RubberBand("moved into a block");
+            }
+        }
+    }
+    Verdict::Accept
+}
@@ -1,12 +1,11 @@
-use byteorder::WriteBytesExt;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use log::debug;
 use minecraft_varint::{VarIntRead, VarIntWrite};
 use std::{io::Read, io::{Write, Cursor}};
 
-use crate::varuint_size;
 
 pub trait Packet<S: Read + Write> {
-    fn read_with_len(r: &mut S, id: u32, len: u32) -> Option<Self> where Self: Sized {
+    fn read_with_len(_r: &mut S, _id: u32, _len: u32) -> Option<Self> where Self: Sized {
         unimplemented!()
     }
     fn read(_r: &mut S) -> Option<Self>
@@ -21,8 +20,12 @@ pub trait Packet<S: Read + Write> {
     }
     fn write(&self, w: &mut S) -> Option<usize> {
         let v = self.write_impl();
-        let lenlen = w.write_var_u32((v.len()).try_into().unwrap()).ok()?;
-        let write = w.write(&v).unwrap();
+        // Length and body go out in a single write so a dead socket can't leave half a frame.
+        let mut frame = vec![];
+        let lenlen = frame.write_var_u32((v.len()).try_into().unwrap()).ok()?;
+        frame.extend_from_slice(&v);
+        w.write_all(&frame).ok()?;
+        let write = v.len();
 
         let mut c = Cursor::new(v.clone());
         let id = c.read_var_u32().unwrap();
@@ -48,6 +51,17 @@ where
         String::from_utf8(buf).unwrap()
     }
 }
+/// Reads a string from a client, `None` if it's longer than `max_len` bytes, cut short or not
+/// UTF-8. The length is checked before anything is allocated.
+pub fn read_bounded_string<R: Read>(r: &mut R, max_len: usize) -> Option<String> {
+    let len = r.read_var_u32().ok()? as usize;
+    if len > max_len {
+        return None;
+    }
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf).ok()?;
+    String::from_utf8(buf).ok()
+}
 pub trait WriteMcString {
     fn write_mc_string(&mut self, s: String);
 }
@@ -59,7 +73,168 @@ where
         self.write_var_u32((s.len()).try_into().unwrap()).unwrap();
         let chars = s.bytes();
         for ch in chars {
-            self.write_u8(ch as u8).unwrap();
+            self.write_u8(ch).unwrap();
+        }
+    }
+}
+
+/// Converts degrees to the 256 steps per turn angles are sent as.
+pub fn to_angle(degrees: f32) -> u8 {
+    (degrees.rem_euclid(360.0) * 256.0 / 360.0) as u8
+}
+
+/// A block position, packed into a single long on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+impl Position {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+    /// The neighbouring position on the given block face, as sent by the client.
+    pub fn offset(&self, face: u8) -> Self {
+        let Self { x, y, z } = *self;
+        match face {
+            0 => Self::new(x, y - 1, z),
+            1 => Self::new(x, y + 1, z),
+            2 => Self::new(x, y, z - 1),
+            3 => Self::new(x, y, z + 1),
+            4 => Self::new(x - 1, y, z),
+            5 => Self::new(x + 1, y, z),
+            _ => *self,
+        }
+    }
+}
+pub trait ReadPosition {
+    fn read_position(&mut self) -> Option<Position>;
+}
+impl<T> ReadPosition for T
+where
+    T: Read,
+{
+    fn read_position(&mut self) -> Option<Position> {
+        let v = self.read_i64::<BigEndian>().ok()?;
+        Some(Position {
+            x: (v >> 38) as i32,
+            y: ((v << 26) >> 52) as i32,
+            z: ((v << 38) >> 38) as i32,
+        })
+    }
+}
+pub trait WritePosition {
+    fn write_position(&mut self, p: Position);
+}
+impl<T> WritePosition for T
+where
+    T: Write,
+{
+    fn write_position(&mut self, p: Position) {
+        let v = ((p.x as i64 & 0x3ffffff) << 38) | ((p.y as i64 & 0xfff) << 26) | (p.z as i64 & 0x3ffffff);
+        self.write_i64::<BigEndian>(v).unwrap();
+    }
+}
+
+/// An item stack as sent over the network. An id of -1 means the slot is empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot {
+    pub id: i16,
+    pub count: i8,
+    pub damage: i16,
+    pub nbt: Option<nbt::Blob>,
+}
+impl Default for Slot {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+impl Slot {
+    pub fn empty() -> Self {
+        Self {
+            id: -1,
+            count: 0,
+            damage: 0,
+            nbt: None,
+        }
+    }
+    pub fn new(id: i16, count: i8, damage: i16) -> Self {
+        Self {
+            id,
+            count,
+            damage,
+            nbt: None,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.id < 0 || self.count <= 0
+    }
+    /// Whether two stacks hold the same kind of item and could be merged.
+    pub fn same_item(&self, other: &Slot) -> bool {
+        self.id == other.id && self.damage == other.damage && self.nbt == other.nbt
+    }
+    /// Takes up to `count` items out of this stack.
+    pub fn split(&mut self, count: i8) -> Slot {
+        let count = count.min(self.count);
+        let taken = Slot {
+            count,
+            ..self.clone()
+        };
+        self.count -= count;
+        if self.count <= 0 {
+            *self = Slot::empty();
+        }
+        taken
+    }
+}
+pub trait ReadSlot {
+    fn read_slot(&mut self) -> Option<Slot>;
+}
+impl<T> ReadSlot for T
+where
+    T: Read,
+{
+    fn read_slot(&mut self) -> Option<Slot> {
+        let id = self.read_i16::<BigEndian>().ok()?;
+        if id < 0 {
+            return Some(Slot::empty());
+        }
+        let count = self.read_i8().ok()?;
+        let damage = self.read_i16::<BigEndian>().ok()?;
+        // A lone TAG_End means there is no NBT.
+        let tag = self.read_u8().ok()?;
+        let nbt = if tag == 0 {
+            None
+        } else {
+            Some(nbt::Blob::from_reader(&mut Read::chain(&[tag][..], self)).ok()?)
+        };
+        Some(Slot {
+            id,
+            count,
+            damage,
+            nbt,
+        })
+    }
+}
+pub trait WriteSlot {
+    fn write_slot(&mut self, s: &Slot);
+}
+impl<T> WriteSlot for T
+where
+    T: Write,
+{
+    fn write_slot(&mut self, s: &Slot) {
+        if s.is_empty() {
+            self.write_i16::<BigEndian>(-1).unwrap();
+            return;
+        }
+        self.write_i16::<BigEndian>(s.id).unwrap();
+        self.write_i8(s.count).unwrap();
+        self.write_i16::<BigEndian>(s.damage).unwrap();
+        match &s.nbt {
+            Some(nbt) => nbt.to_writer(self).unwrap(),
+            None => self.write_u8(0).unwrap(),
         }
     }
 }
@@ -63,7 +63,7 @@ impl<S: Read + Write> crate::packet::Packet<S> for Handshake {
     }
 }
 impl<S: Read + Write> Packet<S> for Request {
-    fn read(r: &mut S) -> Option<Self>
+    fn read(_r: &mut S) -> Option<Self>
     where
         Self: Sized,
     {
@@ -82,7 +82,7 @@ impl<S: Read + Write> Packet<S> for Request {
     }
 }
 impl<S: Read + Write> Packet<S> for Response {
-    fn read(r: &mut S) -> Option<Self>
+    fn read(_r: &mut S) -> Option<Self>
     where
         Self: Sized,
     {
@@ -122,8 +122,7 @@ impl<S: Read + Write> Packet<S> for Pong {
         let mut c = Cursor::new(buf);
         c.write_var_u64(0x1).unwrap();
         c.write_u64::<BigEndian>(self.payload).unwrap();
-        let i = c.into_inner();
-        i
+        c.into_inner()
     }
 
     fn get_id() -> u32 {
@@ -3,6 +3,7 @@ use std::io::{Cursor, Read, Write};
 use byteorder::{BigEndian, WriteBytesExt};
 use minecraft_varint::VarIntWrite;
 
+use crate::entity::uuid_string;
 use crate::packet::{Packet, ReadMcString, WriteMcString};
 
 #[derive(Debug)]
@@ -16,6 +17,9 @@ pub struct LoginSuccess {
     pub as_string: bool,
     pub username: String,
 }
+/// Refuses the login, the reason is a json text component.
+#[derive(Debug)]
+pub struct LoginDisconnect(pub String);
 impl<S: Read + Write> Packet<S> for LoginStart {
     fn get_id() -> u32 {
         0x00
@@ -29,6 +33,17 @@ impl<S: Read + Write> Packet<S> for LoginStart {
         })
     }
 }
+impl<S: Read + Write> Packet<S> for LoginDisconnect {
+    fn get_id() -> u32 {
+        0x00
+    }
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_mc_string(self.0.clone());
+        v
+    }
+}
 impl<S: Read + Write> Packet<S> for LoginSuccess {
     fn get_id() -> u32 {
         0x02
@@ -40,7 +55,7 @@ impl<S: Read + Write> Packet<S> for LoginSuccess {
         c.write_var_u32(0x02).unwrap();
 
         if self.as_string {
-            c.write_mc_string("00000000-0000-0000-0000-000000000000".to_string());
+            c.write_mc_string(uuid_string(self.uuid));
         } else {
             c.write_u128::<BigEndian>(self.uuid).unwrap();
         }
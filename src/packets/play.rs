@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     io::{Cursor, Read, Write},
 };
 
@@ -7,7 +8,11 @@ use minecraft_varint::{VarIntRead, VarIntWrite};
 use nibbler::nibble::Nibble;
 
 use crate::{
-    packet::{Packet, ReadMcString, WriteMcString},
+    world::block_entity::BlockEntity,
+    packet::{
+        read_bounded_string, to_angle, Packet, Position, ReadMcString, ReadPosition, ReadSlot,
+        Slot, WriteMcString, WritePosition, WriteSlot,
+    },
 };
 
 #[derive(Debug)]
@@ -39,6 +44,66 @@ pub struct HeldItemChange {
     pub slot: u8,
 }
 #[derive(Debug)]
+pub struct ServerBoundHeldItemChange {
+    pub slot: i16,
+}
+#[derive(Debug)]
+pub struct ClickWindow {
+    pub window_id: u8,
+    /// -999 for clicks outside the window.
+    pub slot: i16,
+    pub button: i8,
+    pub action_number: i16,
+    pub mode: u32,
+    pub clicked_item: Slot,
+}
+#[derive(Debug)]
+pub struct CreativeInventoryAction {
+    /// -1 drops the item.
+    pub slot: i16,
+    pub clicked_item: Slot,
+}
+#[derive(Debug)]
+pub struct CloseWindow {
+    pub window_id: u8,
+}
+#[derive(Debug)]
+pub struct ServerBoundConfirmTransaction {
+    pub window_id: i8,
+    pub action_number: i16,
+    pub accepted: bool,
+}
+#[derive(Debug)]
+pub struct ClientBoundConfirmTransaction {
+    pub window_id: i8,
+    pub action_number: i16,
+    pub accepted: bool,
+}
+#[derive(Debug)]
+pub struct WindowItems {
+    pub window_id: u8,
+    pub slots: Vec<Slot>,
+}
+#[derive(Debug)]
+pub struct ClientBoundCloseWindow(pub u8);
+#[derive(Debug)]
+pub struct OpenWindow {
+    pub window_id: u8,
+    /// Like `minecraft:chest`.
+    pub window_type: String,
+    /// A json text component.
+    pub title: String,
+    /// Slots of the container, not counting the player's inventory below it.
+    pub slot_count: u8,
+}
+#[derive(Debug)]
+pub struct SetSlot {
+    /// -1 together with slot -1 sets the item held by the cursor.
+    pub window_id: i8,
+    pub slot: i16,
+    pub slot_data: Slot,
+}
+#[derive(Debug)]
 pub struct PluginMessageS {
     pub channel: String,
     pub data: Vec<u8>,
@@ -60,6 +125,31 @@ pub struct ServerBoundPlayerPositionAndRotation {
     pub on_ground: bool,
 }
 #[derive(Debug)]
+pub struct PlayerLook {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+/// Sent every tick a player doesn't move.
+#[derive(Debug)]
+pub struct PlayerOnGround {
+    pub on_ground: bool,
+}
+#[derive(Debug)]
+pub struct ServerBoundPlayerAbilities {
+    /// 0x02 is set while flying.
+    pub flags: u8,
+    pub flying_speed: f32,
+    pub walking_speed: f32,
+}
+/// Sneaking, sprinting and the other actions that don't have their own packet.
+#[derive(Debug)]
+pub struct EntityAction {
+    pub entity_id: u32,
+    pub action: u32,
+    pub jump_boost: u32,
+}
+#[derive(Debug)]
 pub struct ClientBoundPlayerPositionAndRotation {
     pub x: f64,
     pub y: f64,
@@ -88,29 +178,33 @@ pub struct ChunkSection {
 }
 #[derive(Debug)]
 pub struct ChunkColumn {
-    pub sections: Vec<ChunkSection>,
+    /// Always 16 entries, `None` for sections that have never held a block.
+    pub sections: Vec<Option<ChunkSection>>,
     pub biomes: Option<Vec<u8>>,
+    /// Per column (`z << 4 | x`), the lowest y above which every block lets sky light through.
+    pub heightmap: Vec<u16>,
+    pub block_entities: HashMap<Position, BlockEntity>,
+    /// Changed since it was last saved.
+    pub dirty: bool,
 }
 
 #[derive(Debug)]
-pub struct ChunkData {
+pub struct ChunkData<'a> {
     pub chunk_x: i32,
     pub chunk_z: i32,
-    // /// always true
-    // pub ground_up_continuous: bool,
-    // pub primary_bit_mask: u32,
-
-    // // Size and data
-    // pub chunk_sections: Vec<ChunkColumn>,
-    // // Number of block entities, and block entities
-    // pub block_entities: Vec<nbt::Blob>,
+    pub column: &'a ChunkColumn,
+    /// The column's block entities, as vanilla stores them.
+    pub block_entities: Vec<nbt::Blob>,
 }
 #[derive(Debug)]
 pub struct ClientBoundKeepAlive(pub i64);
 #[derive(Debug)]
 pub struct ServerBoundKeepAlive(pub  i64);
+/// Sent back for every `ClientBoundPlayerPositionAndRotation`, with its teleport id.
+#[derive(Debug)]
+pub struct TeleportConfirm(pub u32);
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChatPosition {
     Chat = 0,
     System = 1,
@@ -118,6 +212,432 @@ pub enum ChatPosition {
 }
 #[derive(Debug)]
 pub struct ClientBoundChat(pub String, pub ChatPosition);
+/// A chat message or a command typed by the player, starting with a `/` in the latter case.
+#[derive(Debug)]
+pub struct ServerBoundChat(pub String);
+/// Kicks the player, the reason is a json text component.
+#[derive(Debug)]
+pub struct Disconnect(pub String);
+#[derive(Debug)]
+pub struct ServerBoundTabComplete {
+    pub text: String,
+    /// Set for command blocks, where the text is a command without the leading `/`.
+    pub assume_command: bool,
+    pub looked_at_block: Option<Position>,
+}
+/// Candidates for the last word of the text the client asked about.
+#[derive(Debug)]
+pub struct ClientBoundTabComplete(pub Vec<String>);
+#[derive(Debug)]
+pub enum CommandNodeKind {
+    Root,
+    Literal(String),
+    Argument {
+        name: String,
+        /// Brigadier parser identifier, like `brigadier:integer`.
+        parser: String,
+        /// Already encoded parser properties.
+        properties: Vec<u8>,
+    },
+}
+#[derive(Debug)]
+pub struct CommandNode {
+    pub kind: CommandNodeKind,
+    pub executable: bool,
+    /// Indices into `DeclareCommands::nodes`.
+    pub children: Vec<u32>,
+}
+/// The command graph 1.13+ clients use for completion and highlighting. Not part of 1.12, so
+/// only sent to newer clients.
+#[derive(Debug)]
+pub struct DeclareCommands {
+    pub nodes: Vec<CommandNode>,
+    pub root: u32,
+}
+
+#[derive(Debug)]
+pub struct SpawnPlayer {
+    pub entity_id: u32,
+    pub uuid: u128,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+/// Spawns a non-living entity, like a dropped item.
+#[derive(Debug)]
+pub struct SpawnObject {
+    pub entity_id: u32,
+    pub uuid: u128,
+    /// 2 for items.
+    pub kind: u8,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub pitch: f32,
+    pub yaw: f32,
+    /// Depends on the kind, items need it to be 1.
+    pub data: i32,
+    pub velocity: (f64, f64, f64),
+}
+#[derive(Debug, Clone)]
+pub enum MetadataValue {
+    Byte(i8),
+    VarInt(u32),
+    Slot(Slot),
+    Boolean(bool),
+}
+#[derive(Debug)]
+pub struct EntityMetadata {
+    pub entity_id: u32,
+    /// Only the entries that changed, by index.
+    pub entries: Vec<(u8, MetadataValue)>,
+}
+/// Shows an item flying into whoever picked it up. The item still has to be destroyed.
+#[derive(Debug)]
+pub struct CollectItem {
+    pub collected: u32,
+    pub collector: u32,
+    pub count: u32,
+}
+#[derive(Debug)]
+pub struct DestroyEntities(pub Vec<u32>);
+/// Triggers an entity effect on the client, what `status` means depends on the entity.
+#[derive(Debug)]
+pub struct EntityStatus {
+    pub entity_id: u32,
+    pub status: i8,
+}
+impl EntityStatus {
+    /// Tells a player their own operator level, which decides what the client lets them do,
+    /// like switching gamemodes with F3+F4.
+    pub fn op_level(entity_id: u32, level: u8) -> Self {
+        Self {
+            entity_id,
+            status: 24 + level.min(4) as i8,
+        }
+    }
+}
+/// Movement of less than 8 blocks, in 1/4096ths of a block.
+#[derive(Debug)]
+pub struct EntityRelativeMove {
+    pub entity_id: u32,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub on_ground: bool,
+}
+#[derive(Debug)]
+pub struct EntityLookAndRelativeMove {
+    pub entity_id: u32,
+    pub delta_x: i16,
+    pub delta_y: i16,
+    pub delta_z: i16,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+#[derive(Debug)]
+pub struct EntityLook {
+    pub entity_id: u32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+#[derive(Debug)]
+pub struct EntityTeleport {
+    pub entity_id: u32,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+}
+#[derive(Debug)]
+pub struct EntityHeadLook {
+    pub entity_id: u32,
+    pub head_yaw: f32,
+}
+/// Lets the client predict where an entity goes between position updates.
+#[derive(Debug)]
+pub struct EntityVelocity {
+    pub entity_id: u32,
+    /// In blocks per tick.
+    pub velocity: (f64, f64, f64),
+}
+#[derive(Debug)]
+pub enum PlayerListAction {
+    AddPlayer {
+        name: String,
+        gamemode: u32,
+        ping: u32,
+        display_name: Option<String>,
+    },
+    UpdateGamemode(u32),
+    /// Round trip time in milliseconds.
+    UpdateLatency(u32),
+    /// A json text component, `None` goes back to showing the name.
+    UpdateDisplayName(Option<String>),
+    RemovePlayer,
+}
+/// All entries have to use the same kind of action.
+#[derive(Debug)]
+pub struct PlayerListItem(pub Vec<(u128, PlayerListAction)>);
+#[derive(Debug, Clone)]
+pub enum ObjectiveAction {
+    /// Render type is `integer` or `hearts`.
+    Create {
+        display_name: String,
+        render_type: String,
+    },
+    Remove,
+    Update {
+        display_name: String,
+        render_type: String,
+    },
+}
+#[derive(Debug, Clone)]
+pub struct ScoreboardObjective {
+    pub name: String,
+    pub action: ObjectiveAction,
+}
+/// Sets a score, or removes it with `None`.
+#[derive(Debug, Clone)]
+pub struct UpdateScore {
+    pub entry: String,
+    pub objective: String,
+    pub value: Option<i32>,
+}
+/// Shows an objective in a display slot, an empty name clears it.
+#[derive(Debug, Clone)]
+pub struct DisplayScoreboard {
+    /// 0 for the list, 1 for the sidebar and 2 below names.
+    pub position: u8,
+    pub objective: String,
+}
+#[derive(Debug, Clone)]
+pub struct TeamInfo {
+    pub display_name: String,
+    pub prefix: String,
+    pub suffix: String,
+    /// 0x01 allows friendly fire, 0x02 shows invisible teammates.
+    pub friendly_flags: u8,
+    pub name_tag_visibility: String,
+    pub collision_rule: String,
+    /// A chat color id, -1 for none.
+    pub color: i8,
+}
+#[derive(Debug, Clone)]
+pub enum TeamsAction {
+    Create(TeamInfo, Vec<String>),
+    Remove,
+    UpdateInfo(TeamInfo),
+    AddEntries(Vec<String>),
+    RemoveEntries(Vec<String>),
+}
+#[derive(Debug, Clone)]
+pub struct Teams {
+    pub name: String,
+    pub action: TeamsAction,
+}
+/// A sound played at a position, looked up by name, like `entity.player.levelup`.
+#[derive(Debug, Clone)]
+pub struct NamedSoundEffect {
+    pub name: String,
+    pub category: u32,
+    pub position: (f64, f64, f64),
+    /// 1 is normal, higher values are heard from further away.
+    pub volume: f32,
+    /// From 0.5 to 2.
+    pub pitch: f32,
+}
+#[derive(Debug, Clone)]
+pub struct Particle {
+    pub particle_id: i32,
+    /// Shows the particles however far away the client is, instead of within 32 blocks.
+    pub long_distance: bool,
+    pub position: (f32, f32, f32),
+    /// Particles are spread around the position by a gaussian with these deviations.
+    pub offset: (f32, f32, f32),
+    /// Particle speed, or color for some particles.
+    pub data: f32,
+    pub count: i32,
+    /// Item or block for the particles that show one.
+    pub extra: Vec<u32>,
+}
+/// Big text in the middle of the screen, or small text above the hotbar. Texts are json text
+/// components and times are in ticks.
+#[derive(Debug, Clone)]
+pub enum Title {
+    /// Shows the title, together with the last subtitle sent.
+    Text(String),
+    Subtitle(String),
+    ActionBar(String),
+    Times {
+        fade_in: i32,
+        stay: i32,
+        fade_out: i32,
+    },
+    /// Hides the title, keeping the texts and times.
+    Hide,
+    /// Hides the title and forgets the subtitle and times.
+    Reset,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BossBarColor {
+    Pink = 0,
+    Blue = 1,
+    Red = 2,
+    Green = 3,
+    Yellow = 4,
+    Purple = 5,
+    White = 6,
+}
+/// How many notches the bar is split into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BossBarDivision {
+    None = 0,
+    Six = 1,
+    Ten = 2,
+    Twelve = 3,
+    Twenty = 4,
+}
+#[derive(Debug, Clone)]
+pub enum BossBarAction {
+    Add {
+        /// A json text component.
+        title: String,
+        /// From 0 to 1.
+        health: f32,
+        color: BossBarColor,
+        division: BossBarDivision,
+        /// 0x01 darkens the sky, 0x02 plays boss music and 0x04 creates fog.
+        flags: u8,
+    },
+    Remove,
+    UpdateHealth(f32),
+    UpdateTitle(String),
+    UpdateStyle(BossBarColor, BossBarDivision),
+    UpdateFlags(u8),
+}
+#[derive(Debug, Clone)]
+pub struct BossBar {
+    pub uuid: u128,
+    pub action: BossBarAction,
+}
+/// Text shown above and below the player list, as json text components.
+#[derive(Debug)]
+pub struct PlayerListHeaderAndFooter {
+    pub header: String,
+    pub footer: String,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStateReason {
+    InvalidBed = 0,
+    EndRaining = 1,
+    BeginRaining = 2,
+    ChangeGamemode = 3,
+    ExitEnd = 4,
+    DemoMessage = 5,
+    ArrowHittingPlayer = 6,
+    /// The rain level, from 0 to 1.
+    FadeValue = 7,
+    /// The thunder level, from 0 to 1.
+    FadeTime = 8,
+    ElderGuardian = 10,
+}
+#[derive(Debug)]
+pub struct ChangeGameState {
+    pub reason: GameStateReason,
+    pub value: f32,
+}
+#[derive(Debug)]
+pub struct UpdateHealth {
+    pub health: f32,
+    pub food: i32,
+    pub saturation: f32,
+}
+#[derive(Debug)]
+pub struct SetExperience {
+    /// Progress towards the next level, from 0 to 1.
+    pub bar: f32,
+    pub level: i32,
+    pub total: i32,
+}
+#[derive(Debug)]
+pub struct TimeUpdate {
+    pub world_age: i64,
+    /// Negative when the daylight cycle is frozen, so the client doesn't advance it either.
+    pub time_of_day: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiggingStatus {
+    Started = 0,
+    Cancelled = 1,
+    Finished = 2,
+    DropItemStack = 3,
+    DropItem = 4,
+    ShootArrowOrFinishEating = 5,
+    SwapItemInHand = 6,
+}
+#[derive(Debug)]
+pub struct PlayerDigging {
+    pub status: DiggingStatus,
+    pub location: Position,
+    /// 0: -Y, 1: +Y, 2: -Z, 3: +Z, 4: -X, 5: +X
+    pub face: u8,
+}
+#[derive(Debug)]
+pub struct PlayerBlockPlacement {
+    pub location: Position,
+    pub face: u8,
+    /// 0: main hand, 1: off hand
+    pub hand: u32,
+    pub cursor_x: f32,
+    pub cursor_y: f32,
+    pub cursor_z: f32,
+}
+#[derive(Debug)]
+pub struct BlockChange {
+    pub location: Position,
+    pub block_id: u32,
+}
+/// Several block changes in one chunk column.
+#[derive(Debug)]
+pub struct MultiBlockChange {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    /// Positions in the world, which all have to be in this chunk column, and block states.
+    pub records: Vec<(Position, u32)>,
+}
+/// Animates a block, like a chest lid or a note block. What the action and parameter mean
+/// depends on the block.
+#[derive(Debug)]
+pub struct BlockAction {
+    pub location: Position,
+    pub action_id: u8,
+    pub action_param: u8,
+    /// The block id, without meta.
+    pub block_type: u32,
+}
+#[derive(Debug)]
+pub struct UpdateBlockEntity {
+    pub location: Position,
+    /// What kind of block entity this is for, 9 for signs.
+    pub action: u8,
+    pub data: nbt::Blob,
+}
+#[derive(Debug)]
+pub struct OpenSignEditor(pub Position);
+/// The text a player wrote on a sign, sent when they close the editor.
+#[derive(Debug)]
+pub struct UpdateSign {
+    pub location: Position,
+    pub lines: [String; 4],
+}
 
 
 impl<S: Read + Write> Packet<S> for JoinGame {
@@ -150,8 +670,9 @@ impl<S: Read + Write> Packet<S> for ClientSettings {
         let chat_colors = r.read_i8().ok()? == 0x01;
         let displayed_skin_parts = r.read_u8().ok()?;
         let main_hand = r.read_var_u32().ok()?;
-        let use_text_filtering = r.read_i8().ok()? == 0x01;
-        let allow_server_listing = r.read_i8().ok()? == 0x01;
+        // Only sent by newer clients.
+        let use_text_filtering = r.read_i8().map(|b| b == 0x01).unwrap_or(false);
+        let allow_server_listing = r.read_i8().map(|b| b == 0x01).unwrap_or(false);
 
         Some(Self {
             locale,
@@ -197,7 +718,7 @@ impl<S: Read + Write> Packet<S> for PluginMessageS {
 
         dbg!(&String::from_utf8(buf.clone()));
         Some(Self {
-            channel: channel,
+            channel,
             data: buf,
         })
     }
@@ -245,11 +766,8 @@ impl<S: Read + Write> Packet<S> for ServerBoundPlayerPositionAndRotation {
         0x11
     }
 }
-impl<S: Read + Write> Packet<S> for ChunkData {
+impl<S: Read + Write> Packet<S> for ChunkData<'_> {
     fn write_impl(&self) -> Vec<u8> {
-        // let chunk_height = 256;
-        // let section_height = 16;
-
         let v = vec![];
         let mut c = Cursor::new(v);
         c.write_var_u32(0x20).unwrap();
@@ -257,17 +775,22 @@ impl<S: Read + Write> Packet<S> for ChunkData {
         c.write_i32::<BigEndian>(self.chunk_x).unwrap();
         c.write_i32::<BigEndian>(self.chunk_z).unwrap();
         c.write_u8(1).unwrap(); // true  for full
-                                // let mask = u32::MAX;
-        let mask = u32::MAX;
+        let mask = self.column.primary_bit_mask();
         c.write_var_u32(mask).unwrap();
-        c.write_var_u32(0).unwrap();
-        c.write_var_u32(0).unwrap();
 
-        // let mut mask = 0;
-        // let column_buffer = vec![];todo!()
-        // for section in &self.chunk_sections {
-        //     // mask |= (1 << );
-        // }
+        let mut data = vec![];
+        for (i, section) in self.column.sections.iter().enumerate() {
+            if let Some(section) = section.as_ref().filter(|_| mask & 1 << i != 0) {
+                section.write(&mut data);
+            }
+        }
+        data.extend(self.column.biomes.clone().unwrap_or_else(|| vec![1; 256]));
+        c.write_var_u32(data.len() as u32).unwrap();
+        c.write_all(&data).unwrap();
+        c.write_var_u32(self.block_entities.len() as u32).unwrap();
+        for block_entity in &self.block_entities {
+            block_entity.to_writer(&mut c).unwrap();
+        }
 
         c.into_inner()
     }
@@ -319,17 +842,21 @@ impl<S: Read + Write> Packet<S> for ServerBoundKeepAlive {
         0x0b
     }
 }
+impl<S: Read + Write> Packet<S> for TeleportConfirm {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Some(Self(r.read_var_u32().ok()?))
+    }
+    fn get_id() -> u32 {
+        0x00
+    }
+}
 impl ClientBoundKeepAlive {
-    pub fn new() -> Self {
-        Self(
-            // std::time::SystemTime::now()
-            //     .duration_since(SystemTime::UNIX_EPOCH)
-            //     .unwrap()
-            //     .as_millis()
-            //     .try_into()
-            //     .unwrap(),
-            420
-        )
+    /// The client echoes the id back, so it's used to tell which keep alive a response is for.
+    pub fn new(id: i64) -> Self {
+        Self(id)
     }
 }
 impl<S: Read + Write> Packet<S> for ClientBoundChat {
@@ -343,4 +870,999 @@ impl<S: Read + Write> Packet<S> for ClientBoundChat {
     fn get_id() -> u32 {
         0x0f
     }
-}
\ No newline at end of file
+}
+impl<S: Read + Write> Packet<S> for ServerBoundChat {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        // 256 characters, of up to 4 bytes each.
+        read_bounded_string(r, 256 * 4).map(Self)
+    }
+    fn get_id() -> u32 {
+        0x02
+    }
+}
+impl<S: Read + Write> Packet<S> for ServerBoundTabComplete {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        // Vanilla's 32767 characters, of up to 4 bytes each.
+        let text = read_bounded_string(r, 32767 * 4)?;
+        let assume_command = r.read_u8().ok()? != 0;
+        let looked_at_block = if r.read_u8().ok()? != 0 {
+            Some(r.read_position()?)
+        } else {
+            None
+        };
+        Some(Self {
+            text,
+            assume_command,
+            looked_at_block,
+        })
+    }
+    fn get_id() -> u32 {
+        0x01
+    }
+}
+impl<S: Read + Write> Packet<S> for ClientBoundTabComplete {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.0.len() as u32).unwrap();
+        for m in &self.0 {
+            v.write_mc_string(m.clone());
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x0e
+    }
+}
+impl<S: Read + Write> Packet<S> for DeclareCommands {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.nodes.len() as u32).unwrap();
+        for node in &self.nodes {
+            let kind = match node.kind {
+                CommandNodeKind::Root => 0,
+                CommandNodeKind::Literal(_) => 1,
+                CommandNodeKind::Argument { .. } => 2,
+            };
+            v.write_u8(kind | (node.executable as u8) << 2).unwrap();
+            v.write_var_u32(node.children.len() as u32).unwrap();
+            for child in &node.children {
+                v.write_var_u32(*child).unwrap();
+            }
+            match &node.kind {
+                CommandNodeKind::Root => {}
+                CommandNodeKind::Literal(name) => v.write_mc_string(name.clone()),
+                CommandNodeKind::Argument {
+                    name,
+                    parser,
+                    properties,
+                } => {
+                    v.write_mc_string(name.clone());
+                    v.write_mc_string(parser.clone());
+                    v.write_all(properties).unwrap();
+                }
+            }
+        }
+        v.write_var_u32(self.root).unwrap();
+        v
+    }
+    /// The 1.13 id.
+    fn get_id() -> u32 {
+        0x11
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityStatus {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_u32::<BigEndian>(self.entity_id).unwrap();
+        v.write_i8(self.status).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x1b
+    }
+}
+impl<S: Read + Write> Packet<S> for Disconnect {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_mc_string(self.0.clone());
+        v
+    }
+    fn get_id() -> u32 {
+        0x1a
+    }
+}
+impl<S: Read + Write> Packet<S> for PlayerDigging {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let status = match r.read_var_u32().ok()? {
+            0 => DiggingStatus::Started,
+            1 => DiggingStatus::Cancelled,
+            2 => DiggingStatus::Finished,
+            3 => DiggingStatus::DropItemStack,
+            4 => DiggingStatus::DropItem,
+            5 => DiggingStatus::ShootArrowOrFinishEating,
+            6 => DiggingStatus::SwapItemInHand,
+            _ => return None,
+        };
+        let location = r.read_position()?;
+        let face = r.read_u8().ok()?;
+        Some(Self {
+            status,
+            location,
+            face,
+        })
+    }
+    fn get_id() -> u32 {
+        0x14
+    }
+}
+impl<S: Read + Write> Packet<S> for PlayerBlockPlacement {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let location = r.read_position()?;
+        let face = r.read_var_u32().ok()? as u8;
+        let hand = r.read_var_u32().ok()?;
+        let cursor_x = r.read_f32::<BigEndian>().ok()?;
+        let cursor_y = r.read_f32::<BigEndian>().ok()?;
+        let cursor_z = r.read_f32::<BigEndian>().ok()?;
+        Some(Self {
+            location,
+            face,
+            hand,
+            cursor_x,
+            cursor_y,
+            cursor_z,
+        })
+    }
+    fn get_id() -> u32 {
+        0x1f
+    }
+}
+impl<S: Read + Write> Packet<S> for BlockAction {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_position(self.location);
+        v.write_u8(self.action_id).unwrap();
+        v.write_u8(self.action_param).unwrap();
+        v.write_var_u32(self.block_type).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x0a
+    }
+}
+impl<S: Read + Write> Packet<S> for UpdateBlockEntity {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_position(self.location);
+        v.write_u8(self.action).unwrap();
+        self.data.to_writer(&mut v).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x09
+    }
+}
+impl<S: Read + Write> Packet<S> for OpenSignEditor {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_position(self.0);
+        v
+    }
+    fn get_id() -> u32 {
+        0x2a
+    }
+}
+impl<S: Read + Write> Packet<S> for UpdateSign {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let location = r.read_position()?;
+        // Vanilla allows 384 bytes a line.
+        let lines = [
+            read_bounded_string(r, 384)?,
+            read_bounded_string(r, 384)?,
+            read_bounded_string(r, 384)?,
+            read_bounded_string(r, 384)?,
+        ];
+        Some(Self { location, lines })
+    }
+    fn get_id() -> u32 {
+        0x1c
+    }
+}
+impl<S: Read + Write> Packet<S> for BlockChange {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_position(self.location);
+        v.write_var_u32(self.block_id).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x0b
+    }
+}
+impl<S: Read + Write> Packet<S> for MultiBlockChange {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_i32::<BigEndian>(self.chunk_x).unwrap();
+        v.write_i32::<BigEndian>(self.chunk_z).unwrap();
+        v.write_var_u32(self.records.len() as u32).unwrap();
+        for (position, block_id) in &self.records {
+            v.write_u8(((position.x & 0xf) << 4 | (position.z & 0xf)) as u8)
+                .unwrap();
+            v.write_u8(position.y as u8).unwrap();
+            v.write_var_u32(*block_id).unwrap();
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x10
+    }
+}
+impl<S: Read + Write> Packet<S> for ServerBoundHeldItemChange {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Some(Self {
+            slot: r.read_i16::<BigEndian>().ok()?,
+        })
+    }
+    fn get_id() -> u32 {
+        0x1a
+    }
+}
+impl<S: Read + Write> Packet<S> for ClickWindow {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let window_id = r.read_u8().ok()?;
+        let slot = r.read_i16::<BigEndian>().ok()?;
+        let button = r.read_i8().ok()?;
+        let action_number = r.read_i16::<BigEndian>().ok()?;
+        let mode = r.read_var_u32().ok()?;
+        let clicked_item = r.read_slot()?;
+        Some(Self {
+            window_id,
+            slot,
+            button,
+            action_number,
+            mode,
+            clicked_item,
+        })
+    }
+    fn get_id() -> u32 {
+        0x07
+    }
+}
+impl<S: Read + Write> Packet<S> for CreativeInventoryAction {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let slot = r.read_i16::<BigEndian>().ok()?;
+        let clicked_item = r.read_slot()?;
+        Some(Self { slot, clicked_item })
+    }
+    fn get_id() -> u32 {
+        0x1b
+    }
+}
+impl<S: Read + Write> Packet<S> for CloseWindow {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Some(Self {
+            window_id: r.read_u8().ok()?,
+        })
+    }
+    fn get_id() -> u32 {
+        0x08
+    }
+}
+impl<S: Read + Write> Packet<S> for ServerBoundConfirmTransaction {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let window_id = r.read_i8().ok()?;
+        let action_number = r.read_i16::<BigEndian>().ok()?;
+        let accepted = r.read_u8().ok()? == 1;
+        Some(Self {
+            window_id,
+            action_number,
+            accepted,
+        })
+    }
+    fn get_id() -> u32 {
+        0x05
+    }
+}
+impl<S: Read + Write> Packet<S> for ClientBoundConfirmTransaction {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_i8(self.window_id).unwrap();
+        v.write_i16::<BigEndian>(self.action_number).unwrap();
+        v.write_u8(self.accepted as u8).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x11
+    }
+}
+impl<S: Read + Write> Packet<S> for ClientBoundCloseWindow {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_u8(self.0).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x12
+    }
+}
+impl<S: Read + Write> Packet<S> for OpenWindow {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_u8(self.window_id).unwrap();
+        v.write_mc_string(self.window_type.clone());
+        v.write_mc_string(self.title.clone());
+        v.write_u8(self.slot_count).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x13
+    }
+}
+impl<S: Read + Write> Packet<S> for WindowItems {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_u8(self.window_id).unwrap();
+        v.write_i16::<BigEndian>(self.slots.len() as i16).unwrap();
+        for slot in &self.slots {
+            v.write_slot(slot);
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x14
+    }
+}
+impl<S: Read + Write> Packet<S> for SetSlot {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_i8(self.window_id).unwrap();
+        v.write_i16::<BigEndian>(self.slot).unwrap();
+        v.write_slot(&self.slot_data);
+        v
+    }
+    fn get_id() -> u32 {
+        0x16
+    }
+}
+impl<S: Read + Write> Packet<S> for PlayerLook {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let yaw = r.read_f32::<BigEndian>().ok()?;
+        let pitch = r.read_f32::<BigEndian>().ok()?;
+        let on_ground = r.read_i8().ok()? == 1;
+        Some(Self {
+            yaw,
+            pitch,
+            on_ground,
+        })
+    }
+    fn get_id() -> u32 {
+        0x0f
+    }
+}
+impl<S: Read + Write> Packet<S> for PlayerOnGround {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Some(Self {
+            on_ground: r.read_i8().ok()? == 1,
+        })
+    }
+    fn get_id() -> u32 {
+        0x0c
+    }
+}
+impl<S: Read + Write> Packet<S> for ServerBoundPlayerAbilities {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Some(Self {
+            flags: r.read_u8().ok()?,
+            flying_speed: r.read_f32::<BigEndian>().ok()?,
+            walking_speed: r.read_f32::<BigEndian>().ok()?,
+        })
+    }
+    fn get_id() -> u32 {
+        0x13
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityAction {
+    fn read(r: &mut S) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Some(Self {
+            entity_id: r.read_var_u32().ok()?,
+            action: r.read_var_u32().ok()?,
+            jump_boost: r.read_var_u32().ok()?,
+        })
+    }
+    fn get_id() -> u32 {
+        0x15
+    }
+}
+impl<S: Read + Write> Packet<S> for SpawnPlayer {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        v.write_u128::<BigEndian>(self.uuid).unwrap();
+        v.write_f64::<BigEndian>(self.x).unwrap();
+        v.write_f64::<BigEndian>(self.y).unwrap();
+        v.write_f64::<BigEndian>(self.z).unwrap();
+        v.write_u8(to_angle(self.yaw)).unwrap();
+        v.write_u8(to_angle(self.pitch)).unwrap();
+        // No metadata
+        v.write_u8(0xff).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x05
+    }
+}
+impl<S: Read + Write> Packet<S> for DestroyEntities {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.0.len() as u32).unwrap();
+        for id in &self.0 {
+            v.write_var_u32(*id).unwrap();
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x32
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityRelativeMove {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        v.write_i16::<BigEndian>(self.delta_x).unwrap();
+        v.write_i16::<BigEndian>(self.delta_y).unwrap();
+        v.write_i16::<BigEndian>(self.delta_z).unwrap();
+        v.write_u8(self.on_ground as u8).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x26
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityLookAndRelativeMove {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        v.write_i16::<BigEndian>(self.delta_x).unwrap();
+        v.write_i16::<BigEndian>(self.delta_y).unwrap();
+        v.write_i16::<BigEndian>(self.delta_z).unwrap();
+        v.write_u8(to_angle(self.yaw)).unwrap();
+        v.write_u8(to_angle(self.pitch)).unwrap();
+        v.write_u8(self.on_ground as u8).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x27
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityLook {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        v.write_u8(to_angle(self.yaw)).unwrap();
+        v.write_u8(to_angle(self.pitch)).unwrap();
+        v.write_u8(self.on_ground as u8).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x28
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityTeleport {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        v.write_f64::<BigEndian>(self.x).unwrap();
+        v.write_f64::<BigEndian>(self.y).unwrap();
+        v.write_f64::<BigEndian>(self.z).unwrap();
+        v.write_u8(to_angle(self.yaw)).unwrap();
+        v.write_u8(to_angle(self.pitch)).unwrap();
+        v.write_u8(self.on_ground as u8).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x4c
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityHeadLook {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        v.write_u8(to_angle(self.head_yaw)).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x36
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityVelocity {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        write_velocity(&mut v, self.velocity);
+        v
+    }
+    fn get_id() -> u32 {
+        0x3e
+    }
+}
+/// Writes a velocity in units of 1/8000 blocks per tick, clamped like vanilla.
+fn write_velocity(v: &mut Vec<u8>, (x, y, z): (f64, f64, f64)) {
+    for component in [x, y, z] {
+        v.write_i16::<BigEndian>((component.clamp(-3.9, 3.9) * 8000.0) as i16)
+            .unwrap();
+    }
+}
+impl<S: Read + Write> Packet<S> for SpawnObject {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        v.write_u128::<BigEndian>(self.uuid).unwrap();
+        v.write_u8(self.kind).unwrap();
+        v.write_f64::<BigEndian>(self.x).unwrap();
+        v.write_f64::<BigEndian>(self.y).unwrap();
+        v.write_f64::<BigEndian>(self.z).unwrap();
+        v.write_u8(to_angle(self.pitch)).unwrap();
+        v.write_u8(to_angle(self.yaw)).unwrap();
+        v.write_i32::<BigEndian>(self.data).unwrap();
+        write_velocity(&mut v, self.velocity);
+        v
+    }
+    fn get_id() -> u32 {
+        0x00
+    }
+}
+impl<S: Read + Write> Packet<S> for EntityMetadata {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.entity_id).unwrap();
+        for (index, value) in &self.entries {
+            v.write_u8(*index).unwrap();
+            match value {
+                MetadataValue::Byte(b) => {
+                    v.write_var_u32(0).unwrap();
+                    v.write_i8(*b).unwrap();
+                }
+                MetadataValue::VarInt(i) => {
+                    v.write_var_u32(1).unwrap();
+                    v.write_var_u32(*i).unwrap();
+                }
+                MetadataValue::Slot(slot) => {
+                    v.write_var_u32(5).unwrap();
+                    v.write_slot(slot);
+                }
+                MetadataValue::Boolean(b) => {
+                    v.write_var_u32(6).unwrap();
+                    v.write_u8(*b as u8).unwrap();
+                }
+            }
+        }
+        v.write_u8(0xff).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x3c
+    }
+}
+impl<S: Read + Write> Packet<S> for CollectItem {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_var_u32(self.collected).unwrap();
+        v.write_var_u32(self.collector).unwrap();
+        v.write_var_u32(self.count).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x4b
+    }
+}
+impl<S: Read + Write> Packet<S> for PlayerListItem {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        let action = match self.0.first() {
+            Some((_, PlayerListAction::AddPlayer { .. })) | None => 0,
+            Some((_, PlayerListAction::UpdateGamemode(_))) => 1,
+            Some((_, PlayerListAction::UpdateLatency(_))) => 2,
+            Some((_, PlayerListAction::UpdateDisplayName(_))) => 3,
+            Some((_, PlayerListAction::RemovePlayer)) => 4,
+        };
+        v.write_var_u32(action).unwrap();
+        v.write_var_u32(self.0.len() as u32).unwrap();
+        for (uuid, action) in &self.0 {
+            v.write_u128::<BigEndian>(*uuid).unwrap();
+            match action {
+                PlayerListAction::AddPlayer {
+                    name,
+                    gamemode,
+                    ping,
+                    display_name,
+                } => {
+                    v.write_mc_string(name.clone());
+                    // No skin properties
+                    v.write_var_u32(0).unwrap();
+                    v.write_var_u32(*gamemode).unwrap();
+                    v.write_var_u32(*ping).unwrap();
+                    write_optional_string(&mut v, display_name);
+                }
+                PlayerListAction::UpdateGamemode(gamemode) => {
+                    v.write_var_u32(*gamemode).unwrap();
+                }
+                PlayerListAction::UpdateLatency(ping) => {
+                    v.write_var_u32(*ping).unwrap();
+                }
+                PlayerListAction::UpdateDisplayName(display_name) => {
+                    write_optional_string(&mut v, display_name);
+                }
+                PlayerListAction::RemovePlayer => {}
+            }
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x2e
+    }
+}
+fn write_optional_string(v: &mut Vec<u8>, s: &Option<String>) {
+    v.write_u8(s.is_some() as u8).unwrap();
+    if let Some(s) = s {
+        v.write_mc_string(s.clone());
+    }
+}
+impl<S: Read + Write> Packet<S> for ScoreboardObjective {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_mc_string(self.name.clone());
+        let (mode, display) = match &self.action {
+            ObjectiveAction::Create {
+                display_name,
+                render_type,
+            } => (0, Some((display_name, render_type))),
+            ObjectiveAction::Remove => (1, None),
+            ObjectiveAction::Update {
+                display_name,
+                render_type,
+            } => (2, Some((display_name, render_type))),
+        };
+        v.write_u8(mode).unwrap();
+        if let Some((display_name, render_type)) = display {
+            v.write_mc_string(display_name.clone());
+            v.write_mc_string(render_type.clone());
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x42
+    }
+}
+impl<S: Read + Write> Packet<S> for UpdateScore {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_mc_string(self.entry.clone());
+        v.write_u8(self.value.is_none() as u8).unwrap();
+        v.write_mc_string(self.objective.clone());
+        if let Some(value) = self.value {
+            v.write_var_i32(value).unwrap();
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x45
+    }
+}
+impl<S: Read + Write> Packet<S> for DisplayScoreboard {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_u8(self.position).unwrap();
+        v.write_mc_string(self.objective.clone());
+        v
+    }
+    fn get_id() -> u32 {
+        0x3b
+    }
+}
+fn write_team_info(v: &mut Vec<u8>, info: &TeamInfo) {
+    v.write_mc_string(info.display_name.clone());
+    v.write_mc_string(info.prefix.clone());
+    v.write_mc_string(info.suffix.clone());
+    v.write_u8(info.friendly_flags).unwrap();
+    v.write_mc_string(info.name_tag_visibility.clone());
+    v.write_mc_string(info.collision_rule.clone());
+    v.write_i8(info.color).unwrap();
+}
+fn write_entries(v: &mut Vec<u8>, entries: &[String]) {
+    v.write_var_u32(entries.len() as u32).unwrap();
+    for entry in entries {
+        v.write_mc_string(entry.clone());
+    }
+}
+impl<S: Read + Write> Packet<S> for Teams {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_mc_string(self.name.clone());
+        match &self.action {
+            TeamsAction::Create(info, entries) => {
+                v.write_u8(0).unwrap();
+                write_team_info(&mut v, info);
+                write_entries(&mut v, entries);
+            }
+            TeamsAction::Remove => v.write_u8(1).unwrap(),
+            TeamsAction::UpdateInfo(info) => {
+                v.write_u8(2).unwrap();
+                write_team_info(&mut v, info);
+            }
+            TeamsAction::AddEntries(entries) => {
+                v.write_u8(3).unwrap();
+                write_entries(&mut v, entries);
+            }
+            TeamsAction::RemoveEntries(entries) => {
+                v.write_u8(4).unwrap();
+                write_entries(&mut v, entries);
+            }
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x44
+    }
+}
+/// Sound positions are sent in eighths of a block.
+fn write_sound_position(v: &mut Vec<u8>, (x, y, z): (f64, f64, f64)) {
+    for c in [x, y, z] {
+        v.write_i32::<BigEndian>((c * 8.0) as i32).unwrap();
+    }
+}
+impl<S: Read + Write> Packet<S> for NamedSoundEffect {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_mc_string(self.name.clone());
+        v.write_var_u32(self.category).unwrap();
+        write_sound_position(&mut v, self.position);
+        v.write_f32::<BigEndian>(self.volume).unwrap();
+        v.write_f32::<BigEndian>(self.pitch).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x19
+    }
+}
+impl<S: Read + Write> Packet<S> for Particle {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_i32::<BigEndian>(self.particle_id).unwrap();
+        v.write_u8(self.long_distance as u8).unwrap();
+        let (x, y, z) = self.position;
+        let (dx, dy, dz) = self.offset;
+        for f in [x, y, z, dx, dy, dz, self.data] {
+            v.write_f32::<BigEndian>(f).unwrap();
+        }
+        v.write_i32::<BigEndian>(self.count).unwrap();
+        for extra in &self.extra {
+            v.write_var_u32(*extra).unwrap();
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x22
+    }
+}
+impl<S: Read + Write> Packet<S> for Title {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        match self {
+            Title::Text(text) => {
+                v.write_var_u32(0).unwrap();
+                v.write_mc_string(text.clone());
+            }
+            Title::Subtitle(text) => {
+                v.write_var_u32(1).unwrap();
+                v.write_mc_string(text.clone());
+            }
+            Title::ActionBar(text) => {
+                v.write_var_u32(2).unwrap();
+                v.write_mc_string(text.clone());
+            }
+            Title::Times {
+                fade_in,
+                stay,
+                fade_out,
+            } => {
+                v.write_var_u32(3).unwrap();
+                v.write_i32::<BigEndian>(*fade_in).unwrap();
+                v.write_i32::<BigEndian>(*stay).unwrap();
+                v.write_i32::<BigEndian>(*fade_out).unwrap();
+            }
+            Title::Hide => {
+                v.write_var_u32(4).unwrap();
+            }
+            Title::Reset => {
+                v.write_var_u32(5).unwrap();
+            }
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x48
+    }
+}
+impl<S: Read + Write> Packet<S> for BossBar {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_u128::<BigEndian>(self.uuid).unwrap();
+        match &self.action {
+            BossBarAction::Add {
+                title,
+                health,
+                color,
+                division,
+                flags,
+            } => {
+                v.write_var_u32(0).unwrap();
+                v.write_mc_string(title.clone());
+                v.write_f32::<BigEndian>(*health).unwrap();
+                v.write_var_u32(*color as u32).unwrap();
+                v.write_var_u32(*division as u32).unwrap();
+                v.write_u8(*flags).unwrap();
+            }
+            BossBarAction::Remove => {
+                v.write_var_u32(1).unwrap();
+            }
+            BossBarAction::UpdateHealth(health) => {
+                v.write_var_u32(2).unwrap();
+                v.write_f32::<BigEndian>(*health).unwrap();
+            }
+            BossBarAction::UpdateTitle(title) => {
+                v.write_var_u32(3).unwrap();
+                v.write_mc_string(title.clone());
+            }
+            BossBarAction::UpdateStyle(color, division) => {
+                v.write_var_u32(4).unwrap();
+                v.write_var_u32(*color as u32).unwrap();
+                v.write_var_u32(*division as u32).unwrap();
+            }
+            BossBarAction::UpdateFlags(flags) => {
+                v.write_var_u32(5).unwrap();
+                v.write_u8(*flags).unwrap();
+            }
+        }
+        v
+    }
+    fn get_id() -> u32 {
+        0x0c
+    }
+}
+impl<S: Read + Write> Packet<S> for PlayerListHeaderAndFooter {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_mc_string(self.header.clone());
+        v.write_mc_string(self.footer.clone());
+        v
+    }
+    fn get_id() -> u32 {
+        0x4a
+    }
+}
+impl<S: Read + Write> Packet<S> for ChangeGameState {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_u8(self.reason as u8).unwrap();
+        v.write_f32::<BigEndian>(self.value).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x1e
+    }
+}
+impl<S: Read + Write> Packet<S> for UpdateHealth {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_f32::<BigEndian>(self.health).unwrap();
+        v.write_var_i32(self.food).unwrap();
+        v.write_f32::<BigEndian>(self.saturation).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x41
+    }
+}
+impl<S: Read + Write> Packet<S> for SetExperience {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_f32::<BigEndian>(self.bar).unwrap();
+        v.write_var_i32(self.level).unwrap();
+        v.write_var_i32(self.total).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x40
+    }
+}
+impl<S: Read + Write> Packet<S> for TimeUpdate {
+    fn write_impl(&self) -> Vec<u8> {
+        let mut v = vec![];
+        v.write_var_u32(<Self as Packet<S>>::get_id()).unwrap();
+        v.write_i64::<BigEndian>(self.world_age).unwrap();
+        v.write_i64::<BigEndian>(self.time_of_day).unwrap();
+        v
+    }
+    fn get_id() -> u32 {
+        0x47
+    }
+}
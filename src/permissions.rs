@@ -0,0 +1,140 @@
+//! Who may do what: operators from vanilla's `ops.json`, and permission nodes like
+//! `mycelium.command.tp` handed out through groups in `permissions.json`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{error, info};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::entity::{parse_uuid, uuid_string};
+
+pub const OPS_FILE: &str = "ops.json";
+pub const PERMISSIONS_FILE: &str = "permissions.json";
+/// The level `/op` gives, vanilla's default `op-permission-level`.
+pub const DEFAULT_OP_LEVEL: u8 = 4;
+/// Everyone is in this group, whether they are listed in the file or not.
+const DEFAULT_GROUP: &str = "default";
+
+/// Reads a json file, falling back to the default if it's missing or broken.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    match fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+            error!("Couldn't parse {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => {
+            info!("{} doesn't exist, starting empty.", path.display());
+            T::default()
+        }
+    }
+}
+pub fn save_json<T: Serialize>(path: &Path, value: &T) {
+    let s = serde_json::to_string_pretty(value).unwrap();
+    if let Err(e) = fs::write(path, s) {
+        error!("Couldn't save {}: {}", path.display(), e);
+    }
+}
+
+/// One entry of `ops.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operator {
+    pub uuid: String,
+    pub name: String,
+    pub level: u8,
+    #[serde(default)]
+    pub bypasses_player_limit: bool,
+}
+
+#[derive(Debug)]
+pub struct Operators {
+    path: PathBuf,
+    entries: Vec<Operator>,
+}
+impl Operators {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let entries = load_json(&path);
+        Self { path, entries }
+    }
+    pub fn save(&self) {
+        save_json(&self.path, &self.entries);
+    }
+    fn get(&self, uuid: u128) -> Option<&Operator> {
+        self.entries
+            .iter()
+            .find(|o| parse_uuid(&o.uuid) == Some(uuid))
+    }
+    /// The operator level of a player, 0 for everyone who isn't an operator.
+    pub fn level(&self, uuid: u128) -> u8 {
+        self.get(uuid).map_or(0, |o| o.level.min(4))
+    }
+    pub fn bypasses_player_limit(&self, uuid: u128) -> bool {
+        self.get(uuid).is_some_and(|o| o.bypasses_player_limit)
+    }
+    /// Makes a player an operator with the given level, or removes them for level 0.
+    pub fn set(&mut self, uuid: u128, name: &str, level: u8) {
+        self.entries.retain(|o| parse_uuid(&o.uuid) != Some(uuid));
+        if level > 0 {
+            self.entries.push(Operator {
+                uuid: uuid_string(uuid),
+                name: name.to_string(),
+                level,
+                bypasses_player_limit: false,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Group {
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlayerPermissions {
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+/// The contents of `permissions.json`. Players are listed by UUID or name, and get the nodes
+/// of their groups on top of their own. A node ending in `.*` grants everything below it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Permissions {
+    #[serde(default)]
+    pub groups: HashMap<String, Group>,
+    #[serde(default)]
+    pub players: HashMap<String, PlayerPermissions>,
+}
+impl Permissions {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        load_json(path.as_ref())
+    }
+    pub fn has(&self, uuid: u128, name: &str, node: &str) -> bool {
+        let player = self
+            .players
+            .iter()
+            .find(|(key, _)| parse_uuid(key) == Some(uuid) || key.eq_ignore_ascii_case(name))
+            .map(|(_, p)| p);
+        let groups = std::iter::once(DEFAULT_GROUP).chain(
+            player
+                .iter()
+                .flat_map(|p| p.groups.iter().map(String::as_str)),
+        );
+        groups
+            .filter_map(|g| self.groups.get(g))
+            .flat_map(|g| &g.permissions)
+            .chain(player.iter().flat_map(|p| &p.permissions))
+            .any(|granted| grants(granted, node))
+    }
+}
+fn grants(granted: &str, node: &str) -> bool {
+    match granted.strip_suffix('*') {
+        Some(prefix) => node.starts_with(prefix),
+        None => granted == node,
+    }
+}
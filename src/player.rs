@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+
+use crate::entity::Location;
+use crate::inventory::{self, Inventory};
+use crate::movement::PLAYER_EYE_HEIGHT;
+use crate::packets::play::{ChatPosition, Title};
+use crate::{
+    packet::Position,
+    world::block::{self, BlockState},
+};
+
+/// The sign item, which places a standing or wall sign.
+const SIGN: i16 = 323;
+const REDSTONE: i16 = 331;
+const REPEATER: i16 = 356;
+pub const BUCKET: i16 = 325;
+pub const WATER_BUCKET: i16 = 326;
+pub const LAVA_BUCKET: i16 = 327;
+/// How far from their eyes players can break and place blocks.
+const REACH: f64 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gamemode {
+    Survival = 0,
+    Creative = 1,
+    Adventure = 2,
+    Spectator = 3,
+}
+impl Gamemode {
+    /// Accepts the full name, its first letter(s) or the number, like vanilla's /gamemode.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "survival" | "s" | "0" => Some(Self::Survival),
+            "creative" | "c" | "1" => Some(Self::Creative),
+            "adventure" | "a" | "2" => Some(Self::Adventure),
+            "spectator" | "sp" | "3" => Some(Self::Spectator),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Survival => "survival",
+            Self::Creative => "creative",
+            Self::Adventure => "adventure",
+            Self::Spectator => "spectator",
+        }
+    }
+}
+
+/// Which messages a player wants to see, from their client settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatMode {
+    Enabled = 0,
+    CommandsOnly = 1,
+    Hidden = 2,
+}
+impl ChatMode {
+    pub fn from_id(id: u32) -> Self {
+        match id {
+            1 => Self::CommandsOnly,
+            2 => Self::Hidden,
+            _ => Self::Enabled,
+        }
+    }
+    /// Whether a message sent in the given position should reach the player.
+    pub fn accepts(self, position: ChatPosition) -> bool {
+        match self {
+            Self::Enabled => true,
+            Self::CommandsOnly => position != ChatPosition::Chat,
+            Self::Hidden => position == ChatPosition::GameInfo,
+        }
+    }
+}
+
+/// Server side state of a player that has finished logging in.
+#[derive(Debug)]
+pub struct Player {
+    pub name: String,
+    pub uuid: u128,
+    pub entity_id: u32,
+    pub gamemode: Gamemode,
+    pub location: Location,
+    pub on_ground: bool,
+    pub sneaking: bool,
+    pub sprinting: bool,
+    /// Only believed in creative, spectators are always flying.
+    pub flying: bool,
+    /// Id of the last position sent to the client, counting up from 0 which is used on join.
+    pub teleport_id: u32,
+    /// The tick the last position was sent on, until the client confirms it. Movement is
+    /// ignored meanwhile, since the client hasn't moved to the new position yet.
+    pub awaiting_teleport: Option<u64>,
+    /// Smoothed keep alive round trip time in milliseconds, shown in the player list.
+    pub ping: u32,
+    /// Json text component shown in the player list instead of the name.
+    pub display_name: Option<String>,
+    pub chat_mode: ChatMode,
+    /// Operator level from 0 to 4, deciding which commands the player can run.
+    pub op_level: u8,
+    /// Protocol version of the client.
+    pub protocol: u32,
+    /// The location other players last had sent to them.
+    pub last_sent: Location,
+    /// Entity ids of everything this player has been sent a spawn packet for.
+    pub tracking: HashSet<u32>,
+    /// The block being mined in survival, and the tick mining started on.
+    pub digging: Option<(Position, u64)>,
+    pub inventory: Inventory,
+    /// Half hearts, from 0 to 20.
+    pub health: f32,
+    pub food: i32,
+    pub saturation: f32,
+    pub xp_level: i32,
+    /// Progress towards the next level, from 0 to 1.
+    pub xp_progress: f32,
+    pub xp_total: i32,
+    /// Title packets waiting to be sent at the end of the tick.
+    pub titles: Vec<Title>,
+    /// The chest the player has open, with the id of its window.
+    pub open_window: Option<(u8, Position)>,
+    /// Id of the last window opened, window ids go from 1 to 100 and wrap around.
+    pub window_id: u8,
+}
+impl Player {
+    pub fn new(
+        name: String,
+        uuid: u128,
+        entity_id: u32,
+        gamemode: Gamemode,
+        location: Location,
+        protocol: u32,
+    ) -> Self {
+        Self {
+            name,
+            uuid,
+            entity_id,
+            gamemode,
+            location,
+            on_ground: false,
+            sneaking: false,
+            sprinting: false,
+            flying: false,
+            teleport_id: 0,
+            awaiting_teleport: None,
+            ping: 0,
+            display_name: None,
+            chat_mode: ChatMode::Enabled,
+            op_level: 0,
+            protocol,
+            last_sent: location,
+            tracking: HashSet::new(),
+            digging: None,
+            inventory: Inventory::new(),
+            health: 20.0,
+            food: 20,
+            saturation: 5.0,
+            xp_level: 0,
+            xp_progress: 0.0,
+            xp_total: 0,
+            titles: vec![],
+            open_window: None,
+            window_id: 0,
+        }
+    }
+    /// Shows a title, with a subtitle if given or the last one sent, both json text components.
+    /// Without times the last ones sent are used, vanilla's default is a 10 tick fade in, 70
+    /// ticks of staying and a 20 tick fade out.
+    pub fn show_title(
+        &mut self,
+        title: &str,
+        subtitle: Option<&str>,
+        times: Option<(i32, i32, i32)>,
+    ) {
+        if let Some((fade_in, stay, fade_out)) = times {
+            self.titles.push(Title::Times {
+                fade_in,
+                stay,
+                fade_out,
+            });
+        }
+        // The subtitle is only shown along with the next title.
+        if let Some(subtitle) = subtitle {
+            self.titles.push(Title::Subtitle(subtitle.to_string()));
+        }
+        self.titles.push(Title::Text(title.to_string()));
+    }
+    /// Shows a json text component above the hotbar.
+    pub fn show_action_bar(&mut self, text: &str) {
+        self.titles.push(Title::ActionBar(text.to_string()));
+    }
+    pub fn clear_title(&mut self) {
+        self.titles.push(Title::Hide);
+    }
+    /// Clears the title and sets the times back to the defaults.
+    pub fn reset_title(&mut self) {
+        self.titles.push(Title::Reset);
+    }
+    pub fn next_window_id(&mut self) -> u8 {
+        self.window_id = self.window_id % 100 + 1;
+        self.window_id
+    }
+    /// The held item as a tool, `None` for a bare hand or anything else.
+    pub fn held_tool(&self) -> Option<block::Tool> {
+        inventory::tool(self.inventory.held_item().id)
+    }
+    /// Whether a block is close enough to the player's eyes to break or place.
+    pub fn can_reach(&self, position: Position) -> bool {
+        let dx = position.x as f64 + 0.5 - self.location.x;
+        let dy = position.y as f64 + 0.5 - (self.location.y + PLAYER_EYE_HEIGHT);
+        let dz = position.z as f64 + 0.5 - self.location.z;
+        dx * dx + dy * dy + dz * dz <= REACH * REACH
+    }
+    /// What a right click places, if the held item is a block.
+    pub fn held_block(&self) -> Option<BlockState> {
+        let item = self.inventory.held_item();
+        match item.id {
+            _ if item.is_empty() => None,
+            // Turned into a wall sign when placed on the side of a block.
+            SIGN => Some(block::state(block::STANDING_SIGN, 0)),
+            REDSTONE => Some(block::state(block::REDSTONE_WIRE, 0)),
+            REPEATER => Some(block::state(block::UNPOWERED_REPEATER, 0)),
+            WATER_BUCKET => Some(block::state(block::FLOWING_WATER, 0)),
+            LAVA_BUCKET => Some(block::state(block::FLOWING_LAVA, 0)),
+            // Block items share their id with the block, and their damage is the block's meta.
+            id if id < 256 => Some(block::state(id as u16, item.damage as u8)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! What a player had and where they were, kept between sessions in `playerdata/<uuid>.dat`.
+//! The files are gzipped NBT laid out like vanilla's, so worlds can be moved between the two.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::entity::{uuid_string, Location};
+use crate::inventory::{ARMOR, HOTBAR, MAIN, OFFHAND, PLAYER_INVENTORY_SIZE};
+use crate::packet::Slot;
+use crate::player::{Gamemode, Player};
+use crate::world::{self, block};
+
+pub const PLAYERDATA_DIR: &str = "playerdata";
+/// The data version of 1.12.2.
+const DATA_VERSION: i32 = 1343;
+
+/// One stack of the `Inventory` list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryItem {
+    /// 0-8 for the hotbar, 9-35 for the main inventory, 100-103 for armor from the feet up
+    /// and -106 for the offhand.
+    #[serde(rename = "Slot")]
+    pub slot: i8,
+    pub id: String,
+    #[serde(rename = "Count")]
+    pub count: i8,
+    #[serde(rename = "Damage")]
+    pub damage: i16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<nbt::Blob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerData {
+    #[serde(rename = "Pos")]
+    pub position: Vec<f64>,
+    /// Yaw and pitch.
+    #[serde(rename = "Rotation")]
+    pub rotation: Vec<f32>,
+    #[serde(rename = "OnGround")]
+    pub on_ground: bool,
+    /// Always 0, there is only the overworld.
+    #[serde(rename = "Dimension")]
+    pub dimension: i32,
+    #[serde(rename = "playerGameType")]
+    pub gamemode: i32,
+    #[serde(rename = "Inventory")]
+    pub inventory: Vec<InventoryItem>,
+    #[serde(rename = "SelectedItemSlot")]
+    pub selected_slot: i32,
+    #[serde(rename = "Health")]
+    pub health: f32,
+    #[serde(rename = "foodLevel")]
+    pub food: i32,
+    #[serde(rename = "foodSaturationLevel")]
+    pub saturation: f32,
+    #[serde(rename = "XpLevel")]
+    pub xp_level: i32,
+    /// Progress towards the next level, from 0 to 1.
+    #[serde(rename = "XpP")]
+    pub xp_progress: f32,
+    #[serde(rename = "XpTotal")]
+    pub xp_total: i32,
+    #[serde(rename = "UUIDMost")]
+    pub uuid_most: i64,
+    #[serde(rename = "UUIDLeast")]
+    pub uuid_least: i64,
+    #[serde(rename = "DataVersion")]
+    pub data_version: i32,
+}
+
+fn path(uuid: u128) -> PathBuf {
+    PathBuf::from(PLAYERDATA_DIR).join(format!("{}.dat", uuid_string(uuid)))
+}
+
+/// Where a slot of the player window goes in the `Inventory` list. The crafting grid isn't
+/// saved, it's emptied when the window closes anyway.
+fn nbt_slot(window_slot: usize) -> Option<i8> {
+    match window_slot {
+        s if HOTBAR.contains(&s) => Some((s - HOTBAR.start) as i8),
+        s if MAIN.contains(&s) => Some(s as i8),
+        // The window has the helmet first, the list has the boots first.
+        s if ARMOR.contains(&s) => Some(103 - (s - ARMOR.start) as i8),
+        OFFHAND => Some(-106),
+        _ => None,
+    }
+}
+fn window_slot(slot: i8) -> Option<usize> {
+    (0..PLAYER_INVENTORY_SIZE).find(|s| nbt_slot(*s) == Some(slot))
+}
+
+/// Vanilla's names for the items we know, the numeric id for the rest, which vanilla also
+/// accepts.
+pub fn item_name(id: i16) -> String {
+    let known = (0..256)
+        .contains(&id)
+        .then(|| block::get(block::state(id as u16, 0)));
+    match known {
+        Some(block) if block.id == id as u16 => block.name.to_string(),
+        _ => id.to_string(),
+    }
+}
+pub fn item_id(name: &str) -> Option<i16> {
+    match block::by_name(name) {
+        Some(block) => Some(block.id as i16),
+        None => name.strip_prefix("minecraft:").unwrap_or(name).parse().ok(),
+    }
+}
+
+impl PlayerData {
+    /// `None` for players that haven't been here before, or whose file can't be read.
+    pub fn load(uuid: u128) -> Option<Self> {
+        let path = path(uuid);
+        let file = File::open(&path).ok()?;
+        nbt::from_gzip_reader(BufReader::new(file))
+            .map_err(|e| error!("Couldn't read {}: {}", path.display(), e))
+            .ok()
+    }
+    /// Writes to a temporary file first, so a crash can't leave a broken file behind.
+    pub fn save(&self, uuid: u128) {
+        let path = path(uuid);
+        let temporary = path.with_extension("dat_tmp");
+        let result = fs::create_dir_all(PLAYERDATA_DIR)
+            .and_then(|_| File::create(&temporary))
+            .map_err(nbt::Error::from)
+            .and_then(|file| nbt::to_gzip_writer(&mut BufWriter::new(file), self, None))
+            .and_then(|_| fs::rename(&temporary, &path).map_err(nbt::Error::from));
+        if let Err(e) = result {
+            error!("Couldn't save {}: {}", path.display(), e);
+        }
+    }
+    pub fn from_player(player: &Player) -> Self {
+        let location = player.location;
+        let inventory = player
+            .inventory
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, stack)| !stack.is_empty())
+            .filter_map(|(index, stack)| {
+                Some(InventoryItem {
+                    slot: nbt_slot(index)?,
+                    id: item_name(stack.id),
+                    count: stack.count,
+                    damage: stack.damage,
+                    tag: stack.nbt.clone(),
+                })
+            })
+            .collect();
+        Self {
+            position: vec![location.x, location.y, location.z],
+            rotation: vec![location.yaw, location.pitch],
+            on_ground: player.on_ground,
+            dimension: 0,
+            gamemode: player.gamemode as i32,
+            inventory,
+            selected_slot: player.inventory.selected as i32,
+            health: player.health,
+            food: player.food,
+            saturation: player.saturation,
+            xp_level: player.xp_level,
+            xp_progress: player.xp_progress,
+            xp_total: player.xp_total,
+            uuid_most: (player.uuid >> 64) as i64,
+            uuid_least: player.uuid as i64,
+            data_version: DATA_VERSION,
+        }
+    }
+    /// Where the player was, or spawn if the file doesn't say.
+    pub fn location(&self) -> Location {
+        let mut location = match self.position[..] {
+            [x, y, z] if [x, y, z].iter().all(|c| c.is_finite()) => Location::new(x, y, z),
+            _ => {
+                let (x, y, z) = world::SPAWN;
+                Location::new(x, y, z)
+            }
+        };
+        if let [yaw, pitch] = self.rotation[..] {
+            (location.yaw, location.pitch) = (yaw, pitch);
+        }
+        location
+    }
+    pub fn gamemode(&self) -> Gamemode {
+        Gamemode::from_name(&self.gamemode.to_string()).unwrap_or(Gamemode::Survival)
+    }
+    /// Gives a freshly joined player everything from the file.
+    pub fn apply(&self, player: &mut Player) {
+        player.location = self.location();
+        player.last_sent = player.location;
+        player.on_ground = self.on_ground;
+        player.gamemode = self.gamemode();
+        for item in &self.inventory {
+            let (Some(slot), Some(id)) = (window_slot(item.slot), item_id(&item.id)) else {
+                warn!("{} has an unknown item {:?}.", player.name, item);
+                continue;
+            };
+            player.inventory.slots[slot] = Slot {
+                id,
+                count: item.count,
+                damage: item.damage,
+                nbt: item.tag.clone(),
+            };
+        }
+        player.inventory.selected = self.selected_slot.clamp(0, 8) as usize;
+        player.health = self.health;
+        player.food = self.food;
+        player.saturation = self.saturation;
+        player.xp_level = self.xp_level;
+        player.xp_progress = self.xp_progress;
+        player.xp_total = self.xp_total;
+    }
+}
@@ -0,0 +1,281 @@
+//! Plugins, and the events they get to react to.
+//!
+//! A plugin registers handlers on the event bus when it is loaded. The server fires events on
+//! the tick thread right before it acts on something, and runs the handlers for it from the
+//! lowest priority to the highest, each getting the event and the server. Handlers can
+//! change the event to change what the server does, or cancel it to stop the server from
+//! doing it. Cancelled events still reach the remaining handlers, which can uncancel them.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use crate::entity::Location;
+use crate::packet::Position;
+use crate::server::Server;
+use crate::world::block::BlockState;
+
+pub mod wasm;
+
+/// Plugins compiled into the server, loaded when it starts.
+pub fn builtin() -> Vec<Box<dyn Plugin>> {
+    vec![Box::new(wasm::WasmHost::new())]
+}
+
+pub trait Plugin {
+    fn name(&self) -> &str;
+    /// Called when the plugin is loaded, to register its handlers.
+    fn enable(&mut self, events: &mut EventBus);
+    /// Called once every tick, after the world has been ticked.
+    fn tick(&mut self, _server: &mut Server) {}
+    /// Called when the plugin is unloaded, after its handlers were removed.
+    fn disable(&mut self, _server: &mut Server) {}
+}
+
+/// When a handler runs relative to the others for the same event. `Monitor` handlers run
+/// last and should only look at the outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Lowest,
+    Low,
+    Normal,
+    High,
+    Highest,
+    Monitor,
+}
+
+pub trait Event: Any {
+    /// Whether the server should skip what the event is about. Only some events can be
+    /// cancelled.
+    fn cancelled(&self) -> bool {
+        false
+    }
+}
+
+type Handler<E> = Box<dyn FnMut(&mut E, &mut Server)>;
+
+struct Registered {
+    priority: Priority,
+    /// The plugin that registered the handler.
+    owner: u64,
+    /// A `Handler<E>` for the event type it is filed under.
+    handler: Box<dyn Any>,
+}
+
+#[derive(Default)]
+pub struct EventBus {
+    handlers: HashMap<TypeId, Vec<Registered>>,
+    /// The plugin being enabled, which new handlers belong to.
+    owner: u64,
+}
+impl EventBus {
+    /// Adds a handler for events of type `E`. Handlers with the same priority run in the
+    /// order they were added.
+    pub fn listen<E: Event>(
+        &mut self,
+        priority: Priority,
+        handler: impl FnMut(&mut E, &mut Server) + 'static,
+    ) {
+        let handlers = self.handlers.entry(TypeId::of::<E>()).or_default();
+        let at = handlers.partition_point(|h| h.priority <= priority);
+        let handler: Handler<E> = Box::new(handler);
+        handlers.insert(
+            at,
+            Registered {
+                priority,
+                owner: self.owner,
+                handler: Box::new(handler),
+            },
+        );
+    }
+    pub fn fire<E: Event>(&mut self, event: &mut E, server: &mut Server) {
+        let Some(handlers) = self.handlers.get_mut(&TypeId::of::<E>()) else {
+            return;
+        };
+        for registered in handlers {
+            let handler = registered.handler.downcast_mut::<Handler<E>>().unwrap();
+            handler(event, server);
+        }
+    }
+    fn remove_owner(&mut self, owner: u64) {
+        for handlers in self.handlers.values_mut() {
+            handlers.retain(|h| h.owner != owner);
+        }
+    }
+    /// Adds the handlers registered on `other` in the meantime, keeping priorities in order.
+    fn merge(&mut self, other: EventBus) {
+        for (type_id, added) in other.handlers {
+            let handlers = self.handlers.entry(type_id).or_default();
+            for registered in added {
+                let at = handlers.partition_point(|h| h.priority <= registered.priority);
+                handlers.insert(at, registered);
+            }
+        }
+    }
+}
+
+/// The loaded plugins and their handlers, kept by the server.
+#[derive(Default)]
+pub struct Plugins {
+    loaded: Vec<(u64, Box<dyn Plugin>)>,
+    bus: EventBus,
+    next_id: u64,
+    /// How many times the handlers are out, more than once for events fired by handlers.
+    taken: u32,
+    /// Plugins unloaded while the handlers were out, whose handlers go once they are back.
+    unloaded: Vec<u64>,
+}
+impl Plugins {
+    /// Enables a plugin and returns the id it can be unloaded with.
+    pub fn load(&mut self, mut plugin: Box<dyn Plugin>) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.bus.owner = id;
+        plugin.enable(&mut self.bus);
+        self.loaded.push((id, plugin));
+        id
+    }
+    /// Removes a plugin and its handlers, returning it so it can be disabled.
+    pub fn unload(&mut self, id: u64) -> Option<Box<dyn Plugin>> {
+        let at = self.loaded.iter().position(|(i, _)| *i == id)?;
+        self.bus.remove_owner(id);
+        if self.taken > 0 {
+            self.unloaded.push(id);
+        }
+        Some(self.loaded.remove(at).1)
+    }
+    /// Takes the handlers out, so they can be run with the server they are kept in.
+    pub fn take_bus(&mut self) -> EventBus {
+        self.taken += 1;
+        std::mem::take(&mut self.bus)
+    }
+    /// Puts the handlers back, along with any registered while they were out, and without
+    /// those of plugins unloaded in the meantime.
+    pub fn restore_bus(&mut self, bus: EventBus) {
+        let added = std::mem::replace(&mut self.bus, bus);
+        self.bus.merge(added);
+        self.taken -= 1;
+        if self.taken == 0 {
+            for id in self.unloaded.drain(..) {
+                self.bus.remove_owner(id);
+            }
+        }
+    }
+    pub fn ids(&self) -> Vec<u64> {
+        self.loaded.iter().map(|(id, _)| *id).collect()
+    }
+    /// Takes a plugin out without unloading it, so it can be run with the server it is kept
+    /// in.
+    pub fn take(&mut self, id: u64) -> Option<Box<dyn Plugin>> {
+        let at = self.loaded.iter().position(|(i, _)| *i == id)?;
+        Some(self.loaded.remove(at).1)
+    }
+    /// Puts a plugin taken out with `take` back in its place.
+    pub fn put_back(&mut self, id: u64, plugin: Box<dyn Plugin>) {
+        let at = self.loaded.partition_point(|(i, _)| *i < id);
+        self.loaded.insert(at, (id, plugin));
+    }
+}
+
+/// A player finished logging in and is in the world.
+#[derive(Debug)]
+pub struct PlayerJoin {
+    pub player: usize,
+    pub name: String,
+    pub uuid: u128,
+    /// The json text component shown to everyone in chat, if any.
+    pub message: Option<String>,
+}
+impl Event for PlayerJoin {}
+
+/// A player left or lost their connection. They are still on the server while handlers run.
+#[derive(Debug)]
+pub struct PlayerQuit {
+    pub player: usize,
+    pub name: String,
+    pub uuid: u128,
+    /// The json text component shown to everyone left in chat, if any.
+    pub message: Option<String>,
+}
+impl Event for PlayerQuit {}
+
+/// A player said something in chat. Commands are a `Command` instead.
+#[derive(Debug)]
+pub struct Chat {
+    pub player: usize,
+    pub message: String,
+    pub cancelled: bool,
+}
+impl Event for Chat {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// A player is about to break a block.
+#[derive(Debug)]
+pub struct BlockBreak {
+    pub player: usize,
+    pub position: Position,
+    pub state: BlockState,
+    pub cancelled: bool,
+}
+impl Event for BlockBreak {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// A player is about to place a block. Changing `state` changes what gets placed.
+#[derive(Debug)]
+pub struct BlockPlace {
+    pub player: usize,
+    pub position: Position,
+    pub state: BlockState,
+    pub cancelled: bool,
+}
+impl Event for BlockPlace {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// A player moved or looked around, and the move passed the server's checks. Cancelling it
+/// sends them back to `from`.
+#[derive(Debug)]
+pub struct Move {
+    pub player: usize,
+    pub from: Location,
+    pub to: Location,
+    pub cancelled: bool,
+}
+impl Event for Move {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// A player ran a command. `line` is what they typed, without the slash.
+#[derive(Debug)]
+pub struct Command {
+    pub player: usize,
+    pub line: String,
+    pub cancelled: bool,
+}
+impl Event for Command {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+/// Someone's server list is asking about the server.
+#[derive(Debug)]
+pub struct ServerListPing {
+    /// The protocol version of the client asking.
+    pub protocol: u32,
+    pub motd: String,
+    pub max_players: i32,
+    pub online: i32,
+    /// Names and uuids shown when hovering over the player count.
+    pub sample: Vec<(String, u128)>,
+}
+impl Event for ServerListPing {}
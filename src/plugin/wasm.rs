@@ -0,0 +1,948 @@
+//! Plugins compiled to WebAssembly, loaded from the `plugins/` directory.
+//!
+//! Each `.wasm` file there is a plugin, run by wasmtime in its own sandbox. A plugin that
+//! runs out of fuel, goes over its memory or table limits or traps is unloaded, and stays
+//! unloaded until its file changes. Files are checked every second, and a changed file is
+//! unloaded and loaded again from scratch, so plugins don't keep anything across a reload.
+//!
+//! # ABI
+//!
+//! Strings are UTF-8, passed as a pointer and a length into the plugin's memory. Data with
+//! structure is json. Players are identified by their index on the server.
+//!
+//! The plugin exports:
+//!
+//! - `memory`, and `alloc(len: i32) -> i32`, which the server uses to hand it data.
+//! - `enable()`, run once when loaded. Only `log`, `subscribe`, `register_command` and
+//!   `schedule` are useful this early.
+//! - `disable()`, `tick()`: optional.
+//! - `event(kind: i32, ptr: i32, len: i32) -> i32` for the events it subscribed to, with the
+//!   event as json, which always has a `cancelled` field. It returns 1 to cancel the event,
+//!   2 to uncancel it and 0 to leave it.
+//! - `command(player: i32, ptr: i32, len: i32)` for the commands it registered, with the
+//!   whole command line without the slash.
+//! - `task(id: i32)` for the tasks it scheduled.
+//!
+//! The server provides, in the `mycelium` module:
+//!
+//! - `log(ptr, len)`
+//! - `subscribe(kind: i32, priority: i32)`: kinds are numbered in the order of `Kind`, and
+//!   priorities from 0 for `Priority::Lowest` to 5 for `Priority::Monitor`.
+//! - `update_event(ptr, len)`: changes fields of the event being handled, given a json
+//!   object with the fields to change.
+//! - `register_command(ptr, len, level: i32)`: a command name and the operator level needed
+//!   to run it.
+//! - `schedule(delay: i32, id: i32)`: calls `task(id)` after `delay` ticks.
+//! - `get_block(x, y, z) -> i32`, `set_block(x, y, z, state: i32)`: block states are the id
+//!   shifted left by 4, plus the meta.
+//! - `players(out, cap) -> i32`, `player(index, out, cap) -> i32`: write a json array of
+//!   players or one player to `out` if it fits in `cap` bytes, and return its length, or -1
+//!   for a player that isn't online.
+//! - `send_message(player, ptr, len)`, `broadcast(ptr, len)`: json text components.
+//! - `teleport(player, x: f64, y: f64, z: f64)`
+//! - `kick(player, ptr, len)`: with a json text component as the reason.
+//! - `set_display_name(player, ptr, len)`: the json text component shown for a player in
+//!   the player list, or their name again if `len` is 0.
+//! - `set_player_list_text(header_ptr, header_len, footer_ptr, footer_len)`: json text
+//!   components shown above and below the player list.
+//!
+//! Changes to the world and players happen once the plugin returns.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use log::{error, info, warn};
+use serde_json::{json, Value};
+use wasmtime::{
+    Caller, Config, Engine, Error, Instance, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder, WasmParams, WasmResults,
+};
+
+use super::{
+    BlockBreak, BlockPlace, Chat, Command, Event, EventBus, Move, PlayerJoin, PlayerQuit, Plugin,
+    Priority, ServerListPing,
+};
+use crate::command::CommandError;
+use crate::entity::Location;
+use crate::packet::Position;
+use crate::packets::play::ChatPosition;
+use crate::server::Server;
+use crate::world::{block, World};
+
+const PLUGIN_DIR: &str = "plugins";
+/// How often the plugin directory is checked for changes.
+const RELOAD_CHECK_TICKS: u64 = 20;
+/// Roughly how many instructions a plugin gets for each call into it.
+const FUEL_PER_CALL: u64 = 5_000_000;
+const MAX_MEMORY: usize = 32 << 20;
+const MAX_TABLE_ELEMENTS: usize = 10_000;
+const MAX_STACK: usize = 512 << 10;
+/// The longest string a plugin can hand the server.
+const MAX_STRING: usize = 64 << 10;
+
+const PRIORITIES: [Priority; 6] = [
+    Priority::Lowest,
+    Priority::Low,
+    Priority::Normal,
+    Priority::High,
+    Priority::Highest,
+    Priority::Monitor,
+];
+
+/// The events plugins can subscribe to, numbered for the ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    PlayerJoin,
+    PlayerQuit,
+    Chat,
+    BlockBreak,
+    BlockPlace,
+    Move,
+    Command,
+    ServerListPing,
+}
+impl Kind {
+    const ALL: [Kind; 8] = [
+        Kind::PlayerJoin,
+        Kind::PlayerQuit,
+        Kind::Chat,
+        Kind::BlockBreak,
+        Kind::BlockPlace,
+        Kind::Move,
+        Kind::Command,
+        Kind::ServerListPing,
+    ];
+}
+
+/// An event as plugins see it.
+trait WasmEvent: Event {
+    const KIND: Kind;
+    fn to_json(&self) -> Value;
+    /// Applies the fields a plugin changed.
+    fn update(&mut self, changes: &Value);
+    fn set_cancelled(&mut self, _cancelled: bool) {}
+}
+
+fn location_json(location: &Location) -> Value {
+    json!({
+        "x": location.x,
+        "y": location.y,
+        "z": location.z,
+        "yaw": location.yaw,
+        "pitch": location.pitch,
+    })
+}
+fn position_json(position: Position) -> Value {
+    json!({ "x": position.x, "y": position.y, "z": position.z })
+}
+/// A text field a plugin can change, or remove with `null`.
+fn update_text(field: &mut Option<String>, changes: &Value, key: &str) {
+    match changes.get(key) {
+        Some(Value::String(text)) => *field = Some(text.clone()),
+        Some(Value::Null) => *field = None,
+        _ => {}
+    }
+}
+fn update_string(field: &mut String, changes: &Value, key: &str) {
+    if let Some(text) = changes.get(key).and_then(Value::as_str) {
+        *field = text.to_string();
+    }
+}
+
+impl WasmEvent for PlayerJoin {
+    const KIND: Kind = Kind::PlayerJoin;
+    fn to_json(&self) -> Value {
+        json!({
+            "player": self.player,
+            "name": self.name,
+            "uuid": crate::entity::uuid_string(self.uuid),
+            "message": self.message,
+        })
+    }
+    fn update(&mut self, changes: &Value) {
+        update_text(&mut self.message, changes, "message");
+    }
+}
+impl WasmEvent for PlayerQuit {
+    const KIND: Kind = Kind::PlayerQuit;
+    fn to_json(&self) -> Value {
+        json!({
+            "player": self.player,
+            "name": self.name,
+            "uuid": crate::entity::uuid_string(self.uuid),
+            "message": self.message,
+        })
+    }
+    fn update(&mut self, changes: &Value) {
+        update_text(&mut self.message, changes, "message");
+    }
+}
+impl WasmEvent for Chat {
+    const KIND: Kind = Kind::Chat;
+    fn to_json(&self) -> Value {
+        json!({
+            "player": self.player,
+            "message": self.message,
+        })
+    }
+    fn update(&mut self, changes: &Value) {
+        update_string(&mut self.message, changes, "message");
+    }
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+impl WasmEvent for BlockBreak {
+    const KIND: Kind = Kind::BlockBreak;
+    fn to_json(&self) -> Value {
+        json!({
+            "player": self.player,
+            "position": position_json(self.position),
+            "state": self.state,
+        })
+    }
+    fn update(&mut self, _changes: &Value) {}
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+impl WasmEvent for BlockPlace {
+    const KIND: Kind = Kind::BlockPlace;
+    fn to_json(&self) -> Value {
+        json!({
+            "player": self.player,
+            "position": position_json(self.position),
+            "state": self.state,
+        })
+    }
+    fn update(&mut self, changes: &Value) {
+        let state = changes.get("state").and_then(Value::as_u64);
+        if let Some(state) = state.and_then(|s| u16::try_from(s).ok()) {
+            self.state = state;
+        }
+    }
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+impl WasmEvent for Move {
+    const KIND: Kind = Kind::Move;
+    fn to_json(&self) -> Value {
+        json!({
+            "player": self.player,
+            "from": location_json(&self.from),
+            "to": location_json(&self.to),
+        })
+    }
+    fn update(&mut self, _changes: &Value) {}
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+impl WasmEvent for Command {
+    const KIND: Kind = Kind::Command;
+    fn to_json(&self) -> Value {
+        json!({
+            "player": self.player,
+            "line": self.line,
+        })
+    }
+    fn update(&mut self, changes: &Value) {
+        update_string(&mut self.line, changes, "line");
+    }
+    fn set_cancelled(&mut self, cancelled: bool) {
+        self.cancelled = cancelled;
+    }
+}
+impl WasmEvent for ServerListPing {
+    const KIND: Kind = Kind::ServerListPing;
+    fn to_json(&self) -> Value {
+        json!({
+            "protocol": self.protocol,
+            "motd": self.motd,
+            "max_players": self.max_players,
+            "online": self.online,
+        })
+    }
+    fn update(&mut self, changes: &Value) {
+        update_string(&mut self.motd, changes, "motd");
+        let number = |key| changes.get(key).and_then(Value::as_i64);
+        if let Some(max) = number("max_players").and_then(|n| i32::try_from(n).ok()) {
+            self.max_players = max;
+        }
+        if let Some(online) = number("online").and_then(|n| i32::try_from(n).ok()) {
+            self.online = online;
+        }
+    }
+}
+
+/// Something a plugin asked for that needs the server, done once it returns.
+#[derive(Debug)]
+enum Action {
+    SetBlock(Position, block::BlockState),
+    SendMessage(usize, String),
+    Broadcast(String),
+    Teleport(usize, f64, f64, f64),
+    Kick(usize, String),
+    SetDisplayName(usize, Option<String>),
+    SetPlayerListText(String, String),
+}
+
+/// What host functions can see and do, kept in the plugin's store.
+struct Host {
+    name: String,
+    limits: StoreLimits,
+    world: Arc<Mutex<World>>,
+    /// The players as they were when the server called into the plugin, as json.
+    players: Vec<(usize, Value)>,
+    actions: Vec<Action>,
+    subscriptions: Vec<(Kind, Priority)>,
+    commands: Vec<(String, u8)>,
+    /// Tasks scheduled since the last call, with their delay in ticks.
+    scheduled: Vec<(u64, i32)>,
+    /// Changes to the event being handled.
+    update: Option<Value>,
+}
+
+/// A loaded plugin and its sandbox.
+struct Guest {
+    store: Store<Host>,
+    instance: Instance,
+    /// Set once the plugin trapped, after which it isn't called again.
+    crashed: bool,
+}
+impl Guest {
+    fn name(&self) -> &str {
+        &self.store.data().name
+    }
+    /// Calls an export with a fresh fuel budget, then does what the plugin asked for if the
+    /// server is there to do it. `None` if the plugin doesn't have the export or crashed.
+    fn call<P: WasmParams, R: WasmResults>(
+        &mut self,
+        server: Option<&mut Server>,
+        export: &str,
+        params: P,
+    ) -> Option<R> {
+        if self.crashed || self.instance.get_export(&mut self.store, export).is_none() {
+            return None;
+        }
+        if let Some(server) = &server {
+            self.store.data_mut().players = server
+                .players()
+                .map(|(index, p)| {
+                    let mut info = location_json(&p.location);
+                    info["player"] = json!(index);
+                    info["name"] = json!(p.name);
+                    info["uuid"] = json!(crate::entity::uuid_string(p.uuid));
+                    info["gamemode"] = json!(p.gamemode as u8);
+                    (index, info)
+                })
+                .collect();
+        }
+        self.store.set_fuel(FUEL_PER_CALL).unwrap();
+        let result = self
+            .instance
+            .get_typed_func::<P, R>(&mut self.store, export)
+            .and_then(|f| f.call(&mut self.store, params));
+        if let Some(server) = server {
+            for action in std::mem::take(&mut self.store.data_mut().actions) {
+                apply(server, action);
+            }
+        }
+        match result {
+            Ok(result) => Some(result),
+            Err(e) => {
+                error!("Plugin {} crashed in {}: {:#}", self.name(), export, e);
+                self.crashed = true;
+                None
+            }
+        }
+    }
+    /// Copies data into the plugin's memory, returning where it went.
+    fn write(&mut self, server: &mut Server, data: &[u8]) -> Option<(i32, i32)> {
+        let len = data.len() as i32;
+        let ptr: i32 = self.call(Some(server), "alloc", len)?;
+        let memory = self.instance.get_memory(&mut self.store, "memory")?;
+        if let Err(e) = memory.write(&mut self.store, ptr as u32 as usize, data) {
+            error!("Plugin {} gave a bad allocation: {}", self.name(), e);
+            self.crashed = true;
+            return None;
+        }
+        Some((ptr, len))
+    }
+    fn handle<E: WasmEvent>(&mut self, event: &mut E, server: &mut Server) {
+        let mut data = event.to_json();
+        data["cancelled"] = event.cancelled().into();
+        let data = data.to_string();
+        let Some((ptr, len)) = self.write(server, data.as_bytes()) else {
+            return;
+        };
+        self.store.data_mut().update = None;
+        let kind = E::KIND as i32;
+        let Some(result) = self.call::<_, i32>(Some(server), "event", (kind, ptr, len)) else {
+            return;
+        };
+        if let Some(changes) = self.store.data_mut().update.take() {
+            event.update(&changes);
+        }
+        match result {
+            1 => event.set_cancelled(true),
+            2 => event.set_cancelled(false),
+            _ => {}
+        }
+    }
+    fn run_command(&mut self, server: &mut Server, player: usize, line: &str) {
+        if let Some((ptr, len)) = self.write(server, line.as_bytes()) {
+            self.call::<_, ()>(Some(server), "command", (player as i32, ptr, len));
+        }
+    }
+}
+
+fn apply(server: &mut Server, action: Action) {
+    match action {
+        Action::SetBlock(position, state) => server.set_block(position, state),
+        Action::SendMessage(player, message) => server.send_message(player, &message),
+        Action::Broadcast(message) => server.broadcast_chat(&message, ChatPosition::System),
+        Action::Teleport(player, x, y, z) => {
+            if let Some(p) = server.player(player) {
+                let location = Location {
+                    x,
+                    y,
+                    z,
+                    ..p.location
+                };
+                server.teleport(player, location);
+            }
+        }
+        Action::Kick(player, reason) => server.kick(player, &reason),
+        Action::SetDisplayName(player, name) => server.set_display_name(player, name),
+        Action::SetPlayerListText(header, footer) => server.set_player_list_text(header, footer),
+    }
+}
+
+/// A `.wasm` plugin as the server sees it.
+struct WasmPlugin {
+    name: String,
+    guest: Rc<RefCell<Guest>>,
+    /// Ticks since the plugin was loaded.
+    age: u64,
+    /// Tasks waiting to run, with the age they are due at.
+    tasks: Vec<(u64, i32)>,
+}
+impl WasmPlugin {
+    fn listen<E: WasmEvent>(&self, events: &mut EventBus, priority: Priority) {
+        let guest = self.guest.clone();
+        events.listen::<E>(priority, move |event, server| {
+            guest.borrow_mut().handle(event, server)
+        });
+    }
+    fn take_scheduled(&mut self) {
+        let scheduled = std::mem::take(&mut self.guest.borrow_mut().store.data_mut().scheduled);
+        for (delay, id) in scheduled {
+            self.tasks.push((self.age + delay, id));
+        }
+    }
+}
+impl Plugin for WasmPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn enable(&mut self, events: &mut EventBus) {
+        let (subscriptions, commands) = {
+            let mut guest = self.guest.borrow_mut();
+            guest.call::<(), ()>(None, "enable", ());
+            let host = guest.store.data_mut();
+            (
+                std::mem::take(&mut host.subscriptions),
+                std::mem::take(&mut host.commands),
+            )
+        };
+        self.take_scheduled();
+        for (kind, priority) in subscriptions {
+            match kind {
+                Kind::PlayerJoin => self.listen::<PlayerJoin>(events, priority),
+                Kind::PlayerQuit => self.listen::<PlayerQuit>(events, priority),
+                Kind::Chat => self.listen::<Chat>(events, priority),
+                Kind::BlockBreak => self.listen::<BlockBreak>(events, priority),
+                Kind::BlockPlace => self.listen::<BlockPlace>(events, priority),
+                Kind::Move => self.listen::<Move>(events, priority),
+                Kind::Command => self.listen::<Command>(events, priority),
+                Kind::ServerListPing => self.listen::<ServerListPing>(events, priority),
+            }
+        }
+        if commands.is_empty() {
+            return;
+        }
+        // Plugin commands are taken out of the way before the server looks for them.
+        let guest = self.guest.clone();
+        events.listen::<Command>(Priority::Normal, move |event, server| {
+            let name = event.line.split(' ').next().unwrap();
+            let Some((_, level)) = commands.iter().find(|(n, _)| n.eq_ignore_ascii_case(name))
+            else {
+                return;
+            };
+            if event.cancelled {
+                return;
+            }
+            event.cancelled = true;
+            let node = format!("mycelium.command.{}", name.to_lowercase());
+            let allowed = server
+                .player(event.player)
+                .is_some_and(|p| p.op_level >= *level)
+                || server.has_permission(event.player, &node);
+            if !allowed {
+                server.send_message(event.player, &CommandError::Permission.to_json());
+                return;
+            }
+            guest
+                .borrow_mut()
+                .run_command(server, event.player, &event.line);
+        });
+    }
+    fn tick(&mut self, server: &mut Server) {
+        self.age += 1;
+        let age = self.age;
+        let (due, waiting) = std::mem::take(&mut self.tasks)
+            .into_iter()
+            .partition(|(at, _)| *at <= age);
+        self.tasks = waiting;
+        for (_, id) in due {
+            self.guest
+                .borrow_mut()
+                .call::<_, ()>(Some(server), "task", id);
+        }
+        self.guest
+            .borrow_mut()
+            .call::<(), ()>(Some(server), "tick", ());
+        self.take_scheduled();
+    }
+    fn disable(&mut self, server: &mut Server) {
+        self.guest
+            .borrow_mut()
+            .call::<(), ()>(Some(server), "disable", ());
+    }
+}
+
+fn read_string(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> Result<String, Error> {
+    let len = len as u32 as usize;
+    if len > MAX_STRING {
+        return Err(Error::msg("string too long"));
+    }
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .ok_or_else(|| Error::msg("no memory export"))?;
+    let mut buf = vec![0; len];
+    memory.read(&caller, ptr as u32 as usize, &mut buf)?;
+    String::from_utf8(buf).map_err(Error::msg)
+}
+/// Copies `data` to a buffer the plugin gave, if it fits, and returns its length.
+fn write_output(
+    caller: &mut Caller<'_, Host>,
+    out: i32,
+    cap: i32,
+    data: &[u8],
+) -> Result<i32, Error> {
+    if data.len() <= cap as u32 as usize {
+        let memory = caller
+            .get_export("memory")
+            .and_then(|e| e.into_memory())
+            .ok_or_else(|| Error::msg("no memory export"))?;
+        memory.write(&mut *caller, out as u32 as usize, data)?;
+    }
+    Ok(data.len() as i32)
+}
+
+/// The host functions every plugin gets.
+fn linker(engine: &Engine) -> Linker<Host> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap(
+            "mycelium",
+            "log",
+            |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+                let text = read_string(&mut caller, ptr, len)?;
+                info!("[{}] {}", caller.data().name, text);
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "subscribe",
+            |mut caller: Caller<'_, Host>, kind: i32, priority: i32| {
+                let kind = usize::try_from(kind).ok().and_then(|k| Kind::ALL.get(k));
+                let priority = usize::try_from(priority)
+                    .ok()
+                    .and_then(|p| PRIORITIES.get(p));
+                let (Some(kind), Some(priority)) = (kind, priority) else {
+                    return Err(Error::msg("unknown event kind or priority"));
+                };
+                caller.data_mut().subscriptions.push((*kind, *priority));
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "update_event",
+            |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+                let text = read_string(&mut caller, ptr, len)?;
+                let changes: Value = serde_json::from_str(&text)?;
+                let update = caller.data_mut().update.get_or_insert_with(|| json!({}));
+                if let (Some(update), Some(changes)) = (update.as_object_mut(), changes.as_object())
+                {
+                    update.extend(changes.clone());
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "register_command",
+            |mut caller: Caller<'_, Host>, ptr: i32, len: i32, level: i32| {
+                let name = read_string(&mut caller, ptr, len)?;
+                let level = level.clamp(0, 4) as u8;
+                caller.data_mut().commands.push((name, level));
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "schedule",
+            |mut caller: Caller<'_, Host>, delay: i32, id: i32| {
+                let delay = delay.max(1) as u64;
+                caller.data_mut().scheduled.push((delay, id));
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "get_block",
+            |caller: Caller<'_, Host>, x: i32, y: i32, z: i32| {
+                caller.data().world.lock().unwrap().get_block(x, y, z) as i32
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "set_block",
+            |mut caller: Caller<'_, Host>, x: i32, y: i32, z: i32, state: i32| {
+                let state = u16::try_from(state)
+                    .ok()
+                    .filter(|s| block::get(*s).id == block::id(*s))
+                    .ok_or_else(|| Error::msg("unknown block state"))?;
+                let action = Action::SetBlock(Position::new(x, y, z), state);
+                caller.data_mut().actions.push(action);
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "players",
+            |mut caller: Caller<'_, Host>, out: i32, cap: i32| {
+                let players: Vec<&Value> = caller.data().players.iter().map(|(_, p)| p).collect();
+                let data = serde_json::to_vec(&players).unwrap();
+                write_output(&mut caller, out, cap, &data)
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "player",
+            |mut caller: Caller<'_, Host>, player: i32, out: i32, cap: i32| {
+                let info = caller
+                    .data()
+                    .players
+                    .iter()
+                    .find(|(index, _)| *index as i32 == player)
+                    .map(|(_, p)| serde_json::to_vec(p).unwrap());
+                match info {
+                    Some(data) => write_output(&mut caller, out, cap, &data),
+                    None => Ok(-1),
+                }
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "send_message",
+            |mut caller: Caller<'_, Host>, player: i32, ptr: i32, len: i32| {
+                let message = read_string(&mut caller, ptr, len)?;
+                let action = Action::SendMessage(player as usize, message);
+                caller.data_mut().actions.push(action);
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "broadcast",
+            |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+                let message = read_string(&mut caller, ptr, len)?;
+                caller.data_mut().actions.push(Action::Broadcast(message));
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "teleport",
+            |mut caller: Caller<'_, Host>, player: i32, x: f64, y: f64, z: f64| {
+                if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+                    return Err(Error::msg("teleport to an invalid position"));
+                }
+                let action = Action::Teleport(player as usize, x, y, z);
+                caller.data_mut().actions.push(action);
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "kick",
+            |mut caller: Caller<'_, Host>, player: i32, ptr: i32, len: i32| {
+                let reason = read_string(&mut caller, ptr, len)?;
+                caller
+                    .data_mut()
+                    .actions
+                    .push(Action::Kick(player as usize, reason));
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "set_display_name",
+            |mut caller: Caller<'_, Host>, player: i32, ptr: i32, len: i32| {
+                let name = match len {
+                    0 => None,
+                    _ => Some(read_string(&mut caller, ptr, len)?),
+                };
+                caller
+                    .data_mut()
+                    .actions
+                    .push(Action::SetDisplayName(player as usize, name));
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "mycelium",
+            "set_player_list_text",
+            |mut caller: Caller<'_, Host>,
+             header_ptr: i32,
+             header_len: i32,
+             footer_ptr: i32,
+             footer_len: i32| {
+                let header = read_string(&mut caller, header_ptr, header_len)?;
+                let footer = read_string(&mut caller, footer_ptr, footer_len)?;
+                caller
+                    .data_mut()
+                    .actions
+                    .push(Action::SetPlayerListText(header, footer));
+                Ok(())
+            },
+        )
+        .unwrap();
+    linker
+}
+
+/// A plugin file and what became of it the last time it changed.
+struct Loaded {
+    modified: SystemTime,
+    /// The plugin's id on the server and its sandbox, if it loaded.
+    plugin: Option<(u64, Rc<RefCell<Guest>>)>,
+}
+
+/// A plugin file compiled on another thread.
+struct Compiled {
+    path: PathBuf,
+    modified: SystemTime,
+    module: Result<Module, Error>,
+}
+
+/// Loads the plugins in `plugins/` and keeps them up to date with their files.
+pub struct WasmHost {
+    engine: Engine,
+    linker: Linker<Host>,
+    loaded: HashMap<PathBuf, Loaded>,
+    /// Files being compiled, with the modification time they were compiled at.
+    compiling: HashMap<PathBuf, SystemTime>,
+    compiled: (Sender<Compiled>, Receiver<Compiled>),
+    age: u64,
+}
+impl WasmHost {
+    pub fn new() -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true).max_wasm_stack(MAX_STACK);
+        let engine = Engine::new(&config).unwrap();
+        let linker = linker(&engine);
+        Self {
+            engine,
+            linker,
+            loaded: HashMap::new(),
+            compiling: HashMap::new(),
+            compiled: std::sync::mpsc::channel(),
+            age: 0,
+        }
+    }
+    fn instantiate(&self, path: &Path, module: &Module, server: &Server) -> Result<Guest, Error> {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let host = Host {
+            name,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY)
+                .table_elements(MAX_TABLE_ELEMENTS)
+                .instances(1)
+                .memories(1)
+                .tables(1)
+                .build(),
+            world: server.world_handle(),
+            players: vec![],
+            actions: vec![],
+            subscriptions: vec![],
+            commands: vec![],
+            scheduled: vec![],
+            update: None,
+        };
+        let mut store = Store::new(&self.engine, host);
+        store.limiter(|host| &mut host.limits);
+        // Start functions run while instantiating, and get the same budget as any call.
+        store.set_fuel(FUEL_PER_CALL)?;
+        let instance = self.linker.instantiate(&mut store, module)?;
+        Ok(Guest {
+            store,
+            instance,
+            crashed: false,
+        })
+    }
+    /// Compiles new and changed plugins, and unloads removed and crashed ones.
+    fn reload(&mut self, server: &mut Server) {
+        let mut files = HashMap::new();
+        if let Ok(entries) = std::fs::read_dir(PLUGIN_DIR) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "wasm") {
+                    let modified = entry.metadata().and_then(|m| m.modified());
+                    files.insert(path, modified.unwrap_or(SystemTime::UNIX_EPOCH));
+                }
+            }
+        }
+        let paths: Vec<PathBuf> = self.loaded.keys().cloned().collect();
+        for path in paths {
+            let loaded = &self.loaded[&path];
+            let crashed = loaded
+                .plugin
+                .as_ref()
+                .is_some_and(|(_, guest)| guest.borrow().crashed);
+            if files.get(&path) != Some(&loaded.modified) {
+                let loaded = self.loaded.remove(&path).unwrap();
+                if let Some((id, _)) = loaded.plugin {
+                    server.unload_plugin(id);
+                }
+            } else if crashed {
+                // Stays unloaded until the file changes.
+                let (id, _) = self.loaded.get_mut(&path).unwrap().plugin.take().unwrap();
+                warn!("Unloading {} until its file changes.", path.display());
+                server.unload_plugin(id);
+            }
+        }
+        // Compiling takes a while, so it is done off the tick thread and the plugin is loaded
+        // once it is ready.
+        for (path, modified) in files {
+            if self.loaded.contains_key(&path) || self.compiling.get(&path) == Some(&modified) {
+                continue;
+            }
+            self.compiling.insert(path.clone(), modified);
+            let (engine, done) = (self.engine.clone(), self.compiled.0.clone());
+            std::thread::spawn(move || {
+                let module = Module::from_file(&engine, &path);
+                // The host may be gone by now.
+                done.send(Compiled {
+                    path,
+                    modified,
+                    module,
+                })
+                .ok();
+            });
+        }
+    }
+    /// Loads the plugins that finished compiling.
+    fn load_compiled(&mut self, server: &mut Server) {
+        while let Ok(Compiled {
+            path,
+            modified,
+            module,
+        }) = self.compiled.1.try_recv()
+        {
+            if self.compiling.get(&path) == Some(&modified) {
+                self.compiling.remove(&path);
+            }
+            // Another compile of the file finished first. The next check reloads the plugin
+            // if that one was out of date.
+            if self.loaded.contains_key(&path) {
+                continue;
+            }
+            let plugin = match module.and_then(|module| self.instantiate(&path, &module, server)) {
+                Ok(guest) => {
+                    let guest = Rc::new(RefCell::new(guest));
+                    let plugin = WasmPlugin {
+                        name: guest.borrow().name().to_string(),
+                        guest: guest.clone(),
+                        age: 0,
+                        tasks: vec![],
+                    };
+                    Some((server.load_plugin(Box::new(plugin)), guest))
+                }
+                Err(e) => {
+                    error!("Couldn't load {}: {:#}", path.display(), e);
+                    None
+                }
+            };
+            self.loaded.insert(path, Loaded { modified, plugin });
+        }
+    }
+}
+impl Plugin for WasmHost {
+    fn name(&self) -> &str {
+        "wasm"
+    }
+    fn enable(&mut self, _events: &mut EventBus) {}
+    fn tick(&mut self, server: &mut Server) {
+        if self.age.is_multiple_of(RELOAD_CHECK_TICKS) {
+            self.reload(server);
+        }
+        self.load_compiled(server);
+        self.age += 1;
+    }
+    fn disable(&mut self, server: &mut Server) {
+        for (_, loaded) in self.loaded.drain() {
+            if let Some((id, _)) = loaded.plugin {
+                server.unload_plugin(id);
+            }
+        }
+    }
+}
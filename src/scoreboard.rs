@@ -0,0 +1,448 @@
+//! Objectives, scores and teams, kept in `scoreboard.dat` in vanilla's layout. Changes are
+//! queued as packets and sent to every player at the end of the tick.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::packets::play::{
+    DisplayScoreboard, ObjectiveAction, ScoreboardObjective, TeamInfo, Teams, TeamsAction,
+    UpdateScore,
+};
+
+pub const SCOREBOARD_FILE: &str = "scoreboard.dat";
+/// Chat color names by id, as they appear in `TeamColor`.
+pub const COLORS: [&str; 16] = [
+    "black",
+    "dark_blue",
+    "dark_green",
+    "dark_aqua",
+    "dark_red",
+    "dark_purple",
+    "gold",
+    "gray",
+    "dark_gray",
+    "blue",
+    "green",
+    "aqua",
+    "red",
+    "light_purple",
+    "yellow",
+    "white",
+];
+/// Values of `NameTagVisibility`, and of `CollisionRule` with `push` in place of `hideFor`.
+pub const VISIBILITIES: [&str; 4] = ["always", "never", "hideForOtherTeams", "hideForOwnTeam"];
+/// The longest objective and team names the client accepts, display names may be twice this.
+pub const MAX_NAME_LENGTH: usize = 16;
+pub const MAX_ENTRY_LENGTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplaySlot {
+    List = 0,
+    Sidebar = 1,
+    BelowName = 2,
+}
+impl DisplaySlot {
+    fn key(self) -> String {
+        format!("slot_{}", self as u8)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Objective {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "DisplayName")]
+    pub display_name: String,
+    /// Only `dummy` objectives are supported, scores are only changed by plugins.
+    #[serde(rename = "CriteriaName")]
+    pub criteria: String,
+    /// `integer` or `hearts`.
+    #[serde(rename = "RenderType")]
+    pub render_type: String,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Score {
+    /// A player name, or any other text for fake players.
+    #[serde(rename = "Name")]
+    pub entry: String,
+    #[serde(rename = "Objective")]
+    pub objective: String,
+    #[serde(rename = "Score")]
+    pub value: i32,
+    #[serde(rename = "Locked")]
+    pub locked: bool,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Team {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "DisplayName")]
+    pub display_name: String,
+    #[serde(rename = "Prefix")]
+    pub prefix: String,
+    #[serde(rename = "Suffix")]
+    pub suffix: String,
+    /// A name from `COLORS`, or `reset` for none.
+    #[serde(rename = "TeamColor")]
+    pub color: String,
+    #[serde(rename = "AllowFriendlyFire")]
+    pub friendly_fire: bool,
+    #[serde(rename = "SeeFriendlyInvisibles")]
+    pub see_friendly_invisibles: bool,
+    #[serde(rename = "NameTagVisibility")]
+    pub name_tag_visibility: String,
+    #[serde(rename = "DeathMessageVisibility")]
+    pub death_message_visibility: String,
+    #[serde(rename = "CollisionRule")]
+    pub collision_rule: String,
+    #[serde(rename = "Players")]
+    pub entries: Vec<String>,
+}
+impl Team {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            prefix: String::new(),
+            suffix: String::new(),
+            color: "reset".to_string(),
+            friendly_fire: true,
+            see_friendly_invisibles: true,
+            name_tag_visibility: "always".to_string(),
+            death_message_visibility: "always".to_string(),
+            collision_rule: "always".to_string(),
+            entries: vec![],
+        }
+    }
+    fn info(&self) -> TeamInfo {
+        TeamInfo {
+            display_name: self.display_name.clone(),
+            prefix: self.prefix.clone(),
+            suffix: self.suffix.clone(),
+            friendly_flags: self.friendly_fire as u8 | (self.see_friendly_invisibles as u8) << 1,
+            name_tag_visibility: self.name_tag_visibility.clone(),
+            collision_rule: self.collision_rule.clone(),
+            color: COLORS
+                .iter()
+                .position(|c| *c == self.color)
+                .map_or(-1, |c| c as i8),
+        }
+    }
+}
+
+/// Anything the scoreboard needs to tell the clients.
+#[derive(Debug, Clone)]
+pub enum ScoreboardPacket {
+    Objective(ScoreboardObjective),
+    Score(UpdateScore),
+    Display(DisplayScoreboard),
+    Team(Teams),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Scoreboard {
+    #[serde(rename = "Objectives")]
+    objectives: Vec<Objective>,
+    #[serde(rename = "PlayerScores")]
+    scores: Vec<Score>,
+    #[serde(rename = "Teams")]
+    teams: Vec<Team>,
+    /// Objective names by `slot_<n>`.
+    #[serde(rename = "DisplaySlots")]
+    display_slots: HashMap<String, String>,
+    #[serde(skip)]
+    outbox: Vec<ScoreboardPacket>,
+    #[serde(skip)]
+    dirty: bool,
+}
+/// Vanilla keeps everything under `data`.
+#[derive(Serialize, Deserialize)]
+struct ScoreboardFile {
+    data: Scoreboard,
+}
+
+impl Scoreboard {
+    pub fn load() -> Self {
+        let Ok(file) = File::open(SCOREBOARD_FILE) else {
+            info!("{} doesn't exist, starting empty.", SCOREBOARD_FILE);
+            return Self::default();
+        };
+        match nbt::from_gzip_reader::<_, ScoreboardFile>(BufReader::new(file)) {
+            Ok(file) => file.data,
+            Err(e) => {
+                error!("Couldn't read {}: {}", SCOREBOARD_FILE, e);
+                Self::default()
+            }
+        }
+    }
+    /// Saves if anything changed since the last save.
+    pub fn save(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        let temporary = format!("{}_tmp", SCOREBOARD_FILE);
+        let file = ScoreboardFile {
+            data: std::mem::take(self),
+        };
+        let result = File::create(&temporary)
+            .map_err(nbt::Error::from)
+            .and_then(|f| nbt::to_gzip_writer(&mut BufWriter::new(f), &file, None))
+            .and_then(|_| fs::rename(&temporary, SCOREBOARD_FILE).map_err(nbt::Error::from));
+        *self = file.data;
+        if let Err(e) = result {
+            error!("Couldn't save {}: {}", SCOREBOARD_FILE, e);
+        }
+    }
+    fn send(&mut self, packet: ScoreboardPacket) {
+        self.outbox.push(packet);
+        self.dirty = true;
+    }
+    /// The packets for everything that changed since the last call.
+    pub fn take_packets(&mut self) -> Vec<ScoreboardPacket> {
+        std::mem::take(&mut self.outbox)
+    }
+    /// Everything a joining player needs to see the scoreboard as it is.
+    pub fn packets(&self) -> Vec<ScoreboardPacket> {
+        let objectives = self.objectives.iter().map(|o| {
+            ScoreboardPacket::Objective(ScoreboardObjective {
+                name: o.name.clone(),
+                action: ObjectiveAction::Create {
+                    display_name: o.display_name.clone(),
+                    render_type: o.render_type.clone(),
+                },
+            })
+        });
+        let scores = self.scores.iter().map(|s| {
+            ScoreboardPacket::Score(UpdateScore {
+                entry: s.entry.clone(),
+                objective: s.objective.clone(),
+                value: Some(s.value),
+            })
+        });
+        let slots = [
+            DisplaySlot::List,
+            DisplaySlot::Sidebar,
+            DisplaySlot::BelowName,
+        ];
+        let displays = slots.into_iter().filter_map(|slot| {
+            Some(ScoreboardPacket::Display(DisplayScoreboard {
+                position: slot as u8,
+                objective: self.display_slots.get(&slot.key())?.clone(),
+            }))
+        });
+        let teams = self.teams.iter().map(|t| {
+            ScoreboardPacket::Team(Teams {
+                name: t.name.clone(),
+                action: TeamsAction::Create(t.info(), t.entries.clone()),
+            })
+        });
+        objectives
+            .chain(scores)
+            .chain(displays)
+            .chain(teams)
+            .collect()
+    }
+
+    pub fn objective(&self, name: &str) -> Option<&Objective> {
+        self.objectives.iter().find(|o| o.name == name)
+    }
+    pub fn objectives(&self) -> &[Objective] {
+        &self.objectives
+    }
+    /// Returns false if the name is taken or too long.
+    pub fn add_objective(&mut self, name: &str, display_name: &str, render_type: &str) -> bool {
+        if self.objective(name).is_some() || name.len() > MAX_NAME_LENGTH {
+            return false;
+        }
+        let objective = Objective {
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            criteria: "dummy".to_string(),
+            render_type: render_type.to_string(),
+        };
+        self.send(ScoreboardPacket::Objective(ScoreboardObjective {
+            name: objective.name.clone(),
+            action: ObjectiveAction::Create {
+                display_name: objective.display_name.clone(),
+                render_type: objective.render_type.clone(),
+            },
+        }));
+        self.objectives.push(objective);
+        true
+    }
+    pub fn set_objective_display_name(&mut self, name: &str, display_name: &str) {
+        let Some(objective) = self.objectives.iter_mut().find(|o| o.name == name) else {
+            return;
+        };
+        objective.display_name = display_name.to_string();
+        let action = ObjectiveAction::Update {
+            display_name: objective.display_name.clone(),
+            render_type: objective.render_type.clone(),
+        };
+        self.send(ScoreboardPacket::Objective(ScoreboardObjective {
+            name: name.to_string(),
+            action,
+        }));
+    }
+    /// Removes an objective with its scores, clients clear it from display slots on their own.
+    pub fn remove_objective(&mut self, name: &str) -> bool {
+        if self.objective(name).is_none() {
+            return false;
+        }
+        self.objectives.retain(|o| o.name != name);
+        self.scores.retain(|s| s.objective != name);
+        self.display_slots.retain(|_, o| o != name);
+        self.send(ScoreboardPacket::Objective(ScoreboardObjective {
+            name: name.to_string(),
+            action: ObjectiveAction::Remove,
+        }));
+        true
+    }
+    /// Shows an objective in a slot, or clears the slot with `None`.
+    pub fn set_display(&mut self, slot: DisplaySlot, objective: Option<&str>) {
+        match objective {
+            Some(name) if self.objective(name).is_some() => {
+                self.display_slots.insert(slot.key(), name.to_string());
+            }
+            Some(_) => return,
+            None => {
+                self.display_slots.remove(&slot.key());
+            }
+        }
+        self.send(ScoreboardPacket::Display(DisplayScoreboard {
+            position: slot as u8,
+            objective: objective.unwrap_or_default().to_string(),
+        }));
+    }
+
+    pub fn score(&self, entry: &str, objective: &str) -> Option<i32> {
+        self.scores
+            .iter()
+            .find(|s| s.entry == entry && s.objective == objective)
+            .map(|s| s.value)
+    }
+    /// Returns false if there is no such objective or the entry is too long.
+    pub fn set_score(&mut self, entry: &str, objective: &str, value: i32) -> bool {
+        if self.objective(objective).is_none() || entry.len() > MAX_ENTRY_LENGTH {
+            return false;
+        }
+        let existing = self
+            .scores
+            .iter_mut()
+            .find(|s| s.entry == entry && s.objective == objective);
+        match existing {
+            Some(score) => score.value = value,
+            None => self.scores.push(Score {
+                entry: entry.to_string(),
+                objective: objective.to_string(),
+                value,
+                locked: false,
+            }),
+        }
+        self.send(ScoreboardPacket::Score(UpdateScore {
+            entry: entry.to_string(),
+            objective: objective.to_string(),
+            value: Some(value),
+        }));
+        true
+    }
+    pub fn add_score(&mut self, entry: &str, objective: &str, amount: i32) -> bool {
+        let value = self
+            .score(entry, objective)
+            .unwrap_or(0)
+            .saturating_add(amount);
+        self.set_score(entry, objective, value)
+    }
+    pub fn remove_score(&mut self, entry: &str, objective: &str) {
+        let before = self.scores.len();
+        self.scores
+            .retain(|s| s.entry != entry || s.objective != objective);
+        if before != self.scores.len() {
+            self.send(ScoreboardPacket::Score(UpdateScore {
+                entry: entry.to_string(),
+                objective: objective.to_string(),
+                value: None,
+            }));
+        }
+    }
+
+    pub fn team(&self, name: &str) -> Option<&Team> {
+        self.teams.iter().find(|t| t.name == name)
+    }
+    pub fn teams(&self) -> &[Team] {
+        &self.teams
+    }
+    /// Returns false if the name is taken or too long.
+    pub fn add_team(&mut self, name: &str) -> bool {
+        if self.team(name).is_some() || name.len() > MAX_NAME_LENGTH {
+            return false;
+        }
+        let team = Team::new(name);
+        self.send(ScoreboardPacket::Team(Teams {
+            name: team.name.clone(),
+            action: TeamsAction::Create(team.info(), vec![]),
+        }));
+        self.teams.push(team);
+        true
+    }
+    /// Changes a team's settings, like its prefix or color, and tells the clients.
+    pub fn update_team(&mut self, name: &str, update: impl FnOnce(&mut Team)) -> bool {
+        let Some(team) = self.teams.iter_mut().find(|t| t.name == name) else {
+            return false;
+        };
+        update(team);
+        let info = team.info();
+        self.send(ScoreboardPacket::Team(Teams {
+            name: name.to_string(),
+            action: TeamsAction::UpdateInfo(info),
+        }));
+        true
+    }
+    pub fn remove_team(&mut self, name: &str) -> bool {
+        if self.team(name).is_none() {
+            return false;
+        }
+        self.teams.retain(|t| t.name != name);
+        self.send(ScoreboardPacket::Team(Teams {
+            name: name.to_string(),
+            action: TeamsAction::Remove,
+        }));
+        true
+    }
+    /// Puts an entry on a team, taking it off the team it was on before.
+    pub fn join_team(&mut self, name: &str, entry: &str) -> bool {
+        if self.team(name).is_none() || entry.len() > MAX_ENTRY_LENGTH {
+            return false;
+        }
+        self.leave_team(entry);
+        let team = self.teams.iter_mut().find(|t| t.name == name).unwrap();
+        team.entries.push(entry.to_string());
+        self.send(ScoreboardPacket::Team(Teams {
+            name: name.to_string(),
+            action: TeamsAction::AddEntries(vec![entry.to_string()]),
+        }));
+        true
+    }
+    /// Returns false if the entry wasn't on a team.
+    pub fn leave_team(&mut self, entry: &str) -> bool {
+        let Some(team) = self
+            .teams
+            .iter_mut()
+            .find(|t| t.entries.iter().any(|e| e == entry))
+        else {
+            return false;
+        };
+        team.entries.retain(|e| e != entry);
+        let name = team.name.clone();
+        self.send(ScoreboardPacket::Team(Teams {
+            name,
+            action: TeamsAction::RemoveEntries(vec![entry.to_string()]),
+        }));
+        true
+    }
+}
@@ -0,0 +1,2081 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Shutdown, TcpStream};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use rand::Rng;
+
+use crate::access::AccessLists;
+use crate::bossbar::BossBar;
+use crate::command::{builtin, Command, Dispatcher};
+use crate::effects::{Particle, Sound, SoundCategory};
+use crate::entity::{
+    BoundingBox, Entity, EntityKind, Location, BLOCK_DROP_PICKUP_DELAY, ITEM_LIFETIME,
+    THROWN_PICKUP_DELAY, TRACKING_RANGE, VOID_Y,
+};
+use crate::inventory::{max_stack_size, HOTBAR, PLAYER_INVENTORY_SIZE, PLAYER_WINDOW};
+use crate::movement::{self, Verdict, PLAYER_EYE_HEIGHT, PLAYER_HEIGHT, PLAYER_WIDTH};
+use crate::packet::{Packet, Position, Slot};
+use crate::packets::play::{
+    BlockAction, BlockChange, ChangeGameState, ChatPosition, ClickWindow, ClientBoundChat,
+    ClientBoundCloseWindow, ClientBoundConfirmTransaction, ClientBoundKeepAlive,
+    ClientBoundPlayerPositionAndRotation, ClientBoundTabComplete, ClientSettings, CloseWindow,
+    CollectItem, CreativeInventoryAction, DestroyEntities, DiggingStatus, Disconnect, EntityAction,
+    EntityHeadLook, EntityLook, EntityLookAndRelativeMove, EntityMetadata, EntityRelativeMove,
+    EntityStatus, EntityTeleport, EntityVelocity, GameStateReason, HeldItemChange, MetadataValue,
+    MultiBlockChange, NamedSoundEffect, OpenSignEditor, OpenWindow, Particle as ParticlePacket,
+    PlayerBlockPlacement, PlayerDigging, PlayerListAction, PlayerListHeaderAndFooter,
+    PlayerListItem, ServerBoundConfirmTransaction, ServerBoundHeldItemChange,
+    ServerBoundPlayerAbilities, ServerBoundTabComplete, SetExperience, SetSlot, SpawnObject,
+    SpawnPlayer, UpdateBlockEntity, UpdateHealth, UpdateSign, WindowItems,
+};
+use crate::permissions::{Permissions, PERMISSIONS_FILE};
+use crate::player::{ChatMode, Gamemode, Player, BUCKET};
+use crate::playerdata::PlayerData;
+use crate::plugin::{
+    BlockBreak, BlockPlace, Chat, Command as PluginCommand, Event, Move, PlayerJoin, PlayerQuit,
+    Plugin, Plugins, ServerListPing,
+};
+use crate::scoreboard::{Scoreboard, ScoreboardPacket};
+use crate::world::block::Drops;
+use crate::world::block_entity::{BlockEntity, CHEST_SIZE, SIGN_ACTION};
+use crate::world::fluid::Fluid;
+use crate::world::redstone::Occupant;
+use crate::world::{self, block, BlockEvent, World};
+
+pub const TICK_LENGTH: Duration = Duration::from_millis(50);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(2500);
+/// How often every player is sent the time, between those their clients keep it themselves.
+const TIME_UPDATE_TICKS: u64 = 20;
+/// Positions that haven't been confirmed after this many ticks are sent again, like vanilla.
+const TELEPORT_RESEND_TICKS: u64 = 20;
+/// Player data is saved this often, on top of when they leave.
+const AUTOSAVE_TICKS: u64 = 6000;
+/// How far a sound at volume 1 is heard, louder sounds carry further.
+const SOUND_RANGE: f64 = 16.0;
+const PARTICLE_RANGE: f64 = 32.0;
+const LONG_PARTICLE_RANGE: f64 = 512.0;
+/// Vanilla accepts a block as broken once 70% of the expected time has passed.
+const BREAK_LENIENCY: f32 = 0.7;
+/// Longer chat messages get the player kicked, like in vanilla.
+const MAX_CHAT_LENGTH: usize = 256;
+/// Characters a line on a sign can hold, more than fit on it.
+const MAX_SIGN_LINE: usize = 384;
+pub const MAX_PLAYERS: u8 = 10;
+/// Shown under the server's name in server lists.
+const MOTD: &str = "§l§nMycelium Server 0.0";
+/// How many players a server list gets to see the names of, the same as vanilla.
+const MAX_SAMPLE: usize = 12;
+
+pub type Clients = Arc<Mutex<HashMap<usize, (Receiver<Message>, RefCell<TcpStream>)>>>;
+
+/// Sent from connection threads to the tick thread.
+#[derive(Debug)]
+pub enum Message {
+    PlayerJoined {
+        name: String,
+        uuid: u128,
+        entity_id: u32,
+        protocol: u32,
+        /// What the player had when they last left, `None` for new players.
+        data: Option<PlayerData>,
+    },
+    ConnectionClosed,
+    ClientSettings(ClientSettings),
+    Chat(String),
+    TabComplete(ServerBoundTabComplete),
+    KeepAlive {
+        id: i64,
+        received: Instant,
+    },
+    /// Any of the serverbound movement packets, with the fields they don't carry left out.
+    Movement {
+        position: Option<(f64, f64, f64)>,
+        rotation: Option<(f32, f32)>,
+        on_ground: bool,
+    },
+    Digging(PlayerDigging),
+    BlockPlacement(PlayerBlockPlacement),
+    HeldItemChange(ServerBoundHeldItemChange),
+    ClickWindow(ClickWindow),
+    CreativeInventoryAction(CreativeInventoryAction),
+    CloseWindow(CloseWindow),
+    ConfirmTransaction(ServerBoundConfirmTransaction),
+    PlayerAbilities(ServerBoundPlayerAbilities),
+    EntityAction(EntityAction),
+    TeleportConfirm(u32),
+    UpdateSign(UpdateSign),
+    /// A server list asked about the server. The answer goes back on `reply`.
+    StatusRequest {
+        protocol: u32,
+        reply: Sender<ServerListPing>,
+    },
+}
+
+/// Owns all game state and runs on the tick thread. Once a player has joined, every packet
+/// they receive is written from here.
+pub struct Server {
+    clients: Clients,
+    world: Arc<Mutex<World>>,
+    players: HashMap<usize, Player>,
+    /// Everything that isn't a player, by entity id.
+    entities: HashMap<u32, Entity>,
+    current_tick: u64,
+    last_keep_alive: Instant,
+    /// Id of the last keep alive sent, responses to older ones are ignored.
+    keep_alive_id: i64,
+    player_list_text: Option<PlayerListHeaderAndFooter>,
+    commands: Dispatcher,
+    /// Shared with connection threads, which check it on login.
+    access: Arc<Mutex<AccessLists>>,
+    permissions: Permissions,
+    scoreboard: Scoreboard,
+    boss_bars: HashMap<String, BossBar>,
+    plugins: Plugins,
+}
+impl Server {
+    pub fn new(
+        clients: Clients,
+        world: Arc<Mutex<World>>,
+        access: Arc<Mutex<AccessLists>>,
+    ) -> Self {
+        let mut commands = Dispatcher::new();
+        builtin::register(&mut commands);
+        Self {
+            clients,
+            world,
+            players: HashMap::new(),
+            entities: HashMap::new(),
+            current_tick: 0,
+            last_keep_alive: Instant::now(),
+            keep_alive_id: 0,
+            player_list_text: None,
+            commands,
+            access,
+            permissions: Permissions::load(PERMISSIONS_FILE),
+            scoreboard: Scoreboard::load(),
+            boss_bars: HashMap::new(),
+            plugins: Plugins::default(),
+        }
+    }
+    pub fn run(mut self) {
+        loop {
+            let start = Instant::now();
+            self.tick();
+            std::thread::sleep(TICK_LENGTH.saturating_sub(start.elapsed()));
+        }
+    }
+    fn tick(&mut self) {
+        self.current_tick += 1;
+
+        let mut messages = vec![];
+        for (index, (rx, _)) in self.clients.lock().unwrap().iter() {
+            loop {
+                match rx.try_recv() {
+                    Ok(Message::ConnectionClosed) => {
+                        messages.push((*index, Message::ConnectionClosed));
+                        break;
+                    }
+                    Ok(message) => messages.push((*index, message)),
+                    Err(TryRecvError::Empty) => break,
+                    // The connection thread is gone without saying so.
+                    Err(TryRecvError::Disconnected) => {
+                        messages.push((*index, Message::ConnectionClosed));
+                        break;
+                    }
+                }
+            }
+        }
+        for (index, message) in messages {
+            self.handle_message(index, message);
+        }
+        if self.current_tick.is_multiple_of(AUTOSAVE_TICKS) {
+            self.save_all();
+        }
+        self.resend_teleports();
+        self.tick_world();
+        self.tick_plugins();
+        self.tick_entities();
+        self.broadcast_movement();
+        self.update_tracking();
+        for packet in self.scoreboard.take_packets() {
+            self.send_scoreboard(None, &packet);
+        }
+        for (index, player) in self.players.iter_mut() {
+            for title in player.titles.drain(..) {
+                send_to(&self.clients, *index, &title);
+            }
+        }
+        for bar in self.boss_bars.values_mut() {
+            for (index, packet) in bar.updates() {
+                send_to(&self.clients, index, &packet);
+            }
+        }
+
+        if self.last_keep_alive.elapsed() > KEEP_ALIVE_INTERVAL {
+            self.last_keep_alive = Instant::now();
+            self.keep_alive_id = self.current_tick as i64;
+            self.broadcast(&ClientBoundKeepAlive::new(self.keep_alive_id));
+        }
+    }
+    fn handle_message(&mut self, index: usize, message: Message) {
+        match message {
+            Message::PlayerJoined {
+                name,
+                uuid,
+                entity_id,
+                protocol,
+                data,
+            } => {
+                info!("Player `{}` joined!", name);
+                let (x, y, z) = world::SPAWN;
+                let mut player = Player::new(
+                    name.clone(),
+                    uuid,
+                    entity_id,
+                    Gamemode::Creative,
+                    Location::new(x, y, z),
+                    protocol,
+                );
+                if let Some(data) = data {
+                    data.apply(&mut player);
+                }
+                self.send(
+                    index,
+                    &UpdateHealth {
+                        health: player.health,
+                        food: player.food,
+                        saturation: player.saturation,
+                    },
+                );
+                self.send(
+                    index,
+                    &SetExperience {
+                        bar: player.xp_progress,
+                        level: player.xp_level,
+                        total: player.xp_total,
+                    },
+                );
+                self.send(
+                    index,
+                    &HeldItemChange {
+                        slot: player.inventory.selected as u8,
+                    },
+                );
+                self.players.insert(index, player);
+                self.send_position(index, 0);
+                let op_level = self.access.lock().unwrap().ops.level(uuid);
+                self.set_op_level(index, op_level);
+                self.send_inventory(index);
+                // Clients won't spawn a player that isn't on their player list.
+                self.broadcast(&PlayerListItem(vec![(
+                    uuid,
+                    list_entry(&self.players[&index]),
+                )]));
+                let others = self
+                    .players
+                    .iter()
+                    .filter(|(i, _)| **i != index)
+                    .map(|(_, p)| (p.uuid, list_entry(p)))
+                    .collect::<Vec<_>>();
+                if !others.is_empty() {
+                    self.send(index, &PlayerListItem(others));
+                }
+                if let Some(text) = &self.player_list_text {
+                    self.send(index, text);
+                }
+                let world = self.world.lock().unwrap();
+                self.send(index, &world.time_update());
+                for packet in world.weather_packets() {
+                    self.send(index, &packet);
+                }
+                drop(world);
+                for packet in self.scoreboard.packets() {
+                    self.send_scoreboard(Some(index), &packet);
+                }
+                let mut join = PlayerJoin {
+                    player: index,
+                    message: Some(format!("{{\"text\": \"+{} joined.\"}}", name)),
+                    name,
+                    uuid,
+                };
+                self.fire(&mut join);
+                if let Some(message) = join.message {
+                    self.broadcast_chat(&message, ChatPosition::System);
+                }
+            }
+            Message::ConnectionClosed => {
+                if let Some(player) = self.players.get(&index) {
+                    let mut quit = PlayerQuit {
+                        player: index,
+                        name: player.name.clone(),
+                        uuid: player.uuid,
+                        message: Some(format!("{{\"text\": \"-{} left.\"}}", player.name)),
+                    };
+                    self.fire(&mut quit);
+                    if let Some(message) = quit.message {
+                        let everyone_else: Vec<usize> = self
+                            .players
+                            .keys()
+                            .copied()
+                            .filter(|i| *i != index)
+                            .collect();
+                        for other in everyone_else {
+                            self.send(
+                                other,
+                                &ClientBoundChat(message.clone(), ChatPosition::System),
+                            );
+                        }
+                    }
+                }
+                // Like closing the window, so the cursor and crafting grid aren't lost.
+                if self
+                    .players
+                    .get(&index)
+                    .is_some_and(|p| p.open_window.is_some())
+                {
+                    self.close_chest(index);
+                } else if let Some(player) = self.players.get_mut(&index) {
+                    for stack in player.inventory.close() {
+                        self.throw_item(index, stack);
+                    }
+                }
+                for bar in self.boss_bars.values_mut() {
+                    bar.forget(index);
+                }
+                if let Some(player) = self.players.remove(&index) {
+                    PlayerData::from_player(&player).save(player.uuid);
+                    for (viewer, other) in self.players.iter_mut() {
+                        if other.tracking.remove(&player.entity_id) {
+                            send_to(
+                                &self.clients,
+                                *viewer,
+                                &DestroyEntities(vec![player.entity_id]),
+                            );
+                        }
+                    }
+                    self.broadcast(&PlayerListItem(vec![(
+                        player.uuid,
+                        PlayerListAction::RemovePlayer,
+                    )]));
+                }
+                self.clients.lock().unwrap().remove(&index);
+                info!("Removed {}", index);
+            }
+            Message::ClientSettings(settings) => {
+                if let Some(player) = self.players.get_mut(&index) {
+                    player.chat_mode = ChatMode::from_id(settings.chat_mode);
+                }
+            }
+            Message::Chat(message) => self.handle_chat(index, message),
+            Message::StatusRequest { protocol, reply } => {
+                self.handle_status_request(protocol, reply)
+            }
+            Message::TabComplete(request) => self.handle_tab_complete(index, request),
+            Message::KeepAlive { id, received } => {
+                if id != self.keep_alive_id {
+                    return;
+                }
+                let Some(player) = self.players.get_mut(&index) else {
+                    return;
+                };
+                let rtt = received
+                    .saturating_duration_since(self.last_keep_alive)
+                    .as_millis() as u32;
+                // Same smoothing as vanilla, so one slow response doesn't make the bars jump.
+                player.ping = (player.ping * 3 + rtt) / 4;
+                let update = PlayerListItem(vec![(
+                    player.uuid,
+                    PlayerListAction::UpdateLatency(player.ping),
+                )]);
+                self.broadcast(&update);
+            }
+            Message::Movement {
+                position,
+                rotation,
+                on_ground,
+            } => self.handle_movement(index, position, rotation, on_ground),
+            Message::TeleportConfirm(id) => {
+                if let Some(player) = self.players.get_mut(&index) {
+                    if player.teleport_id == id {
+                        player.awaiting_teleport = None;
+                    }
+                }
+            }
+            Message::PlayerAbilities(abilities) => {
+                if let Some(player) = self.players.get_mut(&index) {
+                    player.flying = abilities.flags & 0x02 != 0;
+                }
+            }
+            Message::EntityAction(action) => {
+                if let Some(player) = self.players.get_mut(&index) {
+                    match action.action {
+                        0 => player.sneaking = true,
+                        1 => player.sneaking = false,
+                        3 => player.sprinting = true,
+                        4 => player.sprinting = false,
+                        _ => {}
+                    }
+                }
+            }
+            Message::Digging(digging) => self.handle_digging(index, digging),
+            Message::BlockPlacement(placement) => self.handle_placement(index, placement),
+            Message::HeldItemChange(change) => {
+                if let Some(player) = self.players.get_mut(&index) {
+                    if (0..9).contains(&change.slot) {
+                        player.inventory.selected = change.slot as usize;
+                    }
+                }
+            }
+            Message::ClickWindow(click) => self.handle_click(index, click),
+            Message::CreativeInventoryAction(action) => self.handle_creative_action(index, action),
+            Message::CloseWindow(close) => {
+                let Some(player) = self.players.get_mut(&index) else {
+                    return;
+                };
+                if close.window_id == PLAYER_WINDOW {
+                    // Whatever doesn't fit back into the inventory falls out.
+                    for stack in player.inventory.close() {
+                        self.throw_item(index, stack);
+                    }
+                } else if player
+                    .open_window
+                    .is_some_and(|(id, _)| id == close.window_id)
+                {
+                    self.close_chest(index);
+                }
+            }
+            Message::UpdateSign(update) => self.handle_update_sign(index, update),
+            Message::ConfirmTransaction(confirm) => {
+                let Some(player) = self.players.get_mut(&index) else {
+                    return;
+                };
+                if player.inventory.awaiting_apology == Some(confirm.window_id as u8) {
+                    player.inventory.awaiting_apology = None;
+                }
+            }
+        }
+    }
+    /// Sends a packet to one player.
+    pub fn send<P: Packet<TcpStream>>(&self, index: usize, packet: &P) {
+        send_to(&self.clients, index, packet);
+    }
+    /// Sends a packet to every player that has been sent the given entity.
+    pub fn send_to_trackers<P: Packet<TcpStream>>(&self, entity_id: u32, packet: &P) {
+        for (index, player) in &self.players {
+            if player.tracking.contains(&entity_id) {
+                self.send(*index, packet);
+            }
+        }
+    }
+    /// Sends a json text component to every player whose chat settings allow it.
+    pub fn broadcast_chat(&self, message: &str, position: ChatPosition) {
+        for (index, player) in &self.players {
+            if player.chat_mode.accepts(position) {
+                self.send(*index, &ClientBoundChat(message.to_string(), position));
+            }
+        }
+    }
+    /// Disconnects a player, showing them the given json text component. The connection
+    /// thread notices the closed socket and the player gets removed like any other quit.
+    pub fn kick(&self, index: usize, reason: &str) {
+        if let Some((_, stream)) = self.clients.lock().unwrap().get(&index) {
+            Disconnect(reason.to_string()).write(&mut *stream.borrow_mut());
+            stream.borrow().shutdown(Shutdown::Both).ok();
+        }
+    }
+    fn handle_chat(&mut self, index: usize, message: String) {
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        if message.chars().count() > MAX_CHAT_LENGTH {
+            warn!("{} sent a chat message that is too long", player.name);
+            self.kick(index, r#"{"text": "Chat message too long"}"#);
+            return;
+        }
+        if !message.chars().all(allowed_in_chat) {
+            warn!("{} sent illegal characters in chat", player.name);
+            self.kick(
+                index,
+                r#"{"translate": "multiplayer.disconnect.illegal_characters"}"#,
+            );
+            return;
+        }
+        if player.chat_mode == ChatMode::Hidden {
+            let cannot_send = r#"{"translate": "chat.cannotSend", "color": "red"}"#;
+            self.send(
+                index,
+                &ClientBoundChat(cannot_send.into(), ChatPosition::System),
+            );
+            return;
+        }
+        if let Some(command) = message.strip_prefix('/') {
+            self.handle_command(index, command);
+            return;
+        }
+        let message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+        if message.is_empty() {
+            return;
+        }
+        let mut chat = Chat {
+            player: index,
+            message,
+            cancelled: false,
+        };
+        self.fire(&mut chat);
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        if chat.cancelled {
+            return;
+        }
+        let message = chat.message;
+        info!("<{}> {}", player.name, message);
+        let component = serde_json::json!({
+            "translate": "chat.type.text",
+            "with": [{ "text": player.name }, message],
+        });
+        self.broadcast_chat(&component.to_string(), ChatPosition::Chat);
+    }
+    fn handle_command(&mut self, index: usize, line: &str) {
+        info!(
+            "{} issued server command: /{}",
+            self.players[&index].name, line
+        );
+        let mut command = PluginCommand {
+            player: index,
+            line: line.to_string(),
+            cancelled: false,
+        };
+        self.fire(&mut command);
+        if command.cancelled || !self.players.contains_key(&index) {
+            return;
+        }
+        let line = command.line.as_str();
+        let result = self
+            .commands
+            .parse(self, index, line)
+            .and_then(|(executor, context)| executor(self, &context));
+        if let Err(e) = result {
+            self.send_message(index, &e.to_json());
+        }
+    }
+    fn handle_tab_complete(&mut self, index: usize, request: ServerBoundTabComplete) {
+        let line = match request.text.strip_prefix('/') {
+            Some(line) => Some(line),
+            None if request.assume_command => Some(request.text.as_str()),
+            None => None,
+        };
+        let matches = match line {
+            Some(line) => self
+                .commands
+                .complete(self, index, line, request.looked_at_block),
+            // In plain chat the last word gets completed to a player name.
+            None => {
+                let last = request.text.rsplit(' ').next().unwrap().to_lowercase();
+                self.players
+                    .values()
+                    .map(|p| p.name.clone())
+                    .filter(|n| n.to_lowercase().starts_with(&last))
+                    .collect()
+            }
+        };
+        self.send(index, &ClientBoundTabComplete(matches));
+    }
+    pub fn player(&self, index: usize) -> Option<&Player> {
+        self.players.get(&index)
+    }
+    pub fn player_mut(&mut self, index: usize) -> Option<&mut Player> {
+        self.players.get_mut(&index)
+    }
+    pub fn players(&self) -> impl Iterator<Item = (usize, &Player)> {
+        self.players.iter().map(|(i, p)| (*i, p))
+    }
+    /// Finds an online player by name, ignoring case.
+    pub fn player_by_name(&self, name: &str) -> Option<usize> {
+        self.players
+            .iter()
+            .find(|(_, p)| p.name.eq_ignore_ascii_case(name))
+            .map(|(i, _)| *i)
+    }
+    /// Whether a player is allowed to run a command, either through their operator level or
+    /// the command's permission node.
+    pub fn can_use(&self, index: usize, command: &Command) -> bool {
+        self.players
+            .get(&index)
+            .is_some_and(|p| p.op_level >= command.level)
+            || self.has_permission(index, &command.permission())
+    }
+    pub fn has_permission(&self, index: usize, node: &str) -> bool {
+        self.players
+            .get(&index)
+            .is_some_and(|p| self.permissions.has(p.uuid, &p.name, node))
+    }
+    /// Updates a player's operator level for this session, and tells their client what it
+    /// may now show.
+    pub fn set_op_level(&mut self, index: usize, level: u8) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        player.op_level = level;
+        let (entity_id, protocol) = (player.entity_id, player.protocol);
+        self.send(index, &EntityStatus::op_level(entity_id, level));
+        // Declare Commands only exists from 1.13 on.
+        if protocol >= 393 {
+            self.send(index, &self.commands.graph(self, index));
+        }
+    }
+    /// Makes a player an operator, or removes them from the list for level 0, and saves it.
+    pub fn op(&mut self, index: usize, level: u8) {
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        let mut access = self.access.lock().unwrap();
+        access.ops.set(player.uuid, &player.name, level);
+        access.ops.save();
+        drop(access);
+        self.set_op_level(index, level);
+    }
+    /// Sends a json text component as a system message.
+    pub fn send_message(&self, index: usize, message: &str) {
+        if let Some(player) = self.players.get(&index) {
+            if player.chat_mode.accepts(ChatPosition::System) {
+                self.send(
+                    index,
+                    &ClientBoundChat(message.into(), ChatPosition::System),
+                );
+            }
+        }
+    }
+    pub fn access(&self) -> MutexGuard<'_, AccessLists> {
+        self.access.lock().unwrap()
+    }
+    pub fn world(&self) -> MutexGuard<'_, World> {
+        self.world.lock().unwrap()
+    }
+    /// The world, for code that keeps it around longer than a call.
+    pub fn world_handle(&self) -> Arc<Mutex<World>> {
+        self.world.clone()
+    }
+    /// Changes made here reach every player at the end of the tick.
+    pub fn scoreboard(&mut self) -> &mut Scoreboard {
+        &mut self.scoreboard
+    }
+    /// Plays a sound at a location for everyone who can hear it, which is within 16 blocks at
+    /// volume 1 and further for louder sounds.
+    pub fn play_sound(
+        &self,
+        location: &Location,
+        sound: &Sound,
+        category: SoundCategory,
+        volume: f32,
+        pitch: f32,
+    ) {
+        let range = SOUND_RANGE * volume.max(1.0) as f64;
+        let packet = NamedSoundEffect {
+            name: sound.name().to_string(),
+            category: category as u32,
+            position: (location.x, location.y, location.z),
+            volume,
+            pitch,
+        };
+        for (index, player) in self.players.iter() {
+            if player.location.distance_squared(location) <= range * range {
+                self.send(*index, &packet);
+            }
+        }
+    }
+    /// Spawns `count` particles spread around a location by `offset`, for everyone within 32
+    /// blocks, or 512 for `long_distance` ones.
+    pub fn spawn_particles(
+        &self,
+        location: &Location,
+        particle: Particle,
+        offset: (f32, f32, f32),
+        speed: f32,
+        count: i32,
+        long_distance: bool,
+    ) {
+        let range = if long_distance {
+            LONG_PARTICLE_RANGE
+        } else {
+            PARTICLE_RANGE
+        };
+        let packet = ParticlePacket {
+            particle_id: particle.id(),
+            long_distance,
+            position: (location.x as f32, location.y as f32, location.z as f32),
+            offset,
+            data: speed,
+            count,
+            extra: particle.data(),
+        };
+        for (index, player) in self.players.iter() {
+            if player.location.distance_squared(location) <= range * range {
+                self.send(*index, &packet);
+            }
+        }
+    }
+    pub fn boss_bar(&mut self, id: &str) -> Option<&mut BossBar> {
+        self.boss_bars.get_mut(id)
+    }
+    pub fn boss_bars(&self) -> &HashMap<String, BossBar> {
+        &self.boss_bars
+    }
+    /// Returns false if the id is taken.
+    pub fn add_boss_bar(&mut self, id: &str, bar: BossBar) -> bool {
+        if self.boss_bars.contains_key(id) {
+            return false;
+        }
+        self.boss_bars.insert(id.to_string(), bar);
+        true
+    }
+    /// Takes the bar off everyone's screen right away.
+    pub fn remove_boss_bar(&mut self, id: &str) -> Option<BossBar> {
+        let mut bar = self.boss_bars.remove(id)?;
+        bar.remove_all_players();
+        for (index, packet) in bar.updates() {
+            self.send(index, &packet);
+        }
+        Some(bar)
+    }
+    /// Sends to one player, or everyone with `None`.
+    fn send_scoreboard(&self, index: Option<usize>, packet: &ScoreboardPacket) {
+        fn send<P: Packet<TcpStream>>(server: &Server, index: Option<usize>, packet: &P) {
+            match index {
+                Some(index) => server.send(index, packet),
+                None => server.broadcast(packet),
+            }
+        }
+        match packet {
+            ScoreboardPacket::Objective(p) => send(self, index, p),
+            ScoreboardPacket::Score(p) => send(self, index, p),
+            ScoreboardPacket::Display(p) => send(self, index, p),
+            ScoreboardPacket::Team(p) => send(self, index, p),
+        }
+    }
+    /// The address a player is connected from.
+    pub fn address(&self, index: usize) -> Option<IpAddr> {
+        let clients = self.clients.lock().unwrap();
+        let (_, stream) = clients.get(&index)?;
+        let address = stream.borrow().peer_addr().ok()?;
+        Some(address.ip())
+    }
+    /// Moves a player, telling their client and everyone who can see them. Their movement
+    /// packets are ignored until the client confirms it got there.
+    pub fn teleport(&mut self, index: usize, location: Location) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        player.location = location;
+        self.send_position(index, 0);
+    }
+    /// Sends a player where the server has them, with a fresh teleport id. `flags` marks
+    /// fields as relative, like vanilla's.
+    fn send_position(&mut self, index: usize, flags: i8) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        player.teleport_id = player.teleport_id.wrapping_add(1);
+        player.awaiting_teleport = Some(self.current_tick);
+        let relative = |flag: i8, value: f32| if flags & flag != 0 { 0.0 } else { value };
+        let packet = ClientBoundPlayerPositionAndRotation {
+            x: player.location.x,
+            y: player.location.y,
+            z: player.location.z,
+            yaw: relative(0x08, player.location.yaw),
+            pitch: relative(0x10, player.location.pitch),
+            flags,
+            teleport_id: player.teleport_id,
+            dismount_veicle: false,
+        };
+        self.send(index, &packet);
+    }
+    pub fn save_players(&self) {
+        for player in self.players.values() {
+            PlayerData::from_player(player).save(player.uuid);
+        }
+        debug!("Saved {} players.", self.players.len());
+    }
+    /// Runs the plugin handlers for an event. Events fired from inside a handler don't reach
+    /// any handlers.
+    pub fn fire<E: Event>(&mut self, event: &mut E) {
+        let mut bus = self.plugins.take_bus();
+        bus.fire(event, self);
+        self.plugins.restore_bus(bus);
+    }
+    /// Enables a plugin, returning the id to unload it with.
+    pub fn load_plugin(&mut self, plugin: Box<dyn Plugin>) -> u64 {
+        info!("Enabling {}.", plugin.name());
+        self.plugins.load(plugin)
+    }
+    pub fn unload_plugin(&mut self, id: u64) {
+        if let Some(mut plugin) = self.plugins.unload(id) {
+            info!("Disabling {}.", plugin.name());
+            plugin.disable(self);
+        }
+    }
+    fn tick_plugins(&mut self) {
+        for id in self.plugins.ids() {
+            // Plugins can unload each other, so one may be gone by its turn.
+            let Some(mut plugin) = self.plugins.take(id) else {
+                continue;
+            };
+            plugin.tick(self);
+            self.plugins.put_back(id, plugin);
+        }
+    }
+    /// Answers a server list, letting plugins change what it shows.
+    fn handle_status_request(&mut self, protocol: u32, reply: Sender<ServerListPing>) {
+        let mut ping = ServerListPing {
+            protocol,
+            motd: MOTD.to_string(),
+            max_players: MAX_PLAYERS as i32,
+            online: self.players.len() as i32,
+            sample: self
+                .players
+                .values()
+                .take(MAX_SAMPLE)
+                .map(|p| (p.name.clone(), p.uuid))
+                .collect(),
+        };
+        self.fire(&mut ping);
+        // The connection may have given up waiting.
+        reply.send(ping).ok();
+    }
+    /// Saves players, the scoreboard and every changed chunk.
+    pub fn save_all(&mut self) {
+        self.save_players();
+        self.scoreboard.save();
+        self.world().save();
+    }
+    fn resend_teleports(&mut self) {
+        let overdue: Vec<usize> = self
+            .players
+            .iter()
+            .filter(|(_, p)| {
+                p.awaiting_teleport
+                    .is_some_and(|sent| self.current_tick - sent >= TELEPORT_RESEND_TICKS)
+            })
+            .map(|(index, _)| *index)
+            .collect();
+        for index in overdue {
+            self.send_position(index, 0);
+        }
+    }
+    fn handle_movement(
+        &mut self,
+        index: usize,
+        position: Option<(f64, f64, f64)>,
+        rotation: Option<(f32, f32)>,
+        on_ground: bool,
+    ) {
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        if player.awaiting_teleport.is_some() {
+            return;
+        }
+        let mut to = player.location;
+        if let Some((x, y, z)) = position {
+            (to.x, to.y, to.z) = (x, y, z);
+        }
+        if let Some((yaw, pitch)) = rotation {
+            (to.yaw, to.pitch) = (yaw, pitch);
+        }
+        let verdict = movement::check(player, &to, &self.world.lock().unwrap());
+        match verdict {
+            Verdict::Accept => {
+                let mut event = Move {
+                    player: index,
+                    from: player.location,
+                    to,
+                    cancelled: false,
+                };
+                // Packets that only say whether the player is on the ground aren't moves.
+                if position.is_some() || rotation.is_some() {
+                    self.fire(&mut event);
+                }
+                if event.cancelled {
+                    self.send_position(index, 0);
+                    return;
+                }
+                let Some(player) = self.players.get_mut(&index) else {
+                    return;
+                };
+                player.location = event.to;
+                player.on_ground = on_ground;
+            }
+            Verdict::RubberBand(reason) => {
+                let from = player.location;
+                warn!(
+                    "{} {}! ({:.2}, {:.2}, {:.2}) -> ({:.2}, {:.2}, {:.2})",
+                    player.name, reason, from.x, from.y, from.z, to.x, to.y, to.z
+                );
+                // Keep where they are looking, only the position is sent back.
+                let player = self.players.get_mut(&index).unwrap();
+                (player.location.yaw, player.location.pitch) = (to.yaw, to.pitch);
+                self.send_position(index, 0x08 | 0x10);
+            }
+            Verdict::Kick => {
+                warn!("{} sent an invalid position, kicking them.", player.name);
+                self.kick(
+                    index,
+                    r#"{"translate": "multiplayer.disconnect.invalid_player_movement"}"#,
+                );
+            }
+        }
+    }
+    /// Puts a stack into a player's inventory, leaving whatever didn't fit in `stack`.
+    pub fn give(&mut self, index: usize, stack: &mut Slot) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        let changed = player.inventory.add(stack);
+        for slot in changed {
+            let slot_data = self.players[&index].inventory.slots[slot].clone();
+            self.send(
+                index,
+                &SetSlot {
+                    window_id: PLAYER_WINDOW as i8,
+                    slot: slot as i16,
+                    slot_data,
+                },
+            );
+        }
+    }
+    /// Advances the clock and weather, telling players about any change in the weather.
+    fn tick_world(&mut self) {
+        let mut occupants = HashMap::new();
+        for player in self.players.values() {
+            if player.gamemode != Gamemode::Spectator {
+                occupants.insert(player.location.block(), Occupant::Mob);
+            }
+        }
+        for entity in self.entities.values() {
+            occupants
+                .entry(entity.location.block())
+                .or_insert(Occupant::Item);
+        }
+        let mut world = self.world.lock().unwrap();
+        world.occupants = occupants;
+        let before = world.weather;
+        world.tick();
+        let after = world.weather;
+        let mut packets = vec![];
+        if before.is_raining() != after.is_raining() {
+            packets.push(ChangeGameState {
+                reason: if after.is_raining() {
+                    GameStateReason::BeginRaining
+                } else {
+                    GameStateReason::EndRaining
+                },
+                value: 0.0,
+            });
+        }
+        if before.rain_level != after.rain_level {
+            packets.push(ChangeGameState {
+                reason: GameStateReason::FadeValue,
+                value: after.rain_level,
+            });
+        }
+        if before.thunder_level != after.thunder_level {
+            packets.push(ChangeGameState {
+                reason: GameStateReason::FadeTime,
+                value: after.thunder_level,
+            });
+        }
+        let time = self
+            .current_tick
+            .is_multiple_of(TIME_UPDATE_TICKS)
+            .then(|| world.time_update());
+        drop(world);
+        for packet in packets {
+            self.broadcast(&packet);
+        }
+        if let Some(time) = time {
+            self.broadcast(&time);
+        }
+        self.send_block_changes();
+    }
+    /// Sends out the blocks the world changed this tick, batched per chunk column, and plays
+    /// what went with them.
+    fn send_block_changes(&mut self) {
+        let mut world = self.world.lock().unwrap();
+        let mut columns: HashMap<(i32, i32), Vec<(Position, u32)>> = HashMap::new();
+        let mut seen = HashSet::new();
+        for position in std::mem::take(&mut world.changed) {
+            if seen.insert(position) {
+                let state = world.block_at(position) as u32;
+                columns
+                    .entry((position.x >> 4, position.z >> 4))
+                    .or_default()
+                    .push((position, state));
+            }
+        }
+        let events = std::mem::take(&mut world.events);
+        drop(world);
+        for ((chunk_x, chunk_z), records) in columns {
+            match records[..] {
+                [(location, block_id)] => self.broadcast(&BlockChange { location, block_id }),
+                _ => self.broadcast(&MultiBlockChange {
+                    chunk_x,
+                    chunk_z,
+                    records,
+                }),
+            }
+        }
+        // Actions go first so pistons start moving along with the blocks they push.
+        for event in &events {
+            if let BlockEvent::Action {
+                position,
+                action,
+                param,
+                block,
+            } = *event
+            {
+                self.broadcast(&BlockAction {
+                    location: position,
+                    action_id: action,
+                    action_param: param,
+                    block_type: block as u32,
+                });
+            }
+        }
+        let mut rng = rand::thread_rng();
+        for event in events {
+            match event {
+                BlockEvent::Fizz(position) => {
+                    let center = Location::new(
+                        position.x as f64 + 0.5,
+                        position.y as f64 + 0.5,
+                        position.z as f64 + 0.5,
+                    );
+                    let pitch = 2.6 + (rng.gen::<f32>() - rng.gen::<f32>()) * 0.8;
+                    self.play_sound(
+                        &center,
+                        &Sound::LavaExtinguish,
+                        SoundCategory::Block,
+                        0.5,
+                        pitch,
+                    );
+                    self.spawn_particles(
+                        &center,
+                        Particle::LargeSmoke,
+                        (0.25, 0.25, 0.25),
+                        0.0,
+                        8,
+                        false,
+                    );
+                }
+                BlockEvent::Destroyed(position, state) => self.drop_block(position, state),
+                BlockEvent::Sound {
+                    position,
+                    sound,
+                    volume,
+                    pitch,
+                } => {
+                    let center = Location::new(
+                        position.x as f64 + 0.5,
+                        position.y as f64 + 0.5,
+                        position.z as f64 + 0.5,
+                    );
+                    self.play_sound(&center, &sound, SoundCategory::Block, volume, pitch);
+                }
+                BlockEvent::Action { .. } => {}
+            }
+        }
+    }
+    /// Sends every player the time right away, after it was changed.
+    pub fn broadcast_time(&self) {
+        let time = self.world().time_update();
+        self.broadcast(&time);
+    }
+    /// Runs physics for every entity, sending new velocities to whoever can see them.
+    fn tick_entities(&mut self) {
+        let mut expired = vec![];
+        for entity in self.entities.values_mut() {
+            entity.age += 1;
+            let EntityKind::Item { pickup_delay, .. } = &mut entity.kind;
+            *pickup_delay = pickup_delay.saturating_sub(1);
+            if entity.age >= ITEM_LIFETIME || entity.location.y < VOID_Y {
+                expired.push(entity.entity_id);
+            }
+        }
+        for entity_id in expired {
+            self.remove_entity(entity_id);
+        }
+        self.merge_items();
+        self.pick_up_items();
+
+        let world = self.world.lock().unwrap();
+        let mut changed = vec![];
+        for entity in self.entities.values_mut() {
+            entity.step(&world);
+            let (x, y, z) = entity.velocity;
+            let (last_x, last_y, last_z) = entity.last_sent_velocity;
+            let difference = (x - last_x).powi(2) + (y - last_y).powi(2) + (z - last_z).powi(2);
+            // Small changes are left to the client's own prediction, but stopping isn't.
+            if difference > 4e-4 || (difference > 0.0 && (x, y, z) == (0.0, 0.0, 0.0)) {
+                entity.last_sent_velocity = entity.velocity;
+                changed.push((entity.entity_id, entity.velocity));
+            }
+        }
+        drop(world);
+        for (entity_id, velocity) in changed {
+            self.send_to_trackers(
+                entity_id,
+                &EntityVelocity {
+                    entity_id,
+                    velocity,
+                },
+            );
+        }
+    }
+    /// Adds an entity, players are sent it once they are in range.
+    pub fn spawn_entity(&mut self, entity: Entity) -> u32 {
+        let entity_id = entity.entity_id;
+        self.entities.insert(entity_id, entity);
+        entity_id
+    }
+    pub fn remove_entity(&mut self, entity_id: u32) {
+        if self.entities.remove(&entity_id).is_none() {
+            return;
+        }
+        self.send_to_trackers(entity_id, &DestroyEntities(vec![entity_id]));
+        for player in self.players.values_mut() {
+            player.tracking.remove(&entity_id);
+        }
+    }
+    /// Drops an item stack into the world.
+    pub fn drop_item(
+        &mut self,
+        location: Location,
+        stack: Slot,
+        velocity: (f64, f64, f64),
+        pickup_delay: u32,
+    ) -> u32 {
+        let kind = EntityKind::Item {
+            stack,
+            pickup_delay,
+        };
+        self.spawn_entity(Entity::new(kind, location, velocity))
+    }
+    /// Throws a stack out of a player's eyes in the direction they are looking, like vanilla.
+    fn throw_item(&mut self, index: usize, stack: Slot) {
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        let mut location = player.location;
+        location.y += PLAYER_EYE_HEIGHT - 0.3;
+        let (yaw, pitch) = (
+            (location.yaw as f64).to_radians(),
+            (location.pitch as f64).to_radians(),
+        );
+        let mut rng = rand::thread_rng();
+        let angle = rng.gen::<f64>() * std::f64::consts::TAU;
+        let spread = rng.gen::<f64>() * 0.02;
+        let velocity = (
+            -yaw.sin() * pitch.cos() * 0.3 + angle.cos() * spread,
+            -pitch.sin() * 0.3 + 0.1 + (rng.gen::<f64>() - rng.gen::<f64>()) * 0.1,
+            yaw.cos() * pitch.cos() * 0.3 + angle.sin() * spread,
+        );
+        self.drop_item(location, stack, velocity, THROWN_PICKUP_DELAY);
+    }
+    /// Merges items lying close to each other into the bigger stack, as far as they fit.
+    fn merge_items(&mut self) {
+        let ids: Vec<u32> = self.entities.keys().copied().collect();
+        for (i, a) in ids.iter().enumerate() {
+            for b in &ids[i + 1..] {
+                let (Some(first), Some(second)) = (self.entities.get(a), self.entities.get(b))
+                else {
+                    continue;
+                };
+                let (EntityKind::Item { stack: s1, .. }, EntityKind::Item { stack: s2, .. }) =
+                    (&first.kind, &second.kind);
+                let close = first
+                    .bounding_box()
+                    .grow(0.5, 0.0, 0.5)
+                    .intersects(&second.bounding_box());
+                if !close
+                    || !s1.same_item(s2)
+                    || s1.count as i32 + s2.count as i32 > max_stack_size(s1.id) as i32
+                {
+                    continue;
+                }
+                let (kept, merged) = if s1.count >= s2.count {
+                    (*a, *b)
+                } else {
+                    (*b, *a)
+                };
+                let merged_age = self.entities[&merged].age;
+                let EntityKind::Item {
+                    stack: merged_stack,
+                    pickup_delay: merged_delay,
+                } = self.entities[&merged].kind.clone();
+                let entity = self.entities.get_mut(&kept).unwrap();
+                entity.age = entity.age.min(merged_age);
+                let EntityKind::Item {
+                    stack,
+                    pickup_delay,
+                } = &mut entity.kind;
+                stack.count += merged_stack.count;
+                *pickup_delay = (*pickup_delay).max(merged_delay);
+                let update = metadata(entity);
+                self.remove_entity(merged);
+                self.send_to_trackers(kept, &update);
+            }
+        }
+    }
+    /// Gives items to the players standing on them.
+    fn pick_up_items(&mut self) {
+        let mut pickups = vec![];
+        for entity in self.entities.values() {
+            let EntityKind::Item { pickup_delay, .. } = &entity.kind;
+            if *pickup_delay > 0 {
+                continue;
+            }
+            let item_box = entity.bounding_box();
+            let collector = self.players.iter().find(|(_, p)| {
+                let Location { x, y, z, .. } = p.location;
+                p.gamemode != Gamemode::Spectator
+                    && BoundingBox::at(x, y, z, PLAYER_WIDTH, PLAYER_HEIGHT)
+                        .grow(1.0, 0.5, 1.0)
+                        .intersects(&item_box)
+            });
+            if let Some((index, _)) = collector {
+                pickups.push((*index, entity.entity_id));
+            }
+        }
+        for (index, entity_id) in pickups {
+            let EntityKind::Item { stack, .. } = &self.entities[&entity_id].kind;
+            let before = stack.count;
+            let mut remaining = stack.clone();
+            self.give(index, &mut remaining);
+            let picked = before - remaining.count.max(0);
+            if picked == 0 {
+                continue;
+            }
+            let collect = CollectItem {
+                collected: entity_id,
+                collector: self.players[&index].entity_id,
+                count: picked as u32,
+            };
+            self.send_to_trackers(entity_id, &collect);
+            if !self.players[&index].tracking.contains(&entity_id) {
+                self.send(index, &collect);
+            }
+            if remaining.is_empty() {
+                self.remove_entity(entity_id);
+            } else {
+                let entity = self.entities.get_mut(&entity_id).unwrap();
+                let EntityKind::Item { stack, .. } = &mut entity.kind;
+                *stack = remaining;
+                let update = metadata(entity);
+                self.send_to_trackers(entity_id, &update);
+            }
+        }
+    }
+    /// Tells everyone who can see a player or entity how it moved since the last tick.
+    fn broadcast_movement(&mut self) {
+        let mut moved = vec![];
+        for player in self.players.values_mut() {
+            if player.location != player.last_sent {
+                moved.push((
+                    player.entity_id,
+                    player.last_sent,
+                    player.location,
+                    player.on_ground,
+                ));
+                player.last_sent = player.location;
+            }
+        }
+        for entity in self.entities.values_mut() {
+            if entity.location != entity.last_sent {
+                moved.push((
+                    entity.entity_id,
+                    entity.last_sent,
+                    entity.location,
+                    entity.on_ground,
+                ));
+                entity.last_sent = entity.location;
+            }
+        }
+        for (entity_id, from, to, on_ground) in moved {
+            let looked = (from.yaw, from.pitch) != (to.yaw, to.pitch);
+            let position_changed = (from.x, from.y, from.z) != (to.x, to.y, to.z);
+            match to.delta(&from) {
+                Some(_) if !position_changed => self.send_to_trackers(
+                    entity_id,
+                    &EntityLook {
+                        entity_id,
+                        yaw: to.yaw,
+                        pitch: to.pitch,
+                        on_ground,
+                    },
+                ),
+                Some((delta_x, delta_y, delta_z)) if looked => self.send_to_trackers(
+                    entity_id,
+                    &EntityLookAndRelativeMove {
+                        entity_id,
+                        delta_x,
+                        delta_y,
+                        delta_z,
+                        yaw: to.yaw,
+                        pitch: to.pitch,
+                        on_ground,
+                    },
+                ),
+                Some((delta_x, delta_y, delta_z)) => self.send_to_trackers(
+                    entity_id,
+                    &EntityRelativeMove {
+                        entity_id,
+                        delta_x,
+                        delta_y,
+                        delta_z,
+                        on_ground,
+                    },
+                ),
+                None => self.send_to_trackers(
+                    entity_id,
+                    &EntityTeleport {
+                        entity_id,
+                        x: to.x,
+                        y: to.y,
+                        z: to.z,
+                        yaw: to.yaw,
+                        pitch: to.pitch,
+                        on_ground,
+                    },
+                ),
+            }
+            if looked {
+                self.send_to_trackers(
+                    entity_id,
+                    &EntityHeadLook {
+                        entity_id,
+                        head_yaw: to.yaw,
+                    },
+                );
+            }
+        }
+    }
+    /// Spawns players for each other once they come within range, and removes them again once
+    /// they leave it.
+    fn update_tracking(&mut self) {
+        let mut spawn = vec![];
+        let mut destroy: HashMap<usize, Vec<u32>> = HashMap::new();
+        for (viewer_index, viewer) in &self.players {
+            for (target_index, target) in &self.players {
+                if viewer_index == target_index {
+                    continue;
+                }
+                let in_range = viewer.location.distance_squared(&target.location)
+                    <= TRACKING_RANGE * TRACKING_RANGE;
+                let tracked = viewer.tracking.contains(&target.entity_id);
+                if in_range && !tracked {
+                    spawn.push((*viewer_index, *target_index));
+                } else if !in_range && tracked {
+                    destroy
+                        .entry(*viewer_index)
+                        .or_default()
+                        .push(target.entity_id);
+                }
+            }
+        }
+        let mut spawn_entities = vec![];
+        for (viewer_index, viewer) in &self.players {
+            for entity in self.entities.values() {
+                let in_range = viewer.location.distance_squared(&entity.location)
+                    <= TRACKING_RANGE * TRACKING_RANGE;
+                let tracked = viewer.tracking.contains(&entity.entity_id);
+                if in_range && !tracked {
+                    spawn_entities.push((*viewer_index, entity.entity_id));
+                } else if !in_range && tracked {
+                    destroy
+                        .entry(*viewer_index)
+                        .or_default()
+                        .push(entity.entity_id);
+                }
+            }
+        }
+        for (viewer, entity_id) in spawn_entities {
+            let entity = &self.entities[&entity_id];
+            let (kind, data) = entity.kind.object_type();
+            self.send(
+                viewer,
+                &SpawnObject {
+                    entity_id,
+                    uuid: entity.uuid,
+                    kind,
+                    x: entity.location.x,
+                    y: entity.location.y,
+                    z: entity.location.z,
+                    pitch: entity.location.pitch,
+                    yaw: entity.location.yaw,
+                    data,
+                    velocity: entity.velocity,
+                },
+            );
+            self.send(viewer, &metadata(entity));
+            self.players
+                .get_mut(&viewer)
+                .unwrap()
+                .tracking
+                .insert(entity_id);
+        }
+        for (viewer, target) in spawn {
+            let target = &self.players[&target];
+            let entity_id = target.entity_id;
+            self.send(
+                viewer,
+                &SpawnPlayer {
+                    entity_id,
+                    uuid: target.uuid,
+                    x: target.location.x,
+                    y: target.location.y,
+                    z: target.location.z,
+                    yaw: target.location.yaw,
+                    pitch: target.location.pitch,
+                },
+            );
+            self.send(
+                viewer,
+                &EntityHeadLook {
+                    entity_id,
+                    head_yaw: target.location.yaw,
+                },
+            );
+            self.players
+                .get_mut(&viewer)
+                .unwrap()
+                .tracking
+                .insert(entity_id);
+        }
+        for (viewer, ids) in destroy {
+            let player = self.players.get_mut(&viewer).unwrap();
+            for id in &ids {
+                player.tracking.remove(id);
+            }
+            self.send(viewer, &DestroyEntities(ids));
+        }
+    }
+    /// Sends a packet to every player that has joined.
+    pub fn broadcast<P: Packet<TcpStream>>(&self, packet: &P) {
+        for (index, (_, stream)) in self.clients.lock().unwrap().iter() {
+            if self.players.contains_key(index) {
+                packet.write(&mut *stream.borrow_mut());
+            }
+        }
+    }
+    /// Changes a player's gamemode, and shows the change in everyone's player list.
+    pub fn set_gamemode(&mut self, index: usize, gamemode: Gamemode) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        player.gamemode = gamemode;
+        let uuid = player.uuid;
+        self.send(
+            index,
+            &ChangeGameState {
+                reason: GameStateReason::ChangeGamemode,
+                value: gamemode as u8 as f32,
+            },
+        );
+        self.broadcast(&PlayerListItem(vec![(
+            uuid,
+            PlayerListAction::UpdateGamemode(gamemode as u32),
+        )]));
+    }
+    /// Sets the json text component shown for a player in the player list, `None` to show
+    /// their name again.
+    pub fn set_display_name(&mut self, index: usize, display_name: Option<String>) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        player.display_name = display_name.clone();
+        let uuid = player.uuid;
+        self.broadcast(&PlayerListItem(vec![(
+            uuid,
+            PlayerListAction::UpdateDisplayName(display_name),
+        )]));
+    }
+    /// Sets the json text components shown above and below the player list, for everyone
+    /// online and everyone joining later.
+    pub fn set_player_list_text(&mut self, header: String, footer: String) {
+        let text = PlayerListHeaderAndFooter { header, footer };
+        self.broadcast(&text);
+        self.player_list_text = Some(text);
+    }
+    /// Tells a player what is really at a position, undoing whatever their client predicted.
+    fn resync_block(&self, index: usize, location: Position) {
+        let state = self
+            .world
+            .lock()
+            .unwrap()
+            .get_block(location.x, location.y, location.z);
+        self.send(
+            index,
+            &BlockChange {
+                location,
+                block_id: state as u32,
+            },
+        );
+    }
+    pub fn set_block(&mut self, location: Position, state: block::BlockState) {
+        let mut world = self.world.lock().unwrap();
+        let old = world.get_block(location.x, location.y, location.z);
+        // A chest that goes away spills what was in it.
+        let spilled = match world.block_entity(location) {
+            Some(BlockEntity::Chest(chest)) if block::id(old) != block::id(state) => {
+                Some(chest.items.clone())
+            }
+            _ => None,
+        };
+        // Sent to everyone at the end of the tick, with whatever the neighbours do about it.
+        world.update_block(location, state, true);
+        drop(world);
+        if let Some(items) = spilled {
+            let viewers: Vec<(usize, u8)> = self
+                .players
+                .iter()
+                .filter_map(|(index, p)| match p.open_window {
+                    Some((id, open)) if open == location => Some((*index, id)),
+                    _ => None,
+                })
+                .collect();
+            for (index, window_id) in viewers {
+                self.send(index, &ClientBoundCloseWindow(window_id));
+                self.close_chest(index);
+            }
+            for stack in items.into_iter().filter(|s| !s.is_empty()) {
+                self.scatter_item(location, stack);
+            }
+        }
+    }
+    fn handle_digging(&mut self, index: usize, digging: PlayerDigging) {
+        let current_tick = self.current_tick;
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        if let DiggingStatus::DropItem | DiggingStatus::DropItemStack = digging.status {
+            let held = player.inventory.held_item_mut();
+            let count = match digging.status {
+                DiggingStatus::DropItemStack => held.count,
+                _ => 1,
+            };
+            let stack = held.split(count);
+            if stack.is_empty() {
+                return;
+            }
+            let slot = HOTBAR.start + player.inventory.selected;
+            let slot_data = player.inventory.slots[slot].clone();
+            self.send(
+                index,
+                &SetSlot {
+                    window_id: PLAYER_WINDOW as i8,
+                    slot: slot as i16,
+                    slot_data,
+                },
+            );
+            self.throw_item(index, stack);
+            return;
+        }
+        let creative = player.gamemode == Gamemode::Creative;
+        let location = digging.location;
+        if digging.status != DiggingStatus::Cancelled && !player.can_reach(location) {
+            warn!("{} can't reach {:?}.", player.name, location);
+            player.digging = None;
+            self.resync_block(index, location);
+            return;
+        }
+        let tool = player.held_tool();
+        let state = self
+            .world
+            .lock()
+            .unwrap()
+            .get_block(location.x, location.y, location.z);
+        let block = block::get(state);
+
+        let broken = match (digging.status, player.gamemode) {
+            (DiggingStatus::Cancelled, _) => {
+                player.digging = None;
+                return;
+            }
+            (DiggingStatus::Started, Gamemode::Creative) => true,
+            (DiggingStatus::Started, Gamemode::Survival) => {
+                player.digging = Some((location, current_tick));
+                if block.break_ticks(tool) != Some(0) {
+                    return;
+                }
+                true
+            }
+            (DiggingStatus::Finished, Gamemode::Survival) => match player.digging.take() {
+                Some((started_at, start)) if started_at == location => {
+                    let elapsed = (current_tick - start + 1) as f32;
+                    match block.break_ticks(tool) {
+                        Some(ticks) => elapsed >= ticks as f32 * BREAK_LENIENCY,
+                        None => false,
+                    }
+                }
+                _ => false,
+            },
+            (DiggingStatus::Started | DiggingStatus::Finished, _) => false,
+            _ => return,
+        };
+
+        if !broken {
+            warn!(
+                "{} can't break {} at {:?} yet.",
+                player.name, block.name, location
+            );
+            self.resync_block(index, location);
+        } else if state != block::AIR {
+            let mut event = BlockBreak {
+                player: index,
+                position: location,
+                state,
+                cancelled: false,
+            };
+            self.fire(&mut event);
+            if event.cancelled {
+                self.resync_block(index, location);
+                return;
+            }
+            let Some(player) = self.players.get(&index) else {
+                return;
+            };
+            debug!("{} broke {} at {:?}.", player.name, block.name, location);
+            self.set_block(location, block::AIR);
+            // Blocks that need a tool drop nothing when broken with the wrong one.
+            if !creative && block.can_harvest(tool) {
+                self.drop_block(location, state);
+            }
+        }
+    }
+    /// Drops what a broken block leaves behind, somewhere around the middle of it.
+    fn drop_block(&mut self, location: Position, state: block::BlockState) {
+        let stack = match block::get(state).drops {
+            Drops::Itself => Slot::new(block::id(state) as i16, 1, block::meta(state) as i16),
+            Drops::Item { id, count } => Slot::new(id, count, 0),
+            Drops::Nothing => return,
+        };
+        self.scatter_item(location, stack);
+    }
+    /// Drops a stack somewhere around the middle of a block.
+    fn scatter_item(&mut self, location: Position, stack: Slot) {
+        let mut rng = rand::thread_rng();
+        let mut offset = || rng.gen::<f64>() * 0.5 + 0.25;
+        let position = Location::new(
+            location.x as f64 + offset(),
+            location.y as f64 + offset(),
+            location.z as f64 + offset(),
+        );
+        let velocity = (
+            rng.gen::<f64>() * 0.2 - 0.1,
+            0.2,
+            rng.gen::<f64>() * 0.2 - 0.1,
+        );
+        self.drop_item(position, stack, velocity, BLOCK_DROP_PICKUP_DELAY);
+    }
+    fn handle_placement(&mut self, index: usize, placement: PlayerBlockPlacement) {
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        let clicked = placement.location;
+        if !player.can_reach(clicked) {
+            warn!("{} can't reach {:?}.", player.name, clicked);
+            self.resync_block(index, clicked.offset(placement.face));
+            // The client may already have taken the item out of its hotbar.
+            self.send_inventory(index);
+            return;
+        }
+        let clicked_state = self
+            .world
+            .lock()
+            .unwrap()
+            .get_block(clicked.x, clicked.y, clicked.z);
+        // Chests open instead, unless the player sneaks to place something against them.
+        let sneaking_with_item = player.sneaking && !player.inventory.held_item().is_empty();
+        if block::id(clicked_state) == block::CHEST && !sneaking_with_item {
+            self.open_chest(index, clicked);
+            return;
+        }
+        if !sneaking_with_item
+            && player.gamemode != Gamemode::Spectator
+            && self.world.lock().unwrap().use_block(clicked)
+        {
+            return;
+        }
+        let target = {
+            if block::get(clicked_state).replaceable {
+                clicked
+            } else {
+                clicked.offset(placement.face)
+            }
+        };
+        let placeable = {
+            let world = self.world.lock().unwrap();
+            world.is_loaded(target.x, target.y, target.z)
+                && block::get(world.get_block(target.x, target.y, target.z)).replaceable
+        };
+        // Signs can't hang from the bottom of a block.
+        let is_sign = |state| block::id(state) == block::STANDING_SIGN;
+        match player.held_block() {
+            Some(state) if is_sign(state) && placement.face == 0 => {
+                self.resync_block(index, target)
+            }
+            Some(state) if placeable && player.gamemode != Gamemode::Adventure => {
+                let uuid = player.uuid;
+                let state = block::placed_state(
+                    state,
+                    placement.face,
+                    player.location.yaw,
+                    player.location.pitch,
+                );
+                let mut event = BlockPlace {
+                    player: index,
+                    position: target,
+                    state,
+                    cancelled: false,
+                };
+                self.fire(&mut event);
+                if event.cancelled {
+                    self.resync_block(index, target);
+                    // The client already took the item out of its hotbar.
+                    self.send_inventory(index);
+                    return;
+                }
+                let state = event.state;
+                let Some(player) = self.players.get(&index) else {
+                    return;
+                };
+                debug!(
+                    "{} placed {} at {:?}.",
+                    player.name,
+                    block::get(state).name,
+                    target
+                );
+                if player.gamemode == Gamemode::Survival {
+                    let player = self.players.get_mut(&index).unwrap();
+                    let held = player.inventory.held_item_mut();
+                    if Fluid::of(state).is_some() {
+                        // Buckets are emptied instead, which the client leaves to the server.
+                        *held = Slot::new(BUCKET, 1, 0);
+                        let slot = HOTBAR.start + player.inventory.selected;
+                        self.send(
+                            index,
+                            &SetSlot {
+                                window_id: PLAYER_WINDOW as i8,
+                                slot: slot as i16,
+                                slot_data: Slot::new(BUCKET, 1, 0),
+                            },
+                        );
+                    } else {
+                        // The client takes the item out of its own hotbar, so this needs no
+                        // packet.
+                        held.split(1);
+                    }
+                }
+                self.set_block(target, state);
+                if let Some(BlockEntity::Sign(sign)) = self.world().block_entity_mut(target) {
+                    sign.editor = Some(uuid);
+                    self.send(index, &OpenSignEditor(target));
+                }
+            }
+            _ => self.resync_block(index, target),
+        }
+    }
+    fn handle_update_sign(&mut self, index: usize, update: UpdateSign) {
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        let location = update.location;
+        let mut world = self.world.lock().unwrap();
+        let Some(BlockEntity::Sign(sign)) = world.block_entity_mut(location) else {
+            warn!("{} wrote on a sign that isn't there.", player.name);
+            return;
+        };
+        if sign.editor != Some(player.uuid) {
+            warn!("{} wrote on a sign they can't edit.", player.name);
+            return;
+        }
+        sign.editor = None;
+        // Like chat, without formatting codes.
+        sign.lines = update.lines.map(|line| {
+            let text: String = line
+                .chars()
+                .filter(|c| *c != '§' && !c.is_control())
+                .take(MAX_SIGN_LINE)
+                .collect();
+            serde_json::json!({ "text": text }).to_string()
+        });
+        let data = world.block_entity(location).unwrap().to_nbt(location);
+        drop(world);
+        self.broadcast(&UpdateBlockEntity {
+            location,
+            action: SIGN_ACTION,
+            data,
+        });
+    }
+    /// How many players have the chest at a position open.
+    fn chest_viewers(&self, location: Position) -> usize {
+        self.players
+            .values()
+            .filter(|p| p.open_window.is_some_and(|(_, open)| open == location))
+            .count()
+    }
+    /// Opens or closes the lid of a chest to match how many players are looking inside, which
+    /// used to be `before`.
+    fn update_chest_lid(&self, location: Position, before: usize) {
+        let viewers = self.chest_viewers(location);
+        self.broadcast(&BlockAction {
+            location,
+            action_id: 1,
+            action_param: viewers.min(u8::MAX as usize) as u8,
+            block_type: block::CHEST as u32,
+        });
+        let center = Location::new(
+            location.x as f64 + 0.5,
+            location.y as f64 + 0.5,
+            location.z as f64 + 0.5,
+        );
+        let sound = match (before, viewers) {
+            (0, 1..) => Sound::ChestOpen,
+            (1.., 0) => Sound::ChestClose,
+            _ => return,
+        };
+        let pitch = rand::thread_rng().gen::<f32>() * 0.1 + 0.9;
+        self.play_sound(&center, &sound, SoundCategory::Block, 0.5, pitch);
+    }
+    fn open_chest(&mut self, index: usize, location: Position) {
+        let Some(BlockEntity::Chest(chest)) = self.world().block_entity(location).cloned() else {
+            return;
+        };
+        // Only one window can be open at a time.
+        self.close_chest(index);
+        let viewers = self.chest_viewers(location);
+        let player = self.players.get_mut(&index).unwrap();
+        let window_id = player.next_window_id();
+        player.open_window = Some((window_id, location));
+        debug!("{} opened the chest at {:?}.", player.name, location);
+        let title = chest
+            .custom_name
+            .unwrap_or_else(|| r#"{"translate": "container.chest"}"#.to_string());
+        self.send(
+            index,
+            &OpenWindow {
+                window_id,
+                window_type: "minecraft:chest".to_string(),
+                title,
+                slot_count: CHEST_SIZE as u8,
+            },
+        );
+        self.send_chest(index);
+        self.update_chest_lid(location, viewers);
+    }
+    /// Sends everything in the chest window a player has open, and the stack on the cursor.
+    fn send_chest(&self, index: usize) {
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        let Some((window_id, location)) = player.open_window else {
+            return;
+        };
+        let Some(BlockEntity::Chest(chest)) = self.world().block_entity(location).cloned() else {
+            return;
+        };
+        let mut slots = chest.items;
+        slots.extend_from_slice(player.inventory.container_slots());
+        self.send(index, &WindowItems { window_id, slots });
+        self.send(
+            index,
+            &SetSlot {
+                window_id: -1,
+                slot: -1,
+                slot_data: player.inventory.cursor.clone(),
+            },
+        );
+    }
+    /// Forgets the chest a player had open, their client has already closed the window.
+    fn close_chest(&mut self, index: usize) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        let Some((_, location)) = player.open_window.take() else {
+            return;
+        };
+        for stack in player.inventory.close() {
+            self.throw_item(index, stack);
+        }
+        let viewers = self.chest_viewers(location);
+        self.update_chest_lid(location, viewers + 1);
+    }
+    /// Sends the whole player inventory, and the stack on the cursor.
+    fn send_inventory(&self, index: usize) {
+        let Some(player) = self.players.get(&index) else {
+            return;
+        };
+        self.send(
+            index,
+            &WindowItems {
+                window_id: PLAYER_WINDOW,
+                slots: player.inventory.slots.clone(),
+            },
+        );
+        self.send(
+            index,
+            &SetSlot {
+                window_id: -1,
+                slot: -1,
+                slot_data: player.inventory.cursor.clone(),
+            },
+        );
+    }
+    fn handle_click(&mut self, index: usize, click: ClickWindow) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        let chest = match player.open_window {
+            _ if click.window_id == PLAYER_WINDOW => None,
+            Some((id, location)) if id == click.window_id => Some(location),
+            _ => {
+                warn!(
+                    "{} clicked in unknown window {}.",
+                    player.name, click.window_id
+                );
+                return;
+            }
+        };
+        // Vanilla ignores clicks until the client has acknowledged the last rejection.
+        if player.inventory.awaiting_apology == Some(click.window_id) {
+            return;
+        }
+        let creative = player.gamemode == Gamemode::Creative;
+        let mut changed = vec![];
+        let outcome = match chest {
+            None => player.inventory.click(&click, creative),
+            Some(location) => {
+                let mut world = self.world.lock().unwrap();
+                let Some(BlockEntity::Chest(chest)) = world.block_entity_mut(location) else {
+                    return;
+                };
+                let before = chest.items.clone();
+                let outcome = player
+                    .inventory
+                    .click_container(&mut chest.items, &click, creative);
+                changed = (0..CHEST_SIZE)
+                    .filter(|i| chest.items[*i] != before[*i])
+                    .map(|i| (i, chest.items[i].clone()))
+                    .collect();
+                outcome
+            }
+        };
+        for stack in &outcome.dropped {
+            debug!("{} threw away {:?}.", player.name, stack);
+        }
+        if !outcome.accepted {
+            warn!(
+                "Rejected click {} from {}, resending their inventory.",
+                click.action_number, player.name
+            );
+            player.inventory.awaiting_apology = Some(click.window_id);
+        }
+        self.send(
+            index,
+            &ClientBoundConfirmTransaction {
+                window_id: click.window_id as i8,
+                action_number: click.action_number,
+                accepted: outcome.accepted,
+            },
+        );
+        if !outcome.accepted {
+            match chest {
+                None => self.send_inventory(index),
+                Some(_) => self.send_chest(index),
+            }
+        }
+        // Everyone else looking into the chest sees what changed.
+        for (other, player) in self.players.iter().filter(|(i, _)| **i != index) {
+            let Some((window_id, open)) = player.open_window else {
+                continue;
+            };
+            if Some(open) != chest {
+                continue;
+            }
+            for (slot, slot_data) in &changed {
+                send_to(
+                    &self.clients,
+                    *other,
+                    &SetSlot {
+                        window_id: window_id as i8,
+                        slot: *slot as i16,
+                        slot_data: slot_data.clone(),
+                    },
+                );
+            }
+        }
+        for stack in outcome.dropped {
+            self.throw_item(index, stack);
+        }
+    }
+    fn handle_creative_action(&mut self, index: usize, action: CreativeInventoryAction) {
+        let Some(player) = self.players.get_mut(&index) else {
+            return;
+        };
+        if player.gamemode != Gamemode::Creative {
+            return;
+        }
+        let item = action.clicked_item;
+        let valid = item.is_empty() || (1..=64).contains(&item.count) && item.damage >= 0;
+        if action.slot == -1 {
+            debug!("{} threw away {:?}.", player.name, item);
+        } else if valid && (1..PLAYER_INVENTORY_SIZE as i16).contains(&action.slot) {
+            player.inventory.slots[action.slot as usize] = item;
+        } else {
+            let slot_data = player
+                .inventory
+                .slots
+                .get(action.slot.max(0) as usize)
+                .cloned()
+                .unwrap_or_default();
+            self.send(
+                index,
+                &SetSlot {
+                    window_id: PLAYER_WINDOW as i8,
+                    slot: action.slot,
+                    slot_data,
+                },
+            );
+        }
+    }
+}
+
+fn send_to<P: Packet<TcpStream>>(clients: &Clients, index: usize, packet: &P) {
+    if let Some((_, stream)) = clients.lock().unwrap().get(&index) {
+        packet.write(&mut *stream.borrow_mut());
+    }
+}
+fn list_entry(player: &Player) -> PlayerListAction {
+    PlayerListAction::AddPlayer {
+        name: player.name.clone(),
+        gamemode: player.gamemode as u32,
+        ping: player.ping,
+        display_name: player.display_name.clone(),
+    }
+}
+/// The metadata that makes an entity look like what it is, sent when it spawns or changes.
+fn metadata(entity: &Entity) -> EntityMetadata {
+    let EntityKind::Item { stack, .. } = &entity.kind;
+    EntityMetadata {
+        entity_id: entity.entity_id,
+        entries: vec![(6, MetadataValue::Slot(stack.clone()))],
+    }
+}
+/// Vanilla's `ChatAllowedCharacters`: no control characters and no formatting codes.
+fn allowed_in_chat(c: char) -> bool {
+    c != '§' && c >= ' ' && c != '\u{7f}'
+}
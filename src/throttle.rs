@@ -0,0 +1,192 @@
+//! Limits on incoming connections, checked by the listener before a connection gets a thread.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::permissions::{load_json, save_json};
+
+pub const LIMITS_FILE: &str = "connection_limits.json";
+
+/// Durations are whole milliseconds in the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionLimits {
+    /// Minimum time between two connections from the same address. Loopback connections are
+    /// never throttled.
+    #[serde(rename = "throttle_ms", with = "millis")]
+    pub throttle: Duration,
+    /// How many connections may be between accepting and LoginSuccess at once.
+    pub max_pre_login: usize,
+    /// How long a connection has from being accepted to logging in or finishing a status
+    /// ping, however the packets trickle in.
+    #[serde(rename = "handshake_timeout_ms", with = "millis")]
+    pub handshake_timeout: Duration,
+    /// How many connections one address may have open at once.
+    pub max_sessions_per_ip: usize,
+}
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            throttle: Duration::from_secs(4),
+            max_pre_login: 32,
+            handshake_timeout: Duration::from_secs(5),
+            max_sessions_per_ip: 8,
+        }
+    }
+}
+
+impl ConnectionLimits {
+    /// Reads the limits from `LIMITS_FILE`, writing out the defaults first if it's missing.
+    pub fn load() -> Self {
+        let path = Path::new(LIMITS_FILE);
+        if !path.exists() {
+            save_json(path, &Self::default());
+        }
+        load_json(path)
+    }
+}
+
+mod millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(duration.as_millis() as u64)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        u64::deserialize(d).map(Duration::from_millis)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Throttled,
+    TooManyPreLogin,
+    TooManySessions,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    last_attempt: HashMap<IpAddr, Instant>,
+    pre_login: usize,
+    sessions: HashMap<IpAddr, usize>,
+    players: usize,
+}
+
+/// Shared between the listener and the connection threads, which give their slots back by
+/// dropping their `Admission`.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    pub limits: ConnectionLimits,
+    counts: Arc<Mutex<Counts>>,
+}
+impl Throttle {
+    pub fn new(limits: ConnectionLimits) -> Self {
+        Self {
+            limits,
+            counts: Arc::default(),
+        }
+    }
+    pub fn admit(&self, ip: IpAddr) -> Result<Admission, Rejection> {
+        let mut counts = self.counts.lock().unwrap();
+        let now = Instant::now();
+        let last = counts.last_attempt.insert(ip, now);
+        if !ip.is_loopback() && last.is_some_and(|l| now - l < self.limits.throttle) {
+            return Err(Rejection::Throttled);
+        }
+        // Forget addresses that can't be throttled anymore, so the map doesn't keep growing.
+        let throttle = self.limits.throttle;
+        counts.last_attempt.retain(|_, l| now - *l < throttle);
+        if counts.pre_login >= self.limits.max_pre_login {
+            return Err(Rejection::TooManyPreLogin);
+        }
+        let sessions = counts.sessions.entry(ip).or_default();
+        if *sessions >= self.limits.max_sessions_per_ip {
+            return Err(Rejection::TooManySessions);
+        }
+        *sessions += 1;
+        counts.pre_login += 1;
+        Ok(Admission {
+            ip,
+            pre_login: true,
+            player: false,
+            counts: self.counts.clone(),
+        })
+    }
+}
+
+/// Holds a connection's slots for as long as it is open.
+#[derive(Debug)]
+pub struct Admission {
+    ip: IpAddr,
+    pre_login: bool,
+    player: bool,
+    counts: Arc<Mutex<Counts>>,
+}
+impl Admission {
+    /// Frees the pre-login slot once the player is in.
+    pub fn logged_in(&mut self) {
+        if self.pre_login {
+            self.pre_login = false;
+            self.counts.lock().unwrap().pre_login -= 1;
+        }
+    }
+    /// Takes one of `max` player slots, or one more than that if the player may `bypass` the
+    /// limit. Returns false if the server is full.
+    pub fn join(&mut self, max: usize, bypass: bool) -> bool {
+        let mut counts = self.counts.lock().unwrap();
+        if counts.players >= max && !bypass {
+            return false;
+        }
+        counts.players += 1;
+        self.player = true;
+        true
+    }
+}
+impl Drop for Admission {
+    fn drop(&mut self) {
+        self.logged_in();
+        let mut counts = self.counts.lock().unwrap();
+        if self.player {
+            counts.players -= 1;
+        }
+        if let Some(sessions) = counts.sessions.get_mut(&self.ip) {
+            *sessions -= 1;
+            if *sessions == 0 {
+                counts.sessions.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// A connection that has to be read by a deadline. The timeout is set to the time left
+/// before every read, so a client sending a byte at a time can't stretch it.
+pub struct Deadline<'a> {
+    pub stream: &'a mut TcpStream,
+    pub deadline: Instant,
+}
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+impl Write for Deadline<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
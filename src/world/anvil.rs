@@ -0,0 +1,342 @@
+//! Chunks kept in vanilla's Anvil format: region files of 32 by 32 chunks each, named
+//! `region/r.<x>.<z>.mca` after the region's coordinates.
+//!
+//! A region file starts with a table of where each chunk is, in 4 KiB sectors, followed by a
+//! table of when each was saved. Every chunk is a zlib compressed NBT compound.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::packet::Position;
+use crate::packets::play::{ChunkColumn, ChunkSection};
+
+use super::block;
+use super::block_entity::BlockEntity;
+use super::LightKind;
+
+pub const REGION_DIR: &str = "region";
+/// The data version of 1.12.2.
+const DATA_VERSION: i32 = 1343;
+const SECTOR_SIZE: u64 = 4096;
+const ZLIB: u8 = 2;
+const GZIP: u8 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Section {
+    #[serde(rename = "Y")]
+    y: i8,
+    /// Low 8 bits of each block id, in `y << 8 | z << 4 | x` order.
+    #[serde(rename = "Blocks", serialize_with = "nbt::i8_array")]
+    blocks: Vec<i8>,
+    /// The metas, two to a byte.
+    #[serde(rename = "Data", serialize_with = "nbt::i8_array")]
+    data: Vec<i8>,
+    #[serde(rename = "BlockLight", serialize_with = "nbt::i8_array")]
+    block_light: Vec<i8>,
+    #[serde(rename = "SkyLight", serialize_with = "nbt::i8_array")]
+    sky_light: Vec<i8>,
+}
+#[derive(Serialize, Deserialize)]
+struct Level {
+    #[serde(rename = "xPos")]
+    x: i32,
+    #[serde(rename = "zPos")]
+    z: i32,
+    #[serde(rename = "LastUpdate", default)]
+    last_update: i64,
+    #[serde(rename = "LightPopulated", default)]
+    light_populated: bool,
+    #[serde(rename = "TerrainPopulated", default)]
+    terrain_populated: bool,
+    #[serde(rename = "InhabitedTime", default)]
+    inhabited_time: i64,
+    #[serde(rename = "Biomes", default, serialize_with = "nbt::i8_array")]
+    biomes: Vec<i8>,
+    #[serde(rename = "HeightMap", default, serialize_with = "nbt::i32_array")]
+    height_map: Vec<i32>,
+    #[serde(rename = "Sections", default)]
+    sections: Vec<Section>,
+    /// Entities aren't saved, but vanilla expects the list.
+    #[serde(rename = "Entities", default)]
+    entities: Vec<nbt::Blob>,
+    #[serde(rename = "TileEntities", default)]
+    tile_entities: Vec<nbt::Blob>,
+    #[serde(rename = "TileTicks", default, skip_serializing_if = "Vec::is_empty")]
+    tile_ticks: Vec<TileTick>,
+}
+/// A scheduled block update.
+#[derive(Serialize, Deserialize)]
+struct TileTick {
+    /// The block's name.
+    i: String,
+    /// Ticks until it is due.
+    t: i32,
+    /// Vanilla's tie breaker between updates due in the same tick, always 0 here.
+    p: i32,
+    x: i32,
+    y: i32,
+    z: i32,
+}
+#[derive(Serialize, Deserialize)]
+struct Chunk {
+    #[serde(rename = "DataVersion", default)]
+    data_version: i32,
+    #[serde(rename = "Level")]
+    level: Level,
+}
+
+fn region_path(cx: i32, cz: i32) -> PathBuf {
+    PathBuf::from(REGION_DIR).join(format!("r.{}.{}.mca", cx >> 5, cz >> 5))
+}
+/// Where the chunk's entry is in the location table, and 4 KiB later in the timestamp table.
+fn table_offset(cx: i32, cz: i32) -> u64 {
+    4 * ((cx & 31) + (cz & 31) * 32) as u64
+}
+
+fn get_nibble(nibbles: &[i8], i: usize) -> u8 {
+    (nibbles[i >> 1] as u8 >> ((i & 1) * 4)) & 0xf
+}
+fn nibbles(values: impl Iterator<Item = u8>) -> Vec<i8> {
+    let values: Vec<u8> = values.collect();
+    values
+        .chunks(2)
+        .map(|pair| (pair[0] | pair[1] << 4) as i8)
+        .collect()
+}
+/// Where in a section each index is, the order the arrays are in.
+fn coordinates(i: usize) -> (usize, usize, usize) {
+    (i & 0xf, i >> 8, (i >> 4) & 0xf)
+}
+
+/// A scheduled update of a block with the given id, and the ticks until it is due.
+pub type PendingTick = (Position, u16, i64);
+
+fn to_column(level: Level) -> ChunkColumn {
+    let mut column = ChunkColumn::new();
+    for stored in &level.sections {
+        if !(0..16).contains(&stored.y) || stored.blocks.len() != 4096 {
+            continue;
+        }
+        let mut section = ChunkSection::empty(Some(&[0; 256]));
+        for i in 0..4096 {
+            let (x, y, z) = coordinates(i);
+            let id = stored.blocks[i] as u8 as u16;
+            let meta = get_nibble(&stored.data, i);
+            if id != 0 {
+                section.set_block(x, y, z, id << 4 | meta as u16);
+            }
+            section.set_light(
+                LightKind::Block,
+                x,
+                y,
+                z,
+                get_nibble(&stored.block_light, i),
+            );
+            section.set_light(LightKind::Sky, x, y, z, get_nibble(&stored.sky_light, i));
+        }
+        column.sections[stored.y as usize] = Some(section);
+    }
+    if level.biomes.len() == 256 {
+        column.biomes = Some(level.biomes.iter().map(|b| *b as u8).collect());
+    }
+    if level.height_map.len() == 256 {
+        column.heightmap = level.height_map.iter().map(|h| *h as u16).collect();
+    } else {
+        for x in 0..16 {
+            for z in 0..16 {
+                column.update_height(x, z);
+            }
+        }
+    }
+    for blob in &level.tile_entities {
+        if let Some((position, block_entity)) = BlockEntity::from_nbt(blob) {
+            column.block_entities.insert(position, block_entity);
+        }
+    }
+    column.dirty = false;
+    column
+}
+fn to_level(cx: i32, cz: i32, column: &ChunkColumn, ticks: &[PendingTick]) -> Level {
+    let sections = column
+        .sections
+        .iter()
+        .enumerate()
+        .filter_map(|(y, section)| Some((y, section.as_ref()?)))
+        .map(|(y, section)| {
+            let states: Vec<u16> = (0..4096)
+                .map(|i| {
+                    let (x, y, z) = coordinates(i);
+                    section.get_block(x, y, z)
+                })
+                .collect();
+            let light = |kind| {
+                nibbles((0..4096).map(|i| {
+                    let (x, y, z) = coordinates(i);
+                    section.get_light(kind, x, y, z)
+                }))
+            };
+            Section {
+                y: y as i8,
+                blocks: states.iter().map(|s| (s >> 4) as u8 as i8).collect(),
+                data: nibbles(states.iter().map(|s| (s & 0xf) as u8)),
+                block_light: light(LightKind::Block),
+                sky_light: light(LightKind::Sky),
+            }
+        })
+        .collect();
+    Level {
+        x: cx,
+        z: cz,
+        last_update: 0,
+        light_populated: true,
+        terrain_populated: true,
+        inhabited_time: 0,
+        biomes: column
+            .biomes
+            .clone()
+            .unwrap_or_else(|| vec![1; 256])
+            .into_iter()
+            .map(|b| b as i8)
+            .collect(),
+        height_map: column.heightmap.iter().map(|h| *h as i32).collect(),
+        sections,
+        entities: vec![],
+        tile_entities: column
+            .block_entities
+            .iter()
+            .map(|(position, block_entity)| block_entity.to_nbt(*position))
+            .collect(),
+        tile_ticks: ticks
+            .iter()
+            .map(|(position, id, delay)| TileTick {
+                i: block::get(block::state(*id, 0)).name.to_string(),
+                t: *delay as i32,
+                p: 0,
+                x: position.x,
+                y: position.y,
+                z: position.z,
+            })
+            .collect(),
+    }
+}
+
+/// The chunk from its region file with the block updates that were pending in it, `None` if
+/// it was never saved.
+pub fn load_chunk(cx: i32, cz: i32) -> Option<(ChunkColumn, Vec<PendingTick>)> {
+    let path = region_path(cx, cz);
+    let mut file = File::open(&path).ok()?;
+    let result = (|| -> Result<Option<Chunk>, nbt::Error> {
+        file.seek(SeekFrom::Start(table_offset(cx, cz)))?;
+        let location = file.read_u32::<BigEndian>()?;
+        if location >> 8 == 0 {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start((location >> 8) as u64 * SECTOR_SIZE))?;
+        let length = file.read_u32::<BigEndian>()?;
+        // The length counts the compression byte but not itself, and a chunk can't be longer
+        // than the sectors the table gives it.
+        let sectors = (location & 0xff) as u64;
+        if length == 0 || length as u64 + 4 > sectors * SECTOR_SIZE {
+            return Err(nbt::Error::from(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("length {} doesn't fit in {} sectors", length, sectors),
+            )));
+        }
+        let compression = file.read_u8()?;
+        let mut data = vec![0; length as usize - 1];
+        file.read_exact(&mut data)?;
+        match compression {
+            ZLIB => nbt::from_zlib_reader(&data[..]).map(Some),
+            GZIP => nbt::from_gzip_reader(&data[..]).map(Some),
+            c => Err(nbt::Error::from(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown compression {}", c),
+            ))),
+        }
+    })();
+    match result {
+        Ok(chunk) => chunk.map(|chunk| {
+            let ticks = chunk
+                .level
+                .tile_ticks
+                .iter()
+                .filter_map(|tick| {
+                    let id = block::by_name(&tick.i)?.id;
+                    Some((Position::new(tick.x, tick.y, tick.z), id, tick.t as i64))
+                })
+                .collect();
+            (to_column(chunk.level), ticks)
+        }),
+        Err(e) => {
+            error!(
+                "Couldn't read chunk {}, {} from {}: {}",
+                cx,
+                cz,
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Writes a chunk and the block updates pending in it into its region file, over its old
+/// copy if the new one fits in the same sectors and at the end of the file otherwise.
+pub fn save_chunk(
+    cx: i32,
+    cz: i32,
+    column: &ChunkColumn,
+    ticks: &[PendingTick],
+) -> Result<(), nbt::Error> {
+    let chunk = Chunk {
+        data_version: DATA_VERSION,
+        level: to_level(cx, cz, column, ticks),
+    };
+    let mut data = vec![];
+    nbt::to_zlib_writer(&mut data, &chunk, None)?;
+    let sectors = (data.len() as u64 + 5).div_ceil(SECTOR_SIZE);
+    if sectors > 255 {
+        return Err(nbt::Error::from(io::Error::other("chunk too big")));
+    }
+
+    fs::create_dir_all(REGION_DIR)?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(region_path(cx, cz))?;
+    if file.metadata()?.len() < 2 * SECTOR_SIZE {
+        file.write_all(&[0; 2 * SECTOR_SIZE as usize])?;
+    }
+    file.seek(SeekFrom::Start(table_offset(cx, cz)))?;
+    let location = file.read_u32::<BigEndian>()?;
+    let (old_offset, old_sectors) = ((location >> 8) as u64, (location & 0xff) as u64);
+    let offset = if old_offset != 0 && sectors <= old_sectors {
+        old_offset
+    } else {
+        file.metadata()?.len().div_ceil(SECTOR_SIZE)
+    };
+
+    file.seek(SeekFrom::Start(offset * SECTOR_SIZE))?;
+    file.write_u32::<BigEndian>(data.len() as u32 + 1)?;
+    file.write_u8(ZLIB)?;
+    file.write_all(&data)?;
+    let padding = sectors * SECTOR_SIZE - data.len() as u64 - 5;
+    file.write_all(&vec![0; padding as usize])?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as u32);
+    file.seek(SeekFrom::Start(table_offset(cx, cz)))?;
+    file.write_u32::<BigEndian>((offset as u32) << 8 | sectors as u32)?;
+    file.seek(SeekFrom::Start(SECTOR_SIZE + table_offset(cx, cz)))?;
+    file.write_u32::<BigEndian>(now)?;
+    Ok(())
+}
@@ -0,0 +1,416 @@
+/// A 1.12 block state, `id << 4 | meta`.
+pub type BlockState = u16;
+
+pub const AIR: BlockState = 0;
+pub const STONE: BlockState = 1 << 4;
+pub const GRASS: BlockState = 2 << 4;
+pub const DIRT: BlockState = 3 << 4;
+pub const COBBLESTONE: BlockState = 4 << 4;
+pub const BEDROCK: BlockState = 7 << 4;
+pub const OBSIDIAN: BlockState = 49 << 4;
+
+#[derive(Debug)]
+pub struct Block {
+    pub id: u16,
+    pub name: &'static str,
+    /// How much light is lost passing through this block, 15 for fully opaque blocks.
+    pub opacity: u8,
+    /// Light level emitted by this block.
+    pub emission: u8,
+    /// Seconds-ish it takes to break, negative for unbreakable blocks.
+    pub hardness: f32,
+    /// The kind of tool that breaks this block faster.
+    pub tool: Option<ToolKind>,
+    /// Breaking this block without its tool is a lot slower, and drops nothing.
+    pub needs_tool: bool,
+    /// How good the tool has to be, 0 for wood or gold through 3 for diamond.
+    pub harvest_level: u8,
+    /// Placing a block here replaces it instead of going next to it.
+    pub replaceable: bool,
+    /// Holds back fluids and can carry torches, signs and the like.
+    pub solid: bool,
+    /// What entities collide with.
+    pub shape: Shape,
+    /// How much of their speed entities sliding on this block keep each tick.
+    pub slipperiness: f64,
+    pub drops: Drops,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolKind {
+    Pickaxe,
+    Shovel,
+    Axe,
+}
+
+/// What an item does as a tool when breaking blocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tool {
+    pub kind: ToolKind,
+    /// The highest harvest level it can break.
+    pub level: u8,
+    /// How many times faster than a hand it is on blocks it is made for.
+    pub speed: f32,
+}
+
+/// What entities collide with. Everything but `Empty` and `Full` is worked out from the meta,
+/// and for fences from the neighbours, by `Block::collision_boxes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Empty,
+    Full,
+    /// A fixed box in sixteenths of a block, the low corner and then the high one.
+    Box([u8; 6]),
+    /// The lower half, or the upper one with meta bit 8.
+    Slab,
+    /// A slab with a step on the side the meta faces. Corners aren't worked out, every stair
+    /// collides like a straight one.
+    Stairs,
+    /// A post joined to the fences, fence gates and full blocks next to it, 1.5 blocks tall so
+    /// nothing jumps over.
+    Fence,
+    /// A 1.5 block tall bar across its facing, or nothing while open.
+    FenceGate,
+}
+
+/// What breaking a block leaves behind, if it was broken with a tool that `can_harvest` it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drops {
+    /// The block's own item, with the meta as its damage.
+    Itself,
+    Item { id: i16, count: i8 },
+    Nothing,
+}
+
+/// Used for every id that isn't in `BLOCKS`, so unknown blocks behave like stone.
+const UNKNOWN: Block = block(u16::MAX, "minecraft:unknown").strength(1.5);
+
+/// An opaque block that breaks instantly, the rest is filled in with the builder methods below.
+const fn block(id: u16, name: &'static str) -> Block {
+    Block {
+        id,
+        name,
+        opacity: 15,
+        emission: 0,
+        hardness: 0.0,
+        tool: None,
+        needs_tool: false,
+        harvest_level: 0,
+        replaceable: false,
+        solid: true,
+        shape: Shape::Full,
+        slipperiness: 0.6,
+        drops: Drops::Itself,
+    }
+}
+impl Block {
+    const fn opacity(self, opacity: u8) -> Self {
+        Self { opacity, ..self }
+    }
+    const fn emits(self, emission: u8) -> Self {
+        Self { emission, ..self }
+    }
+    const fn strength(self, hardness: f32) -> Self {
+        Self { hardness, ..self }
+    }
+    const fn tool(self, tool: ToolKind) -> Self {
+        Self {
+            tool: Some(tool),
+            ..self
+        }
+    }
+    const fn harvest_level(self, harvest_level: u8) -> Self {
+        Self {
+            harvest_level,
+            ..self
+        }
+    }
+    const fn needs_tool(self) -> Self {
+        Self {
+            needs_tool: true,
+            ..self
+        }
+    }
+    const fn replaceable(self) -> Self {
+        Self {
+            replaceable: true,
+            ..self
+        }
+    }
+    const fn slippery(self, slipperiness: f64) -> Self {
+        Self {
+            slipperiness,
+            ..self
+        }
+    }
+    const fn drops(self, id: i16, count: i8) -> Self {
+        Self {
+            drops: Drops::Item { id, count },
+            ..self
+        }
+    }
+    const fn drops_nothing(self) -> Self {
+        Self {
+            drops: Drops::Nothing,
+            ..self
+        }
+    }
+    const fn passable(self) -> Self {
+        Self {
+            solid: false,
+            shape: Shape::Empty,
+            ..self
+        }
+    }
+    const fn shape(self, shape: Shape) -> Self {
+        Self { shape, ..self }
+    }
+}
+
+#[rustfmt::skip]
+pub static BLOCKS: &[Block] = &[
+    block(0, "minecraft:air").opacity(0).replaceable().passable().drops_nothing(),
+    block(1, "minecraft:stone").strength(1.5).tool(ToolKind::Pickaxe).needs_tool().drops(4, 1),
+    block(2, "minecraft:grass").strength(0.6).tool(ToolKind::Shovel).drops(3, 1),
+    block(3, "minecraft:dirt").strength(0.5).tool(ToolKind::Shovel),
+    block(4, "minecraft:cobblestone").strength(2.0).tool(ToolKind::Pickaxe).needs_tool(),
+    block(5, "minecraft:planks").strength(2.0).tool(ToolKind::Axe),
+    block(7, "minecraft:bedrock").strength(-1.0).drops_nothing(),
+    block(8, "minecraft:flowing_water").opacity(3).strength(100.0).replaceable().passable().drops_nothing(),
+    block(9, "minecraft:water").opacity(3).strength(100.0).replaceable().passable().drops_nothing(),
+    block(10, "minecraft:flowing_lava").emits(15).strength(100.0).replaceable().passable().drops_nothing(),
+    block(11, "minecraft:lava").emits(15).strength(100.0).replaceable().passable().drops_nothing(),
+    block(12, "minecraft:sand").strength(0.5).tool(ToolKind::Shovel),
+    block(13, "minecraft:gravel").strength(0.6).tool(ToolKind::Shovel),
+    block(14, "minecraft:gold_ore").strength(3.0).tool(ToolKind::Pickaxe).needs_tool().harvest_level(2),
+    block(15, "minecraft:iron_ore").strength(3.0).tool(ToolKind::Pickaxe).needs_tool().harvest_level(1),
+    block(16, "minecraft:coal_ore").strength(3.0).tool(ToolKind::Pickaxe).needs_tool().drops(263, 1),
+    block(17, "minecraft:log").strength(2.0).tool(ToolKind::Axe),
+    block(18, "minecraft:leaves").opacity(1).strength(0.2).drops_nothing(),
+    block(20, "minecraft:glass").opacity(0).strength(0.3).drops_nothing(),
+    block(24, "minecraft:sandstone").strength(0.8).tool(ToolKind::Pickaxe).needs_tool(),
+    block(29, "minecraft:sticky_piston").opacity(0).strength(0.5).drops(29, 1),
+    block(31, "minecraft:tallgrass").opacity(0).replaceable().passable().drops_nothing(),
+    block(33, "minecraft:piston").opacity(0).strength(0.5).drops(33, 1),
+    block(34, "minecraft:piston_head").opacity(0).strength(0.5).drops_nothing(),
+    block(35, "minecraft:wool").strength(0.8),
+    block(37, "minecraft:yellow_flower").opacity(0).passable(),
+    block(38, "minecraft:red_flower").opacity(0).passable(),
+    block(41, "minecraft:gold_block").strength(3.0).tool(ToolKind::Pickaxe).needs_tool().harvest_level(2),
+    block(42, "minecraft:iron_block").strength(5.0).tool(ToolKind::Pickaxe).needs_tool().harvest_level(1),
+    block(43, "minecraft:double_stone_slab").strength(2.0).tool(ToolKind::Pickaxe).needs_tool().drops(44, 2),
+    block(44, "minecraft:stone_slab").strength(2.0).tool(ToolKind::Pickaxe).needs_tool().shape(Shape::Slab).drops(44, 1),
+    block(45, "minecraft:brick_block").strength(2.0).tool(ToolKind::Pickaxe).needs_tool(),
+    block(49, "minecraft:obsidian").strength(50.0).tool(ToolKind::Pickaxe).needs_tool().harvest_level(3),
+    block(50, "minecraft:torch").opacity(0).emits(14).passable(),
+    block(51, "minecraft:fire").opacity(0).emits(15).replaceable().passable().drops_nothing(),
+    block(53, "minecraft:oak_stairs").strength(2.0).tool(ToolKind::Axe).shape(Shape::Stairs).drops(53, 1),
+    block(54, "minecraft:chest").opacity(0).strength(2.5).tool(ToolKind::Axe).shape(Shape::Box([1, 0, 1, 15, 14, 15])).drops(54, 1),
+    block(55, "minecraft:redstone_wire").opacity(0).passable().drops(331, 1),
+    block(63, "minecraft:standing_sign").opacity(0).strength(1.0).tool(ToolKind::Axe).passable().drops(323, 1),
+    block(67, "minecraft:stone_stairs").strength(2.0).tool(ToolKind::Pickaxe).needs_tool().shape(Shape::Stairs).drops(67, 1),
+    block(68, "minecraft:wall_sign").opacity(0).strength(1.0).tool(ToolKind::Axe).passable().drops(323, 1),
+    block(69, "minecraft:lever").opacity(0).strength(0.5).passable().drops(69, 1),
+    block(70, "minecraft:stone_pressure_plate").opacity(0).strength(0.5).tool(ToolKind::Pickaxe).needs_tool().passable().drops(70, 1),
+    block(72, "minecraft:wooden_pressure_plate").opacity(0).strength(0.5).tool(ToolKind::Axe).passable().drops(72, 1),
+    block(75, "minecraft:unlit_redstone_torch").opacity(0).passable().drops(76, 1),
+    block(76, "minecraft:redstone_torch").opacity(0).emits(7).passable().drops(76, 1),
+    block(77, "minecraft:stone_button").opacity(0).strength(0.5).passable().drops(77, 1),
+    block(79, "minecraft:ice").opacity(3).strength(0.5).tool(ToolKind::Pickaxe).slippery(0.98).drops_nothing(),
+    block(85, "minecraft:fence").opacity(0).strength(2.0).tool(ToolKind::Axe).shape(Shape::Fence).drops(85, 1),
+    block(89, "minecraft:glowstone").emits(15).strength(0.3).drops(348, 3),
+    block(91, "minecraft:lit_pumpkin").emits(15).strength(1.0).tool(ToolKind::Axe),
+    block(93, "minecraft:unpowered_repeater").opacity(0).passable().drops(356, 1),
+    block(94, "minecraft:powered_repeater").opacity(0).emits(9).passable().drops(356, 1),
+    block(107, "minecraft:fence_gate").opacity(0).strength(2.0).tool(ToolKind::Axe).shape(Shape::FenceGate).drops(107, 1),
+    block(108, "minecraft:brick_stairs").strength(2.0).tool(ToolKind::Pickaxe).needs_tool().shape(Shape::Stairs).drops(108, 1),
+    block(125, "minecraft:double_wooden_slab").strength(2.0).tool(ToolKind::Axe).drops(126, 2),
+    block(126, "minecraft:wooden_slab").strength(2.0).tool(ToolKind::Axe).shape(Shape::Slab).drops(126, 1),
+    block(143, "minecraft:wooden_button").opacity(0).strength(0.5).passable().drops(143, 1),
+];
+
+pub fn id(state: BlockState) -> u16 {
+    state >> 4
+}
+pub fn meta(state: BlockState) -> u8 {
+    (state & 0xf) as u8
+}
+pub fn state(id: u16, meta: u8) -> BlockState {
+    id << 4 | (meta & 0xf) as u16
+}
+/// Looks up the block for a state, ignoring its meta.
+pub fn get(state: BlockState) -> &'static Block {
+    let id = id(state);
+    BLOCKS.iter().find(|b| b.id == id).unwrap_or(&UNKNOWN)
+}
+/// Looks up a block by name, the `minecraft:` prefix is optional.
+pub fn by_name(name: &str) -> Option<&'static Block> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+    BLOCKS
+        .iter()
+        .find(|b| &b.name["minecraft:".len()..] == name)
+}
+impl Block {
+    /// Whether breaking this block with a tool, or by hand for `None`, drops anything.
+    pub fn can_harvest(&self, tool: Option<Tool>) -> bool {
+        !self.needs_tool
+            || tool.is_some_and(|t| Some(t.kind) == self.tool && t.level >= self.harvest_level)
+    }
+    /// Ticks it takes to break this block with a tool, or by hand for `None`. 0 means it
+    /// breaks instantly, and `None` that it can't be broken at all.
+    pub fn break_ticks(&self, tool: Option<Tool>) -> Option<u32> {
+        if self.hardness < 0.0 {
+            return None;
+        }
+        let speed = match tool {
+            Some(tool) if Some(tool.kind) == self.tool => tool.speed,
+            _ => 1.0,
+        };
+        let per_hardness = if self.can_harvest(tool) { 30.0 } else { 100.0 };
+        let ticks = self.hardness * per_hardness / speed;
+        Some(if ticks <= 1.0 { 0 } else { ticks.ceil() as u32 })
+    }
+    /// The boxes entities collide with, in blocks from the block's low corner. `neighbour`
+    /// gives the state `(dx, dz)` away, which only fences look at.
+    pub fn collision_boxes(
+        &self,
+        meta: u8,
+        neighbour: impl Fn(i32, i32) -> BlockState,
+    ) -> Vec<[f64; 6]> {
+        let boxes = match self.shape {
+            Shape::Empty => vec![],
+            Shape::Full => vec![[0, 0, 0, 16, 16, 16]],
+            Shape::Box(b) => vec![b],
+            Shape::Slab if meta & 8 != 0 => vec![[0, 8, 0, 16, 16, 16]],
+            Shape::Slab => vec![[0, 0, 0, 16, 8, 16]],
+            Shape::Stairs => {
+                // Upside down stairs have the slab on top and the step below it.
+                let (slab, step) = if meta & 4 != 0 { (8, 0) } else { (0, 8) };
+                let step = match meta & 3 {
+                    0 => [8, step, 0, 16, step + 8, 16],
+                    1 => [0, step, 0, 8, step + 8, 16],
+                    2 => [0, step, 8, 16, step + 8, 16],
+                    _ => [0, step, 0, 16, step + 8, 8],
+                };
+                vec![[0, slab, 0, 16, slab + 8, 16], step]
+            }
+            Shape::Fence => {
+                let arms = [
+                    ((0, -1), [6, 0, 0, 10, 24, 6]),
+                    ((0, 1), [6, 0, 10, 10, 24, 16]),
+                    ((-1, 0), [0, 0, 6, 6, 24, 10]),
+                    ((1, 0), [10, 0, 6, 16, 24, 10]),
+                ];
+                let connected = arms
+                    .into_iter()
+                    .filter(|((dx, dz), _)| fence_connects(neighbour(*dx, *dz)))
+                    .map(|(_, arm)| arm);
+                std::iter::once([6, 0, 6, 10, 24, 10])
+                    .chain(connected)
+                    .collect()
+            }
+            Shape::FenceGate if meta & 4 != 0 => vec![],
+            // Facing south or north, the gate runs along x.
+            Shape::FenceGate if meta & 1 == 0 => vec![[0, 0, 6, 16, 24, 10]],
+            Shape::FenceGate => vec![[6, 0, 0, 10, 24, 16]],
+        };
+        boxes
+            .into_iter()
+            .map(|b| b.map(|v| v as f64 / 16.0))
+            .collect()
+    }
+}
+/// Fences join other fences, fence gates and full opaque blocks.
+fn fence_connects(state: BlockState) -> bool {
+    let block = get(state);
+    match block.shape {
+        Shape::Fence | Shape::FenceGate => true,
+        Shape::Full => block.solid && block.opacity == 15,
+        _ => false,
+    }
+}
+
+pub const FLOWING_WATER: u16 = 8;
+pub const WATER: u16 = 9;
+pub const FLOWING_LAVA: u16 = 10;
+pub const LAVA: u16 = 11;
+pub const TORCH: u16 = 50;
+pub const CHEST: u16 = 54;
+pub const STANDING_SIGN: u16 = 63;
+pub const WALL_SIGN: u16 = 68;
+pub const STICKY_PISTON: u16 = 29;
+pub const PISTON: u16 = 33;
+pub const PISTON_HEAD: u16 = 34;
+pub const REDSTONE_WIRE: u16 = 55;
+pub const LEVER: u16 = 69;
+pub const STONE_PRESSURE_PLATE: u16 = 70;
+pub const WOODEN_PRESSURE_PLATE: u16 = 72;
+pub const UNLIT_REDSTONE_TORCH: u16 = 75;
+pub const REDSTONE_TORCH: u16 = 76;
+pub const STONE_BUTTON: u16 = 77;
+pub const UNPOWERED_REPEATER: u16 = 93;
+pub const POWERED_REPEATER: u16 = 94;
+pub const WOODEN_BUTTON: u16 = 143;
+
+/// The state a block ends up in when placed against the given face of another block, by a
+/// player looking towards `yaw` and `pitch`.
+pub fn placed_state(state: BlockState, face: u8, yaw: f32, pitch: f32) -> BlockState {
+    // 0 is south, then clockwise through west, north and east.
+    let facing = ((yaw * 4.0 / 360.0 + 0.5).floor() as i32 & 3) as usize;
+    // The face pointing back at the player, for blocks that are placed facing them.
+    let towards_player = [2, 5, 3, 4][facing];
+    match id(state) {
+        // Chests open towards the player.
+        CHEST => self::state(CHEST, towards_player),
+        // Pistons push away from the player, up or down if they look steeply enough.
+        PISTON | STICKY_PISTON => {
+            let meta = match pitch {
+                p if p > 55.0 => 1,
+                p if p < -55.0 => 0,
+                _ => towards_player,
+            };
+            self::state(id(state), meta)
+        }
+        // Repeaters point away from the player.
+        UNPOWERED_REPEATER => self::state(UNPOWERED_REPEATER, (facing as u8 + 2) & 3),
+        // Levers point away from the block they are on, and lie along the direction the
+        // player looks when on a floor or ceiling.
+        LEVER => {
+            let along_z = facing.is_multiple_of(2);
+            let meta = match face {
+                0 if along_z => 7,
+                0 => 0,
+                1 if along_z => 5,
+                1 => 6,
+                2 => 4,
+                3 => 3,
+                4 => 2,
+                _ => 1,
+            };
+            self::state(LEVER, meta)
+        }
+        STONE_BUTTON | WOODEN_BUTTON => {
+            let meta = [0, 5, 4, 3, 2, 1][face.min(5) as usize];
+            self::state(id(state), meta)
+        }
+        // Signs stand on top of blocks, turned to the player in sixteen steps, and hang on
+        // the sides of them.
+        STANDING_SIGN if face == 1 => {
+            let rotation = ((yaw + 180.0) * 16.0 / 360.0 + 0.5).floor() as i32 & 15;
+            self::state(STANDING_SIGN, rotation as u8)
+        }
+        STANDING_SIGN => self::state(WALL_SIGN, face),
+        // Torches point away from the block they are attached to.
+        TORCH | REDSTONE_TORCH => {
+            let meta = match face {
+                2 => 4,
+                3 => 3,
+                4 => 2,
+                5 => 1,
+                _ => 5,
+            };
+            self::state(id(state), meta)
+        }
+        _ => state,
+    }
+}
@@ -0,0 +1,185 @@
+//! Data some blocks keep besides their state, like the text on signs and the items in chests.
+//! They are sent to clients and saved in region files in vanilla's NBT layout.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::packet::{Position, Slot};
+use crate::playerdata::{item_id, item_name, InventoryItem};
+
+use super::block::{self, BlockState, CHEST, STANDING_SIGN, WALL_SIGN};
+
+pub const CHEST_SIZE: usize = 27;
+/// Update Block Entity action that sets a sign's text.
+pub const SIGN_ACTION: u8 = 9;
+const EMPTY_LINE: &str = r#"{"text":""}"#;
+
+#[derive(Debug, Clone)]
+pub struct Sign {
+    /// Json text components, one per line.
+    pub lines: [String; 4],
+    /// The player allowed to write on the sign, whoever placed it until they are done.
+    pub editor: Option<u128>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chest {
+    pub items: Vec<Slot>,
+    /// Shown instead of "Chest" when the chest is opened, a json text component.
+    pub custom_name: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum BlockEntity {
+    Sign(Sign),
+    Chest(Chest),
+    /// Anything else found in a region file, kept so it survives a save.
+    Other(nbt::Blob),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    id: String,
+    x: i32,
+    y: i32,
+    z: i32,
+}
+#[derive(Serialize, Deserialize)]
+struct SignNbt {
+    id: String,
+    x: i32,
+    y: i32,
+    z: i32,
+    #[serde(rename = "Text1")]
+    text1: String,
+    #[serde(rename = "Text2")]
+    text2: String,
+    #[serde(rename = "Text3")]
+    text3: String,
+    #[serde(rename = "Text4")]
+    text4: String,
+}
+#[derive(Serialize, Deserialize)]
+struct ChestNbt {
+    id: String,
+    x: i32,
+    y: i32,
+    z: i32,
+    #[serde(rename = "Items", default)]
+    items: Vec<InventoryItem>,
+    #[serde(
+        rename = "CustomName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    custom_name: Option<String>,
+}
+
+/// Goes through the binary form, since deserializing a `Blob` with serde guesses the smallest
+/// type that fits each number instead of keeping the tag types.
+fn to_blob<T: Serialize>(value: &T) -> nbt::Blob {
+    let mut bytes = vec![];
+    nbt::to_writer(&mut bytes, value, None).unwrap();
+    nbt::Blob::from_reader(&mut &bytes[..]).unwrap()
+}
+fn from_blob<T: DeserializeOwned>(blob: &nbt::Blob) -> Option<T> {
+    let mut bytes = vec![];
+    blob.to_writer(&mut bytes).ok()?;
+    nbt::from_reader(&bytes[..]).ok()
+}
+
+impl BlockEntity {
+    /// The empty block entity a freshly placed block starts with, if it has one.
+    pub fn for_block(state: BlockState) -> Option<Self> {
+        match block::id(state) {
+            STANDING_SIGN | WALL_SIGN => Some(Self::Sign(Sign {
+                lines: [(); 4].map(|_| EMPTY_LINE.to_string()),
+                editor: None,
+            })),
+            CHEST => Some(Self::Chest(Chest {
+                items: vec![Slot::empty(); CHEST_SIZE],
+                custom_name: None,
+            })),
+            _ => None,
+        }
+    }
+    /// Reads a block entity from a region file or anything else in vanilla's layout.
+    pub fn from_nbt(blob: &nbt::Blob) -> Option<(Position, Self)> {
+        let header: Header = from_blob(blob)?;
+        let position = Position::new(header.x, header.y, header.z);
+        let block_entity = match header.id.as_str() {
+            "minecraft:sign" | "Sign" => {
+                let sign: SignNbt = from_blob(blob)?;
+                Self::Sign(Sign {
+                    lines: [sign.text1, sign.text2, sign.text3, sign.text4],
+                    editor: None,
+                })
+            }
+            "minecraft:chest" | "Chest" => {
+                let chest: ChestNbt = from_blob(blob)?;
+                let mut items = vec![Slot::empty(); CHEST_SIZE];
+                for item in chest.items {
+                    let (Some(slot), Some(id)) =
+                        (items.get_mut(item.slot as usize), item_id(&item.id))
+                    else {
+                        continue;
+                    };
+                    *slot = Slot {
+                        id,
+                        count: item.count,
+                        damage: item.damage,
+                        nbt: item.tag,
+                    };
+                }
+                Self::Chest(Chest {
+                    items,
+                    custom_name: chest.custom_name,
+                })
+            }
+            _ => Self::Other(blob.clone()),
+        };
+        Some((position, block_entity))
+    }
+    pub fn to_nbt(&self, position: Position) -> nbt::Blob {
+        let Position { x, y, z } = position;
+        match self {
+            Self::Sign(sign) => {
+                let [text1, text2, text3, text4] = sign.lines.clone();
+                to_blob(&SignNbt {
+                    id: "minecraft:sign".to_string(),
+                    x,
+                    y,
+                    z,
+                    text1,
+                    text2,
+                    text3,
+                    text4,
+                })
+            }
+            Self::Chest(chest) => {
+                let items = chest
+                    .items
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, stack)| !stack.is_empty())
+                    .map(|(slot, stack)| InventoryItem {
+                        slot: slot as i8,
+                        id: item_name(stack.id),
+                        count: stack.count,
+                        damage: stack.damage,
+                        tag: stack.nbt.clone(),
+                    })
+                    .collect();
+                to_blob(&ChestNbt {
+                    id: "minecraft:chest".to_string(),
+                    x,
+                    y,
+                    z,
+                    items,
+                    custom_name: chest.custom_name.clone(),
+                })
+            }
+            Self::Other(blob) => blob.clone(),
+        }
+    }
+}
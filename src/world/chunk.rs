@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use byteorder::{BigEndian, WriteBytesExt};
+use minecraft_varint::VarIntWrite;
+use nibbler::{nibble::Nibble, traits::Nib};
+
+use crate::packets::play::{ChunkColumn, ChunkSection, Palette};
+
+use super::block::{self, BlockState};
+
+/// Anything above this uses the global palette, which is 13 bits wide on 1.12.
+const MAX_PALETTE_BITS: u8 = 8;
+const GLOBAL_PALETTE_BITS: u8 = 13;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    Block,
+    Sky,
+}
+
+fn index(x: usize, y: usize, z: usize) -> usize {
+    y << 8 | z << 4 | x
+}
+
+impl ChunkSection {
+    /// A section full of air. `sky_light` holds the initial sky light per column (`z << 4 | x`).
+    pub fn empty(sky_light: Option<&[u8; 256]>) -> Self {
+        let bits_per_block = 4;
+        let data_array_length = 4096 * bits_per_block as u32 / 64;
+        Self {
+            bits_per_block,
+            palette: Palette {
+                palette_length: 1,
+                palette: vec![block::AIR as u32],
+            },
+            data_array_length,
+            data_array: vec![0; data_array_length as usize],
+            block_light: vec![Nibble::from(0u8); 4096],
+            sky_light: sky_light.map(|columns| {
+                (0..4096)
+                    .map(|i| Nibble::from(columns[i & 0xff]))
+                    .collect()
+            }),
+        }
+    }
+    fn get_raw(&self, i: usize) -> u32 {
+        let bits = self.bits_per_block as usize;
+        let mask = (1u64 << bits) - 1;
+        let bit = i * bits;
+        let (start, offset) = (bit / 64, bit % 64);
+        let mut value = (self.data_array[start] as u64) >> offset;
+        if offset + bits > 64 {
+            value |= (self.data_array[start + 1] as u64) << (64 - offset);
+        }
+        (value & mask) as u32
+    }
+    fn set_raw(&mut self, i: usize, value: u32) {
+        let bits = self.bits_per_block as usize;
+        let mask = (1u64 << bits) - 1;
+        let value = value as u64 & mask;
+        let bit = i * bits;
+        let (start, offset) = (bit / 64, bit % 64);
+        let long = self.data_array[start] as u64;
+        self.data_array[start] = ((long & !(mask << offset)) | (value << offset)) as i64;
+        if offset + bits > 64 {
+            let spill = offset + bits - 64;
+            let long = self.data_array[start + 1] as u64;
+            self.data_array[start + 1] =
+                ((long & !((1u64 << spill) - 1)) | (value >> (bits - spill))) as i64;
+        }
+    }
+    /// Re-packs the data array with a different number of bits per block.
+    fn repack(&mut self, bits_per_block: u8) {
+        let states: Vec<BlockState> = (0..4096).map(|i| self.get_index(i)).collect();
+        self.bits_per_block = bits_per_block;
+        self.data_array_length = 4096 * bits_per_block as u32 / 64;
+        self.data_array = vec![0; self.data_array_length as usize];
+        if bits_per_block > MAX_PALETTE_BITS {
+            self.palette = Palette {
+                palette_length: 0,
+                palette: vec![],
+            };
+            for (i, s) in states.into_iter().enumerate() {
+                self.set_raw(i, s as u32);
+            }
+        } else {
+            for (i, s) in states.into_iter().enumerate() {
+                let p = self.palette.palette.iter().position(|&p| p == s as u32);
+                self.set_raw(i, p.unwrap() as u32);
+            }
+        }
+    }
+    fn get_index(&self, i: usize) -> BlockState {
+        let raw = self.get_raw(i);
+        if self.palette.palette_length == 0 {
+            raw as BlockState
+        } else {
+            self.palette.palette[raw as usize] as BlockState
+        }
+    }
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> BlockState {
+        self.get_index(index(x, y, z))
+    }
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, state: BlockState) {
+        let i = index(x, y, z);
+        if self.palette.palette_length == 0 {
+            self.set_raw(i, state as u32);
+            return;
+        }
+        let p = match self.palette.palette.iter().position(|&p| p == state as u32) {
+            Some(p) => p,
+            None => {
+                self.palette.palette.push(state as u32);
+                self.palette.palette_length += 1;
+                if self.palette.palette_length > 1 << self.bits_per_block {
+                    let bits = self.bits_per_block + 1;
+                    self.repack(if bits > MAX_PALETTE_BITS {
+                        GLOBAL_PALETTE_BITS
+                    } else {
+                        bits
+                    });
+                    if self.palette.palette_length == 0 {
+                        self.set_raw(i, state as u32);
+                        return;
+                    }
+                }
+                self.palette.palette.len() - 1
+            }
+        };
+        self.set_raw(i, p as u32);
+    }
+    pub fn get_light(&self, kind: LightKind, x: usize, y: usize, z: usize) -> u8 {
+        match kind {
+            LightKind::Block => self.block_light[index(x, y, z)].ival(),
+            LightKind::Sky => self
+                .sky_light
+                .as_ref()
+                .map(|s| s[index(x, y, z)].ival())
+                .unwrap_or(0),
+        }
+    }
+    pub fn set_light(&mut self, kind: LightKind, x: usize, y: usize, z: usize, level: u8) {
+        let nibble = Nibble::from(level);
+        match kind {
+            LightKind::Block => self.block_light[index(x, y, z)] = nibble,
+            LightKind::Sky => {
+                if let Some(s) = self.sky_light.as_mut() {
+                    s[index(x, y, z)] = nibble;
+                }
+            }
+        }
+    }
+    /// Whether this section can be left out of chunk packets. Clients assume sections they
+    /// weren't sent are air with no block light and full sky light, so a lit empty section,
+    /// like the one above a torch, still has to be sent.
+    pub fn can_skip(&self) -> bool {
+        (0..4096).all(|i| self.get_index(i) == block::AIR)
+            && self.block_light.iter().all(|n| n.ival() == 0)
+            && self
+                .sky_light
+                .as_ref()
+                .is_none_or(|s| s.iter().all(|n| n.ival() == 15))
+    }
+    pub fn write(&self, w: &mut Vec<u8>) {
+        w.write_u8(self.bits_per_block).unwrap();
+        w.write_var_u32(self.palette.palette_length).unwrap();
+        for p in &self.palette.palette {
+            w.write_var_u32(*p).unwrap();
+        }
+        w.write_var_u32(self.data_array_length).unwrap();
+        for long in &self.data_array {
+            w.write_i64::<BigEndian>(*long).unwrap();
+        }
+        write_nibbles(w, &self.block_light);
+        if let Some(sky_light) = &self.sky_light {
+            write_nibbles(w, sky_light);
+        }
+    }
+}
+fn write_nibbles(w: &mut Vec<u8>, nibbles: &[Nibble]) {
+    for pair in nibbles.chunks(2) {
+        w.write_u8(pair[0].ival() | pair[1].ival() << 4).unwrap();
+    }
+}
+
+impl ChunkColumn {
+    pub fn new() -> Self {
+        Self {
+            sections: (0..16).map(|_| None).collect(),
+            biomes: Some(vec![1; 256]),
+            heightmap: vec![0; 256],
+            block_entities: HashMap::new(),
+            dirty: true,
+        }
+    }
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> BlockState {
+        match &self.sections[y >> 4] {
+            Some(section) => section.get_block(x, y & 0xf, z),
+            None => block::AIR,
+        }
+    }
+    /// Sets a block, creating its section if needed. Doesn't touch the heightmap or lighting.
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, state: BlockState) {
+        self.section_mut(y >> 4).set_block(x, y & 0xf, z, state);
+    }
+    pub fn section_mut(&mut self, section: usize) -> &mut ChunkSection {
+        if self.sections[section].is_none() {
+            let mut sky = [0u8; 256];
+            for (i, height) in self.heightmap.iter().enumerate() {
+                sky[i] = if (*height as usize) <= section << 4 { 15 } else { 0 };
+            }
+            self.sections[section] = Some(ChunkSection::empty(Some(&sky)));
+        }
+        self.sections[section].as_mut().unwrap()
+    }
+    pub fn get_light(&self, kind: LightKind, x: usize, y: usize, z: usize) -> u8 {
+        match &self.sections[y >> 4] {
+            Some(section) => section.get_light(kind, x, y & 0xf, z),
+            None => match kind {
+                LightKind::Block => 0,
+                LightKind::Sky if self.heightmap[z << 4 | x] as usize <= y => 15,
+                LightKind::Sky => 0,
+            },
+        }
+    }
+    pub fn set_light(&mut self, kind: LightKind, x: usize, y: usize, z: usize, level: u8) {
+        if self.get_light(kind, x, y, z) == level {
+            return;
+        }
+        self.section_mut(y >> 4).set_light(kind, x, y & 0xf, z, level);
+    }
+    pub fn height(&self, x: usize, z: usize) -> usize {
+        self.heightmap[z << 4 | x] as usize
+    }
+    /// Recalculates the height of one column, returning it.
+    pub fn update_height(&mut self, x: usize, z: usize) -> usize {
+        let height = (0..256)
+            .rev()
+            .find(|&y| block::get(self.get_block(x, y, z)).opacity > 0)
+            .map(|y| y + 1)
+            .unwrap_or(0);
+        self.heightmap[z << 4 | x] = height as u16;
+        height
+    }
+    /// The bitmask of sections that are sent to clients.
+    pub fn primary_bit_mask(&self) -> u32 {
+        self.sections
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| matches!(s, Some(s) if !s.can_skip()))
+            .fold(0, |mask, (i, _)| mask | 1 << i)
+    }
+}
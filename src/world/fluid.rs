@@ -0,0 +1,282 @@
+//! Water and lava, spreading by vanilla 1.12's rules.
+//!
+//! A liquid's meta is its level: 0 for a source, 1 to 7 for liquid flowing away from it and
+//! 8 and up for liquid falling down. Liquid that has settled is the still block, which turns
+//! back into the flowing one when something next to it changes.
+
+use rand::Rng;
+
+use crate::packet::Position;
+
+use super::block::{
+    self, BlockState, COBBLESTONE, FLOWING_LAVA, FLOWING_WATER, LAVA, OBSIDIAN, STANDING_SIGN,
+    STONE, WALL_SIGN, WATER,
+};
+use super::{BlockEvent, World};
+
+const DOWN: u8 = 0;
+const UP: u8 = 1;
+/// North, east, south and west, the order vanilla looks around in.
+const HORIZONTAL: [u8; 4] = [2, 5, 3, 4];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+impl Fluid {
+    pub fn of(state: BlockState) -> Option<Self> {
+        match block::id(state) {
+            FLOWING_WATER | WATER => Some(Self::Water),
+            FLOWING_LAVA | LAVA => Some(Self::Lava),
+            _ => None,
+        }
+    }
+    fn flowing(self) -> u16 {
+        match self {
+            Self::Water => FLOWING_WATER,
+            Self::Lava => FLOWING_LAVA,
+        }
+    }
+    fn still(self) -> u16 {
+        self.flowing() + 1
+    }
+    /// Ticks between updates of flowing liquid.
+    fn tick_rate(self) -> i64 {
+        match self {
+            Self::Water => 5,
+            Self::Lava => 30,
+        }
+    }
+    /// How much the level goes up with each block the liquid spreads sideways, which makes
+    /// water flow 7 blocks and lava 3.
+    fn level_step(self) -> i32 {
+        match self {
+            Self::Water => 1,
+            Self::Lava => 2,
+        }
+    }
+    /// How far flowing liquid looks for a way down before picking where to spread.
+    fn slope_distance(self) -> u32 {
+        match self {
+            Self::Water => 4,
+            Self::Lava => 2,
+        }
+    }
+}
+
+/// The level of a block if it is the given liquid.
+fn level(fluid: Fluid, state: BlockState) -> Option<i32> {
+    (Fluid::of(state) == Some(fluid)).then(|| block::meta(state) as i32)
+}
+/// Blocks liquid doesn't wash away.
+fn is_blocked(state: BlockState) -> bool {
+    let id = block::id(state);
+    id == STANDING_SIGN || id == WALL_SIGN || block::get(state).solid
+}
+fn can_flow_into(fluid: Fluid, state: BlockState) -> bool {
+    let other = Fluid::of(state);
+    other != Some(fluid) && other != Some(Fluid::Lava) && !is_blocked(state)
+}
+/// Whether liquid can spread sideways into a block: anything that isn't in the way and isn't
+/// already a source of the same liquid.
+fn is_open(fluid: Fluid, state: BlockState) -> bool {
+    !is_blocked(state) && level(fluid, state) != Some(0)
+}
+
+/// Lets liquid that was just placed start flowing.
+pub fn added(world: &mut World, position: Position, state: BlockState) {
+    let Some(fluid) = Fluid::of(state) else {
+        return;
+    };
+    if mix(world, position, state) || block::id(state) != fluid.flowing() {
+        return;
+    }
+    world.schedule(position, fluid.flowing(), fluid.tick_rate());
+}
+
+/// Wakes up settled liquid when a block next to it changes.
+pub fn neighbor_changed(world: &mut World, position: Position, state: BlockState) {
+    let Some(fluid) = Fluid::of(state) else {
+        return;
+    };
+    if mix(world, position, state) || block::id(state) != fluid.still() {
+        return;
+    }
+    let flowing = block::state(fluid.flowing(), block::meta(state));
+    world.update_block(position, flowing, false);
+    world.schedule(position, fluid.flowing(), fluid.tick_rate());
+}
+
+/// Turns lava touching water into obsidian if it is a source, and cobblestone if it is close
+/// enough to one. Returns whether it did.
+fn mix(world: &mut World, position: Position, state: BlockState) -> bool {
+    if Fluid::of(state) != Some(Fluid::Lava) {
+        return false;
+    }
+    let touches_water = [UP, 2, 3, 4, 5]
+        .iter()
+        .any(|face| Fluid::of(world.block_at(position.offset(*face))) == Some(Fluid::Water));
+    if !touches_water {
+        return false;
+    }
+    let result = match block::meta(state) {
+        0 => OBSIDIAN,
+        1..=4 => COBBLESTONE,
+        _ => return false,
+    };
+    world.update_block(position, result, true);
+    world.events.push(BlockEvent::Fizz(position));
+    true
+}
+
+/// A scheduled update of flowing liquid: it takes the level its surroundings give it, then
+/// spreads down if it can and sideways otherwise.
+pub fn tick(world: &mut World, position: Position, state: BlockState) {
+    let Some(fluid) = Fluid::of(state) else {
+        return;
+    };
+    if block::id(state) != fluid.flowing() {
+        return;
+    }
+    let mut current = block::meta(state) as i32;
+    let step = fluid.level_step();
+    if current > 0 {
+        let mut lowest = -1;
+        let mut sources = 0;
+        for face in HORIZONTAL {
+            let Some(next) = level(fluid, world.block_at(position.offset(face))) else {
+                continue;
+            };
+            if next == 0 {
+                sources += 1;
+            }
+            // Falling liquid feeds its neighbours like a source.
+            let next = if next >= 8 { 0 } else { next };
+            if lowest < 0 || next < lowest {
+                lowest = next;
+            }
+        }
+        let mut new = if lowest < 0 || lowest + step >= 8 {
+            -1
+        } else {
+            lowest + step
+        };
+        if let Some(above) = level(fluid, world.block_at(position.offset(UP))) {
+            new = if above >= 8 { above } else { above + 8 };
+        }
+        // Water between two sources becomes one, if there is something to hold it up.
+        if sources >= 2 && fluid == Fluid::Water {
+            let below = world.block_at(position.offset(DOWN));
+            if block::get(below).solid || level(fluid, below) == Some(0) {
+                new = 0;
+            }
+        }
+        let mut delay = fluid.tick_rate();
+        if fluid == Fluid::Lava
+            && current < 8
+            && new < 8
+            && new > current
+            && rand::thread_rng().gen_range(0..4) != 0
+        {
+            delay *= 4;
+        }
+        if new == current {
+            settle(world, position, fluid, current);
+        } else if new < 0 {
+            world.update_block(position, block::AIR, true);
+            return;
+        } else {
+            current = new;
+            world.update_block(position, block::state(fluid.flowing(), new as u8), false);
+            world.schedule(position, fluid.flowing(), delay);
+            world.notify_neighbors(position);
+        }
+    } else {
+        settle(world, position, fluid, current);
+    }
+
+    let below_position = position.offset(DOWN);
+    let below = world.block_at(below_position);
+    if can_flow_into(fluid, below) {
+        if fluid == Fluid::Lava && Fluid::of(below) == Some(Fluid::Water) {
+            world.update_block(below_position, STONE, true);
+            world.events.push(BlockEvent::Fizz(below_position));
+            return;
+        }
+        let falling = if current >= 8 { current } else { current + 8 };
+        flow_into(world, fluid, below_position, falling);
+    } else if current == 0 || is_blocked(below) {
+        let spread = if current >= 8 { 1 } else { current + step };
+        if spread >= 8 {
+            return;
+        }
+        for face in flow_directions(world, fluid, position) {
+            flow_into(world, fluid, position.offset(face), spread);
+        }
+    }
+}
+
+/// Turns flowing liquid into the still block, which stays put until a neighbour changes.
+fn settle(world: &mut World, position: Position, fluid: Fluid, level: i32) {
+    world.update_block(position, block::state(fluid.still(), level as u8), false);
+}
+
+fn flow_into(world: &mut World, fluid: Fluid, position: Position, level: i32) {
+    let state = world.block_at(position);
+    if !can_flow_into(fluid, state) {
+        return;
+    }
+    if state != block::AIR {
+        // Lava burns what it flows into, water washes it away.
+        world.events.push(match fluid {
+            Fluid::Lava => BlockEvent::Fizz(position),
+            Fluid::Water => BlockEvent::Destroyed(position, state),
+        });
+    }
+    world.update_block(position, block::state(fluid.flowing(), level as u8), true);
+}
+
+/// The sides liquid spreads to: those with the shortest way down, or all open sides if none
+/// is close enough.
+fn flow_directions(world: &World, fluid: Fluid, position: Position) -> Vec<u8> {
+    let mut shortest = u32::MAX;
+    let mut faces = vec![];
+    for face in HORIZONTAL {
+        let next = position.offset(face);
+        if !is_open(fluid, world.block_at(next)) {
+            continue;
+        }
+        let distance = if is_blocked(world.block_at(next.offset(DOWN))) {
+            slope_distance(world, fluid, next, 1, face ^ 1)
+        } else {
+            0
+        };
+        if distance < shortest {
+            faces.clear();
+        }
+        if distance <= shortest {
+            faces.push(face);
+            shortest = distance;
+        }
+    }
+    faces
+}
+/// How many blocks sideways from `position` liquid would have to flow to fall, not going
+/// back the way it came. `u32::MAX` if there is no way down close enough.
+fn slope_distance(world: &World, fluid: Fluid, position: Position, distance: u32, from: u8) -> u32 {
+    let mut shortest = u32::MAX;
+    for face in HORIZONTAL.into_iter().filter(|f| *f != from) {
+        let next = position.offset(face);
+        if !is_open(fluid, world.block_at(next)) {
+            continue;
+        }
+        if !is_blocked(world.block_at(next.offset(DOWN))) {
+            return distance;
+        }
+        if distance < fluid.slope_distance() {
+            shortest = shortest.min(slope_distance(world, fluid, next, distance + 1, face ^ 1));
+        }
+    }
+    shortest
+}
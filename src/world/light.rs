@@ -0,0 +1,207 @@
+//! Sky and block light propagation.
+//!
+//! Light spreads with a breadth first search, losing at least one level per block. Sky light
+//! is special in that a level of 15 travels straight down through transparent blocks without
+//! decaying. Removal works the other way around: it clears everything that was lit by the
+//! removed light, and collects the brighter cells at the border so they can fill the gap again.
+
+use std::collections::VecDeque;
+
+use super::{block, LightKind, World};
+
+const DIRECTIONS: [(i32, i32, i32); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+type Pos = (i32, i32, i32);
+
+fn opacity(world: &World, (x, y, z): Pos) -> u8 {
+    block::get(world.get_block(x, y, z)).opacity
+}
+/// The level light at `level` has after moving into `to` in direction `dir`.
+fn spread(world: &World, kind: LightKind, level: u8, to: Pos, dir: Pos) -> u8 {
+    let opacity = opacity(world, to);
+    if kind == LightKind::Sky && dir == (0, -1, 0) && level == 15 && opacity == 0 {
+        return 15;
+    }
+    level.saturating_sub(opacity.max(1))
+}
+
+/// Spreads light outwards from every position in `queue`.
+fn propagate(world: &mut World, kind: LightKind, mut queue: VecDeque<Pos>) {
+    while let Some(pos) = queue.pop_front() {
+        let level = world.get_light(kind, pos.0, pos.1, pos.2);
+        if level <= 1 {
+            continue;
+        }
+        for dir in DIRECTIONS {
+            let to = (pos.0 + dir.0, pos.1 + dir.1, pos.2 + dir.2);
+            if !world.is_loaded(to.0, to.1, to.2) {
+                continue;
+            }
+            let new = spread(world, kind, level, to, dir);
+            if new > world.get_light(kind, to.0, to.1, to.2) {
+                world.set_light(kind, to.0, to.1, to.2, new);
+                queue.push_back(to);
+            }
+        }
+    }
+}
+
+/// Clears all light that came from the positions in `queue`, which hold the level each
+/// position had before it was cleared. Returns the positions that need to spread light again.
+fn unpropagate(world: &mut World, kind: LightKind, mut queue: VecDeque<(Pos, u8)>) -> VecDeque<Pos> {
+    let mut relight = VecDeque::new();
+    while let Some((pos, level)) = queue.pop_front() {
+        for dir in DIRECTIONS {
+            let to = (pos.0 + dir.0, pos.1 + dir.1, pos.2 + dir.2);
+            if !world.is_loaded(to.0, to.1, to.2) {
+                continue;
+            }
+            let current = world.get_light(kind, to.0, to.1, to.2);
+            if current == 0 {
+                continue;
+            }
+            let sky_column = kind == LightKind::Sky && dir == (0, -1, 0) && level == 15;
+            if current < level || (sky_column && current == 15) {
+                world.set_light(kind, to.0, to.1, to.2, 0);
+                queue.push_back((to, current));
+                if kind == LightKind::Block {
+                    let emission = block::get(world.get_block(to.0, to.1, to.2)).emission;
+                    if emission > 0 {
+                        world.set_light(kind, to.0, to.1, to.2, emission);
+                        relight.push_back(to);
+                    }
+                }
+            } else {
+                relight.push_back(to);
+            }
+        }
+    }
+    relight
+}
+
+/// Computes the initial sky and block light for a freshly generated chunk, pulling in light
+/// from neighbouring chunks that are already lit.
+pub fn light_chunk(world: &mut World, cx: i32, cz: i32) {
+    let (bx, bz) = (cx << 4, cz << 4);
+    let mut sky = VecDeque::new();
+    let mut blocks = VecDeque::new();
+
+    let chunk = world.chunks.get_mut(&(cx, cz)).unwrap();
+    for x in 0..16 {
+        for z in 0..16 {
+            let height = chunk.height(x, z);
+            for s in 0..16 {
+                if chunk.sections[s].is_none() {
+                    continue;
+                }
+                for y in s << 4..(s + 1) << 4 {
+                    let level = if y >= height { 15 } else { 0 };
+                    chunk.set_light(LightKind::Sky, x, y, z, level);
+                    chunk.set_light(LightKind::Block, x, y, z, 0);
+                    let emission = block::get(chunk.get_block(x, y, z)).emission;
+                    if emission > 0 {
+                        chunk.set_light(LightKind::Block, x, y, z, emission);
+                        blocks.push_back((bx + x as i32, y as i32, bz + z as i32));
+                    }
+                }
+            }
+        }
+    }
+    for x in 0..16 {
+        for z in 0..16 {
+            let (wx, wz) = (bx + x, bz + z);
+            let height = world.height(wx, wz).unwrap() as i32;
+            // Sky light only needs to spread sideways where a neighbouring column is taller.
+            let top = DIRECTIONS
+                .iter()
+                .filter_map(|(dx, _, dz)| world.height(wx + dx, wz + dz))
+                .max()
+                .unwrap_or(0) as i32;
+            for y in height..top.min(256) {
+                sky.push_back((wx, y, wz));
+            }
+        }
+    }
+    // Light already in the neighbouring chunks has to flow over the border as well.
+    for i in 0..16 {
+        let borders = [
+            ((bx - 1, bz + i), (bx, bz + i)),
+            ((bx + 16, bz + i), (bx + 15, bz + i)),
+            ((bx + i, bz - 1), (bx + i, bz)),
+            ((bx + i, bz + 16), (bx + i, bz + 15)),
+        ];
+        for ((x, z), inside) in borders {
+            if !world.is_loaded(x, 0, z) {
+                continue;
+            }
+            // Above the inner column everything is lit by the sky already.
+            let inner_height = world.height(inside.0, inside.1).unwrap() as i32;
+            for y in 0..256 {
+                if world.get_light(LightKind::Block, x, y, z) > 1 {
+                    blocks.push_back((x, y, z));
+                }
+                if y < inner_height && world.get_light(LightKind::Sky, x, y, z) > 1 {
+                    sky.push_back((x, y, z));
+                }
+            }
+        }
+    }
+    propagate(world, LightKind::Sky, sky);
+    propagate(world, LightKind::Block, blocks);
+}
+
+/// Updates light after the block at `(x, y, z)` has changed. `old_height` is the height of the
+/// column before the change; the heightmap must already be updated.
+pub fn relight(world: &mut World, x: i32, y: i32, z: i32, old_height: usize) {
+    let pos = (x, y, z);
+    let neighbours = || {
+        DIRECTIONS
+            .iter()
+            .map(move |d| (x + d.0, y + d.1, z + d.2))
+            .filter(|p| (0..256).contains(&p.1))
+    };
+
+    // Block light: clear whatever was here, then let the new block and its neighbours refill it.
+    let old = world.get_light(LightKind::Block, x, y, z);
+    world.set_light(LightKind::Block, x, y, z, 0);
+    let mut queue = unpropagate(world, LightKind::Block, VecDeque::from([(pos, old)]));
+    let emission = block::get(world.get_block(x, y, z)).emission;
+    if emission > 0 {
+        world.set_light(LightKind::Block, x, y, z, emission);
+        queue.push_back(pos);
+    }
+    queue.extend(neighbours());
+    propagate(world, LightKind::Block, queue);
+
+    // Sky light: the column below the block may have gained or lost direct sunlight.
+    let new_height = world.height(x, z).unwrap();
+    let mut removed = VecDeque::new();
+    let mut queue = VecDeque::new();
+    if new_height > old_height {
+        for y in old_height..new_height {
+            let level = world.get_light(LightKind::Sky, x, y as i32, z);
+            world.set_light(LightKind::Sky, x, y as i32, z, 0);
+            removed.push_back(((x, y as i32, z), level));
+        }
+    } else if new_height < old_height {
+        for y in new_height..old_height {
+            world.set_light(LightKind::Sky, x, y as i32, z, 15);
+            queue.push_back((x, y as i32, z));
+        }
+    }
+    if !(new_height..old_height).contains(&(y as usize)) {
+        let level = world.get_light(LightKind::Sky, x, y, z);
+        world.set_light(LightKind::Sky, x, y, z, 0);
+        removed.push_back((pos, level));
+    }
+    queue.extend(unpropagate(world, LightKind::Sky, removed));
+    queue.extend(neighbours());
+    propagate(world, LightKind::Sky, queue);
+}
@@ -0,0 +1,351 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use log::{debug, error};
+
+use crate::effects::Sound;
+
+use crate::packet::Position;
+use crate::packets::play::{ChangeGameState, ChunkColumn, GameStateReason, TimeUpdate};
+
+use self::block::BlockState;
+use self::block_entity::BlockEntity;
+pub use self::chunk::LightKind;
+use self::fluid::Fluid;
+use self::redstone::Occupant;
+use self::scheduled::ScheduledTicks;
+use self::time::{Clock, Weather};
+
+pub mod anvil;
+pub mod block;
+pub mod block_entity;
+pub mod chunk;
+pub mod fluid;
+pub mod light;
+pub mod redstone;
+pub mod scheduled;
+pub mod time;
+
+/// Height of the grass layer in generated chunks.
+const GROUND_LEVEL: usize = 63;
+/// Where players appear when they join.
+pub const SPAWN: (f64, f64, f64) = (0.5, GROUND_LEVEL as f64 + 1.0, 0.5);
+
+/// Settings changed with `/gamerule`, only the ones the server does something with.
+#[derive(Debug, Clone)]
+pub struct GameRules {
+    pub do_daylight_cycle: bool,
+    pub do_weather_cycle: bool,
+}
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            do_daylight_cycle: true,
+            do_weather_cycle: true,
+        }
+    }
+}
+impl GameRules {
+    pub const NAMES: &'static [&'static str] = &["doDaylightCycle", "doWeatherCycle"];
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "doDaylightCycle" => Some(&mut self.do_daylight_cycle),
+            "doWeatherCycle" => Some(&mut self.do_weather_cycle),
+            _ => None,
+        }
+    }
+}
+
+/// The order vanilla tells the six neighbours of a block about a change: west, east, down,
+/// up, north, south.
+const UPDATE_ORDER: [u8; 6] = [4, 5, 0, 1, 2, 3];
+
+/// Something blocks did on their own that players should see or hear, besides the blocks
+/// changing.
+#[derive(Debug, Clone)]
+pub enum BlockEvent {
+    /// Lava touched water or burned a block.
+    Fizz(Position),
+    /// A block was washed away or knocked off and drops as an item.
+    Destroyed(Position, BlockState),
+    Sound {
+        position: Position,
+        sound: Sound,
+        volume: f32,
+        pitch: f32,
+    },
+    /// A block action for clients to animate, like a piston moving.
+    Action {
+        position: Position,
+        action: u8,
+        param: u8,
+        block: u16,
+    },
+}
+
+/// All loaded chunk columns, addressed by chunk coordinates, and the state shared by the
+/// whole world.
+#[derive(Debug, Default)]
+pub struct World {
+    pub chunks: HashMap<(i32, i32), ChunkColumn>,
+    pub clock: Clock,
+    pub weather: Weather,
+    pub rules: GameRules,
+    scheduled: ScheduledTicks,
+    /// Blocks changed by the world itself since the server last sent them out.
+    pub changed: Vec<Position>,
+    pub events: Vec<BlockEvent>,
+    /// The blocks something is standing in, filled in by the server before each tick for
+    /// pressure plates.
+    pub occupants: HashMap<Position, Occupant>,
+    /// When each redstone torch recently turned off, to burn out ones that flicker.
+    torch_toggles: HashMap<Position, Vec<i64>>,
+    /// Pistons waiting to extend or retract at the end of the tick.
+    piston_moves: Vec<(Position, bool)>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Advances the clock and weather by one tick, and runs the block updates that are due.
+    pub fn tick(&mut self) {
+        self.clock.tick(self.rules.do_daylight_cycle);
+        self.weather.tick(self.rules.do_weather_cycle);
+        redstone::press_plates(self);
+        for (position, id) in self.scheduled.take_due(self.clock.age) {
+            let state = self.block_at(position);
+            // The block may have been replaced since.
+            if redstone::kind(block::id(state)) != id {
+                continue;
+            }
+            if Fluid::of(state).is_some() {
+                fluid::tick(self, position, state);
+            } else {
+                redstone::tick(self, position, state);
+            }
+        }
+        redstone::run_piston_moves(self);
+    }
+    /// A player right-clicked a block. Returns whether it did something, in which case
+    /// nothing gets placed.
+    pub fn use_block(&mut self, position: Position) -> bool {
+        redstone::use_block(self, position)
+    }
+    /// Schedules an update of the block with id `block` at `position`, `delay` ticks from
+    /// now.
+    pub fn schedule(&mut self, position: Position, block: u16, delay: i64) {
+        if self.is_loaded(position.x, position.y, position.z) {
+            self.scheduled
+                .schedule(position, block, self.clock.age + delay);
+        }
+    }
+    /// Sets a block the way the game itself does: the new block gets to react to being
+    /// placed, its neighbours to the change if `notify` is set, and the change goes out to
+    /// players at the end of the tick.
+    pub fn update_block(&mut self, position: Position, state: BlockState, notify: bool) {
+        let Some(old) = self.set_block(position.x, position.y, position.z, state) else {
+            return;
+        };
+        if old == state {
+            return;
+        }
+        self.changed.push(position);
+        if block::id(old) != block::id(state) {
+            fluid::added(self, position, state);
+            redstone::replaced(self, position, old, state);
+        }
+        if notify {
+            self.notify_neighbors(position);
+        }
+    }
+    /// Lets the six blocks around a position react to it changing.
+    pub fn notify_neighbors(&mut self, position: Position) {
+        for face in UPDATE_ORDER {
+            let neighbor = position.offset(face);
+            let state = self.block_at(neighbor);
+            if Fluid::of(state).is_some() {
+                fluid::neighbor_changed(self, neighbor, state);
+            } else {
+                redstone::neighbor_changed(self, neighbor, state);
+            }
+        }
+    }
+    pub fn time_update(&self) -> TimeUpdate {
+        let time_of_day = self.clock.day_time;
+        TimeUpdate {
+            world_age: self.clock.age,
+            time_of_day: if self.rules.do_daylight_cycle {
+                time_of_day
+            } else {
+                // Vanilla can't freeze at 0, since -0 is 0.
+                -time_of_day.max(1)
+            },
+        }
+    }
+    /// What a joining player needs to be told about the weather.
+    pub fn weather_packets(&self) -> Vec<ChangeGameState> {
+        if !self.weather.is_raining() {
+            return vec![];
+        }
+        vec![
+            ChangeGameState {
+                reason: GameStateReason::BeginRaining,
+                value: 0.0,
+            },
+            ChangeGameState {
+                reason: GameStateReason::FadeValue,
+                value: self.weather.rain_level,
+            },
+            ChangeGameState {
+                reason: GameStateReason::FadeTime,
+                value: self.weather.thunder_level,
+            },
+        ]
+    }
+    /// Loads every missing chunk within `radius` chunks of `center` from its region file, or
+    /// generates and lights it if it was never saved.
+    pub fn load_area(&mut self, center: (i32, i32), radius: i32) {
+        let mut generated = vec![];
+        for cx in center.0 - radius..=center.0 + radius {
+            for cz in center.1 - radius..=center.1 + radius {
+                if let Entry::Vacant(e) = self.chunks.entry((cx, cz)) {
+                    match anvil::load_chunk(cx, cz) {
+                        Some((column, ticks)) => {
+                            e.insert(column);
+                            for (position, id, delay) in ticks {
+                                self.scheduled
+                                    .schedule(position, id, self.clock.age + delay);
+                            }
+                        }
+                        None => {
+                            e.insert(generate_flat());
+                            generated.push((cx, cz));
+                        }
+                    }
+                }
+            }
+        }
+        for (cx, cz) in generated {
+            light::light_chunk(self, cx, cz);
+        }
+    }
+    pub fn get_chunk(&self, cx: i32, cz: i32) -> Option<&ChunkColumn> {
+        self.chunks.get(&(cx, cz))
+    }
+    pub fn block_at(&self, position: Position) -> BlockState {
+        self.get_block(position.x, position.y, position.z)
+    }
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> BlockState {
+        if !(0..256).contains(&y) {
+            return block::AIR;
+        }
+        match self.chunks.get(&(x >> 4, z >> 4)) {
+            Some(c) => c.get_block((x & 0xf) as usize, y as usize, (z & 0xf) as usize),
+            None => block::AIR,
+        }
+    }
+    /// Writes every chunk changed since it was last saved to its region file.
+    pub fn save(&mut self) {
+        let mut saved = 0;
+        for ((cx, cz), column) in self.chunks.iter_mut().filter(|(_, c)| c.dirty) {
+            let ticks: Vec<_> = self
+                .scheduled
+                .in_chunk(*cx, *cz)
+                .into_iter()
+                .map(|(position, id, due)| (position, id, due - self.clock.age))
+                .collect();
+            match anvil::save_chunk(*cx, *cz, column, &ticks) {
+                Ok(()) => saved += 1,
+                Err(e) => error!("Couldn't save chunk {}, {}: {}", cx, cz, e),
+            }
+            column.dirty = false;
+        }
+        debug!("Saved {} chunks.", saved);
+    }
+    /// Sets a block and relights around it. A different block replaces the block entity with
+    /// a fresh one, if the new block has one. Returns the previous state, or `None` if the
+    /// position isn't loaded.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, state: BlockState) -> Option<BlockState> {
+        if !(0..256).contains(&y) {
+            return None;
+        }
+        let chunk = self.chunks.get_mut(&(x >> 4, z >> 4))?;
+        let (lx, lz) = ((x & 0xf) as usize, (z & 0xf) as usize);
+        let old = chunk.get_block(lx, y as usize, lz);
+        if old == state {
+            return Some(old);
+        }
+        chunk.dirty = true;
+        if block::id(old) != block::id(state) {
+            let position = Position::new(x, y, z);
+            chunk.block_entities.remove(&position);
+            if let Some(block_entity) = BlockEntity::for_block(state) {
+                chunk.block_entities.insert(position, block_entity);
+            }
+        }
+        chunk.set_block(lx, y as usize, lz, state);
+        let old_height = chunk.height(lx, lz);
+        chunk.update_height(lx, lz);
+        light::relight(self, x, y, z, old_height);
+        Some(old)
+    }
+    pub fn block_entity(&self, position: Position) -> Option<&BlockEntity> {
+        let chunk = self.chunks.get(&(position.x >> 4, position.z >> 4))?;
+        chunk.block_entities.get(&position)
+    }
+    /// Marks the chunk as changed, since the caller is probably about to change it.
+    pub fn block_entity_mut(&mut self, position: Position) -> Option<&mut BlockEntity> {
+        let chunk = self.chunks.get_mut(&(position.x >> 4, position.z >> 4))?;
+        let block_entity = chunk.block_entities.get_mut(&position)?;
+        chunk.dirty = true;
+        Some(block_entity)
+    }
+    pub fn is_loaded(&self, x: i32, y: i32, z: i32) -> bool {
+        (0..256).contains(&y) && self.chunks.contains_key(&(x >> 4, z >> 4))
+    }
+    pub fn get_light(&self, kind: LightKind, x: i32, y: i32, z: i32) -> u8 {
+        if y >= 256 {
+            return if kind == LightKind::Sky { 15 } else { 0 };
+        }
+        if y < 0 {
+            return 0;
+        }
+        match self.chunks.get(&(x >> 4, z >> 4)) {
+            Some(c) => c.get_light(kind, (x & 0xf) as usize, y as usize, (z & 0xf) as usize),
+            None => 0,
+        }
+    }
+    pub fn set_light(&mut self, kind: LightKind, x: i32, y: i32, z: i32, level: u8) {
+        if !(0..256).contains(&y) {
+            return;
+        }
+        if let Some(c) = self.chunks.get_mut(&(x >> 4, z >> 4)) {
+            c.set_light(kind, (x & 0xf) as usize, y as usize, (z & 0xf) as usize, level);
+        }
+    }
+    pub fn height(&self, x: i32, z: i32) -> Option<usize> {
+        self.chunks
+            .get(&(x >> 4, z >> 4))
+            .map(|c| c.height((x & 0xf) as usize, (z & 0xf) as usize))
+    }
+}
+
+/// Bedrock, stone, three layers of dirt and grass on top.
+fn generate_flat() -> ChunkColumn {
+    let mut column = ChunkColumn::new();
+    for x in 0..16 {
+        for z in 0..16 {
+            for y in 0..=GROUND_LEVEL {
+                let state = match y {
+                    0 => block::BEDROCK,
+                    y if y == GROUND_LEVEL => block::GRASS,
+                    y if y >= GROUND_LEVEL - 3 => block::DIRT,
+                    _ => block::STONE,
+                };
+                column.set_block(x, y, z, state);
+            }
+            column.update_height(x, z);
+        }
+    }
+    column
+}
@@ -0,0 +1,740 @@
+//! Redstone: wire, torches, repeaters, levers, buttons, pressure plates and pistons, by
+//! vanilla 1.12's rules.
+//!
+//! Components give weak power to the blocks next to them, and strong power to one or two of
+//! them, usually the block they are attached to. A normal cube, an opaque full block, passes
+//! strong power on to everything around it. Changes spread through neighbour updates,
+//! scheduled ticks for the components with a delay, and piston moves run at the end of the
+//! tick.
+//!
+//! Wire is recalculated a whole network at a time instead of block by block, which ends up
+//! with the same power levels as vanilla without the flood of updates.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::effects::Sound;
+use crate::packet::Position;
+
+use super::block::{
+    self, BlockState, Shape, CHEST, LEVER, PISTON, PISTON_HEAD, POWERED_REPEATER, REDSTONE_TORCH,
+    REDSTONE_WIRE, STANDING_SIGN, STICKY_PISTON, STONE_BUTTON, STONE_PRESSURE_PLATE,
+    UNLIT_REDSTONE_TORCH, UNPOWERED_REPEATER, WALL_SIGN, WOODEN_BUTTON, WOODEN_PRESSURE_PLATE,
+};
+use super::{BlockEvent, World, UPDATE_ORDER};
+
+const DOWN: u8 = 0;
+const UP: u8 = 1;
+/// North, east, south and west.
+const HORIZONTAL: [u8; 4] = [2, 5, 3, 4];
+
+const TORCH_DELAY: i64 = 2;
+/// A torch that turns off this many times within `BURNOUT_TICKS` burns out.
+const BURNOUT_TOGGLES: usize = 8;
+const BURNOUT_TICKS: i64 = 60;
+/// How long a burnt out torch waits before trying to light again.
+const BURNOUT_RECOVERY: i64 = 160;
+const STONE_BUTTON_TICKS: i64 = 20;
+const WOODEN_BUTTON_TICKS: i64 = 30;
+/// How often a pressed plate checks whether something is still on it.
+const PLATE_TICKS: i64 = 20;
+/// The most blocks a piston can push.
+const PUSH_LIMIT: usize = 12;
+
+/// What is standing on a block, for pressure plates. Stone plates only react to mobs,
+/// wooden ones to anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Occupant {
+    Item,
+    Mob,
+}
+
+/// How a block reacts to a piston pushing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mobility {
+    Normal,
+    /// Breaks and drops as an item.
+    Destroy,
+    /// Stops the piston.
+    Block,
+}
+
+/// The id scheduled updates are kept under, the same for the on and off versions of a block.
+pub fn kind(id: u16) -> u16 {
+    match id {
+        REDSTONE_TORCH => UNLIT_REDSTONE_TORCH,
+        POWERED_REPEATER => UNPOWERED_REPEATER,
+        id => id,
+    }
+}
+
+fn is_normal_cube(state: BlockState) -> bool {
+    let block = block::get(state);
+    block.solid && block.shape == Shape::Full && block.opacity == 15
+}
+fn is_wire(state: BlockState) -> bool {
+    block::id(state) == REDSTONE_WIRE
+}
+fn is_piston(state: BlockState) -> bool {
+    matches!(block::id(state), PISTON | STICKY_PISTON)
+}
+/// Levers, buttons and pistons keep whether they are on in the top bit of their meta.
+fn top_bit(state: BlockState) -> bool {
+    block::meta(state) & 8 != 0
+}
+fn rotate_clockwise(face: u8) -> u8 {
+    match face {
+        2 => 5,
+        5 => 3,
+        3 => 4,
+        4 => 2,
+        face => face,
+    }
+}
+
+/// The side of the block holding a component up, for the ones that pop off without one.
+fn support(state: BlockState) -> Option<u8> {
+    let meta = block::meta(state);
+    match block::id(state) {
+        REDSTONE_WIRE
+        | STONE_PRESSURE_PLATE
+        | WOODEN_PRESSURE_PLATE
+        | UNPOWERED_REPEATER
+        | POWERED_REPEATER => Some(DOWN),
+        REDSTONE_TORCH | UNLIT_REDSTONE_TORCH => {
+            Some([DOWN, 4, 5, 2, 3, DOWN][meta.min(5) as usize])
+        }
+        LEVER => Some([UP, 4, 5, 2, 3, DOWN, DOWN, UP][(meta & 7) as usize]),
+        STONE_BUTTON | WOODEN_BUTTON => {
+            Some([UP, 4, 5, 2, 3, DOWN, DOWN, DOWN][(meta & 7) as usize])
+        }
+        _ => None,
+    }
+}
+
+/// The side a repeater takes its input from. It powers the opposite one.
+fn repeater_facing(state: BlockState) -> u8 {
+    [3, 4, 2, 5][(block::meta(state) & 3) as usize]
+}
+fn repeater_delay(state: BlockState) -> i64 {
+    ((block::meta(state) >> 2) as i64 + 1) * 2
+}
+
+/// The power a block gives the one next to it at `position.offset(towards)`. Wire only
+/// counts if `wires` is set, so it can work out its own level without seeing itself.
+fn weak_power(
+    world: &World,
+    position: Position,
+    state: BlockState,
+    towards: u8,
+    wires: bool,
+) -> u8 {
+    match block::id(state) {
+        REDSTONE_WIRE if wires => wire_power(world, position, state, towards),
+        REDSTONE_TORCH if support(state) != Some(towards) => 15,
+        POWERED_REPEATER if towards == repeater_facing(state) ^ 1 => 15,
+        LEVER | STONE_BUTTON | WOODEN_BUTTON if top_bit(state) => 15,
+        STONE_PRESSURE_PLATE | WOODEN_PRESSURE_PLATE if block::meta(state) == 1 => 15,
+        _ => 0,
+    }
+}
+/// The power a block gives the one at `position.offset(towards)` strongly enough for it to
+/// pass on.
+fn strong_power(
+    world: &World,
+    position: Position,
+    state: BlockState,
+    towards: u8,
+    wires: bool,
+) -> u8 {
+    match block::id(state) {
+        REDSTONE_WIRE if wires => wire_power(world, position, state, towards),
+        REDSTONE_TORCH if towards == UP => 15,
+        POWERED_REPEATER if towards == repeater_facing(state) ^ 1 => 15,
+        LEVER | STONE_BUTTON | WOODEN_BUTTON
+            if top_bit(state) && support(state) == Some(towards) =>
+        {
+            15
+        }
+        STONE_PRESSURE_PLATE | WOODEN_PRESSURE_PLATE
+            if block::meta(state) == 1 && towards == DOWN =>
+        {
+            15
+        }
+        _ => 0,
+    }
+}
+/// The power reaching the block at `source.offset(towards)` from `source`, either from a
+/// component there or passed on by a normal cube.
+fn power_from(world: &World, source: Position, towards: u8, wires: bool) -> u8 {
+    let state = world.block_at(source);
+    if is_normal_cube(state) {
+        (0..6)
+            .map(|face| {
+                let next = source.offset(face);
+                strong_power(world, next, world.block_at(next), face ^ 1, wires)
+            })
+            .max()
+            .unwrap()
+    } else {
+        weak_power(world, source, state, towards, wires)
+    }
+}
+/// The most power reaching a block from any side.
+fn received_power(world: &World, position: Position, wires: bool) -> u8 {
+    (0..6)
+        .map(|face| power_from(world, position.offset(face), face ^ 1, wires))
+        .max()
+        .unwrap()
+}
+
+/// Wire powers the block under it, and the block a straight line of it runs into. Wire that
+/// doesn't point anywhere powers all four sides.
+fn wire_power(world: &World, position: Position, state: BlockState, towards: u8) -> u8 {
+    let power = block::meta(state);
+    match towards {
+        DOWN => power,
+        UP => 0,
+        _ => {
+            let links: Vec<u8> = HORIZONTAL
+                .into_iter()
+                .filter(|face| wire_points(world, position, *face))
+                .collect();
+            let clockwise = rotate_clockwise(towards);
+            let straight = links.contains(&(towards ^ 1))
+                && !links.contains(&clockwise)
+                && !links.contains(&(clockwise ^ 1));
+            if links.is_empty() || straight {
+                power
+            } else {
+                0
+            }
+        }
+    }
+}
+/// Whether wire at `position` bends towards a side: to other wire on the same level or one
+/// step up or down, and to components it can power.
+fn wire_points(world: &World, position: Position, face: u8) -> bool {
+    let side = position.offset(face);
+    let state = world.block_at(side);
+    let cube = is_normal_cube(state);
+    let covered = is_normal_cube(world.block_at(position.offset(UP)));
+    if !covered && cube && is_wire(world.block_at(side.offset(UP))) {
+        return true;
+    }
+    if !cube && is_wire(world.block_at(side.offset(DOWN))) {
+        return true;
+    }
+    match block::id(state) {
+        REDSTONE_WIRE
+        | REDSTONE_TORCH
+        | UNLIT_REDSTONE_TORCH
+        | LEVER
+        | STONE_BUTTON
+        | WOODEN_BUTTON
+        | STONE_PRESSURE_PLATE
+        | WOODEN_PRESSURE_PLATE => true,
+        UNPOWERED_REPEATER | POWERED_REPEATER => repeater_facing(state) | 1 == face | 1,
+        _ => false,
+    }
+}
+/// The wire a wire at `position` passes power to.
+fn wire_links(world: &World, position: Position) -> Vec<Position> {
+    let covered = is_normal_cube(world.block_at(position.offset(UP)));
+    let mut links = vec![];
+    for face in HORIZONTAL {
+        let side = position.offset(face);
+        links.push(side);
+        if !is_normal_cube(world.block_at(side)) {
+            links.push(side.offset(DOWN));
+        } else if !covered {
+            links.push(side.offset(UP));
+        }
+    }
+    links.retain(|link| is_wire(world.block_at(*link)));
+    links
+}
+/// The level a wire should be at given its surroundings as they are.
+fn wire_target(world: &World, position: Position) -> u8 {
+    let direct = received_power(world, position, false);
+    let carried = wire_links(world, position)
+        .into_iter()
+        .map(|link| block::meta(world.block_at(link)))
+        .max()
+        .unwrap_or(0);
+    direct.max(carried.saturating_sub(1))
+}
+/// Works out the levels of the whole network of wire connected to `start` from the power
+/// going into it, and updates the wire that changed.
+fn update_network(world: &mut World, start: Position) {
+    let mut network = vec![start];
+    let mut seen = HashSet::from([start]);
+    let mut i = 0;
+    while i < network.len() {
+        for link in wire_links(world, network[i]) {
+            if seen.insert(link) {
+                network.push(link);
+            }
+        }
+        i += 1;
+    }
+    let mut power: HashMap<Position, u8> = network
+        .iter()
+        .map(|position| (*position, received_power(world, *position, false)))
+        .collect();
+    // Spread from the strongest wire down, each one being one less than the best link.
+    let mut levels: Vec<Vec<Position>> = vec![vec![]; 16];
+    for (position, level) in &power {
+        levels[*level as usize].push(*position);
+    }
+    for level in (2..16).rev() {
+        for position in std::mem::take(&mut levels[level]) {
+            if power[&position] as usize != level {
+                continue;
+            }
+            for link in wire_links(world, position) {
+                let Some(current) = power.get_mut(&link) else {
+                    continue;
+                };
+                if (*current as usize) < level - 1 {
+                    *current = level as u8 - 1;
+                    levels[level - 1].push(link);
+                }
+            }
+        }
+    }
+    let changed: Vec<Position> = network
+        .into_iter()
+        .filter(|position| block::meta(world.block_at(*position)) != power[position])
+        .collect();
+    for position in &changed {
+        world.update_block(
+            *position,
+            block::state(REDSTONE_WIRE, power[position]),
+            false,
+        );
+    }
+    let mut notified = HashSet::new();
+    for position in changed {
+        for next in std::iter::once(position).chain(UPDATE_ORDER.map(|face| position.offset(face)))
+        {
+            if notified.insert(next) {
+                world.notify_neighbors(next);
+            }
+        }
+    }
+}
+
+/// Lets every block within two blocks of `position` know about a component there turning
+/// on or off, since it may power the blocks next to it strongly.
+fn notify_around(world: &mut World, position: Position) {
+    world.notify_neighbors(position);
+    for face in UPDATE_ORDER {
+        world.notify_neighbors(position.offset(face));
+    }
+}
+/// Whether a block gives power to anything.
+fn gives_power(state: BlockState) -> bool {
+    match block::id(state) {
+        REDSTONE_TORCH | POWERED_REPEATER => true,
+        LEVER | STONE_BUTTON | WOODEN_BUTTON => top_bit(state),
+        STONE_PRESSURE_PLATE | WOODEN_PRESSURE_PLATE => block::meta(state) == 1,
+        _ => false,
+    }
+}
+
+/// Reacts to the block at `position` being replaced by a different one.
+pub fn replaced(world: &mut World, position: Position, old: BlockState, new: BlockState) {
+    if gives_power(old) || gives_power(new) || is_wire(old) || is_wire(new) {
+        notify_around(world, position);
+    }
+    // A piston and its head go together.
+    if is_piston(old) && top_bit(old) {
+        let facing = block::meta(old) & 7;
+        let head = position.offset(facing);
+        let state = world.block_at(head);
+        if block::id(state) == PISTON_HEAD && block::meta(state) & 7 == facing {
+            world.update_block(head, block::AIR, true);
+        }
+    } else if block::id(old) == PISTON_HEAD {
+        let facing = block::meta(old) & 7;
+        let base = position.offset(facing ^ 1);
+        let state = world.block_at(base);
+        if is_piston(state) && top_bit(state) && block::meta(state) & 7 == facing {
+            world.events.push(BlockEvent::Destroyed(base, state));
+            world.update_block(base, block::AIR, true);
+        }
+    }
+    neighbor_changed(world, position, new);
+}
+
+/// Lets a component react to a block next to it changing.
+pub fn neighbor_changed(world: &mut World, position: Position, state: BlockState) {
+    if let Some(face) = support(state) {
+        if !block::get(world.block_at(position.offset(face))).solid {
+            world.events.push(BlockEvent::Destroyed(position, state));
+            world.update_block(position, block::AIR, true);
+            return;
+        }
+    }
+    match block::id(state) {
+        REDSTONE_WIRE if block::meta(state) != wire_target(world, position) => {
+            update_network(world, position);
+        }
+        REDSTONE_TORCH | UNLIT_REDSTONE_TORCH
+            if (block::id(state) == REDSTONE_TORCH)
+                == torch_should_be_off(world, position, state) =>
+        {
+            world.schedule(position, UNLIT_REDSTONE_TORCH, TORCH_DELAY);
+        }
+        UNPOWERED_REPEATER | POWERED_REPEATER => {
+            if repeater_locked(world, position, state)
+                || world.scheduled.is_pending(position, UNPOWERED_REPEATER)
+            {
+                return;
+            }
+            let powered = block::id(state) == POWERED_REPEATER;
+            if powered != (repeater_input(world, position, state) > 0) {
+                world.schedule(position, UNPOWERED_REPEATER, repeater_delay(state));
+            }
+        }
+        PISTON | STICKY_PISTON => check_piston(world, position, state),
+        _ => {}
+    }
+}
+
+/// A scheduled update of a component.
+pub fn tick(world: &mut World, position: Position, state: BlockState) {
+    let meta = block::meta(state);
+    match block::id(state) {
+        REDSTONE_TORCH | UNLIT_REDSTONE_TORCH => tick_torch(world, position, state),
+        UNPOWERED_REPEATER | POWERED_REPEATER => {
+            if repeater_locked(world, position, state) {
+                return;
+            }
+            let should = repeater_input(world, position, state) > 0;
+            if block::id(state) == POWERED_REPEATER {
+                if !should {
+                    world.update_block(position, block::state(UNPOWERED_REPEATER, meta), false);
+                }
+            } else {
+                world.update_block(position, block::state(POWERED_REPEATER, meta), false);
+                // A pulse shorter than the delay still comes out as long as the delay.
+                if !should {
+                    world.schedule(position, UNPOWERED_REPEATER, repeater_delay(state));
+                }
+            }
+        }
+        id @ (STONE_BUTTON | WOODEN_BUTTON) if top_bit(state) => {
+            set_attached(world, position, block::state(id, meta & 7));
+            let sound = if id == STONE_BUTTON {
+                Sound::StoneButtonClickOff
+            } else {
+                Sound::WoodButtonClickOff
+            };
+            play(world, position, sound, 0.3, 0.5);
+        }
+        id @ (STONE_PRESSURE_PLATE | WOODEN_PRESSURE_PLATE) if meta == 1 => {
+            if plate_pressed(world, position, id) {
+                world.schedule(position, id, PLATE_TICKS);
+                return;
+            }
+            set_attached(world, position, block::state(id, 0));
+            let sound = if id == STONE_PRESSURE_PLATE {
+                Sound::StonePressurePlateClickOff
+            } else {
+                Sound::WoodPressurePlateClickOff
+            };
+            play(world, position, sound, 0.3, 0.5);
+        }
+        _ => {}
+    }
+}
+
+/// Right-clicking a lever, button or repeater. Returns whether the block reacted.
+pub fn use_block(world: &mut World, position: Position) -> bool {
+    let state = world.block_at(position);
+    let meta = block::meta(state);
+    match block::id(state) {
+        LEVER => {
+            set_attached(world, position, block::state(LEVER, meta ^ 8));
+            let pitch = if meta & 8 == 0 { 0.6 } else { 0.5 };
+            play(world, position, Sound::LeverClick, 0.3, pitch);
+        }
+        id @ (STONE_BUTTON | WOODEN_BUTTON) => {
+            if top_bit(state) {
+                return true;
+            }
+            set_attached(world, position, block::state(id, meta | 8));
+            let (sound, ticks) = if id == STONE_BUTTON {
+                (Sound::StoneButtonClick, STONE_BUTTON_TICKS)
+            } else {
+                (Sound::WoodButtonClickOn, WOODEN_BUTTON_TICKS)
+            };
+            play(world, position, sound, 0.3, 0.6);
+            world.schedule(position, id, ticks);
+        }
+        id @ (UNPOWERED_REPEATER | POWERED_REPEATER) => {
+            world.update_block(position, block::state(id, (meta + 4) & 15), true);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Presses the plates something is standing on. Run once a tick, after the server has
+/// filled in `World::occupants`.
+pub fn press_plates(world: &mut World) {
+    let positions: Vec<Position> = world.occupants.keys().copied().collect();
+    for position in positions {
+        let state = world.block_at(position);
+        let id = block::id(state);
+        if !matches!(id, STONE_PRESSURE_PLATE | WOODEN_PRESSURE_PLATE)
+            || block::meta(state) == 1
+            || !plate_pressed(world, position, id)
+        {
+            continue;
+        }
+        set_attached(world, position, block::state(id, 1));
+        let sound = if id == STONE_PRESSURE_PLATE {
+            Sound::StonePressurePlateClickOn
+        } else {
+            Sound::WoodPressurePlateClickOn
+        };
+        play(world, position, sound, 0.3, 0.6);
+        world.schedule(position, id, PLATE_TICKS);
+    }
+}
+fn plate_pressed(world: &World, position: Position, id: u16) -> bool {
+    match world.occupants.get(&position) {
+        Some(Occupant::Mob) => true,
+        Some(Occupant::Item) => id == WOODEN_PRESSURE_PLATE,
+        None => false,
+    }
+}
+
+/// Switches a lever, button or pressure plate, letting the block it is attached to pass the
+/// change on.
+fn set_attached(world: &mut World, position: Position, state: BlockState) {
+    world.update_block(position, state, true);
+    if let Some(face) = support(state) {
+        world.notify_neighbors(position.offset(face));
+    }
+}
+fn play(world: &mut World, position: Position, sound: Sound, volume: f32, pitch: f32) {
+    world.events.push(BlockEvent::Sound {
+        position,
+        sound,
+        volume,
+        pitch,
+    });
+}
+
+/// Torches turn off when the block they are attached to is powered.
+fn torch_should_be_off(world: &World, position: Position, state: BlockState) -> bool {
+    let face = support(state).unwrap();
+    power_from(world, position.offset(face), face ^ 1, true) > 0
+}
+fn tick_torch(world: &mut World, position: Position, state: BlockState) {
+    let lit = block::id(state) == REDSTONE_TORCH;
+    let off = torch_should_be_off(world, position, state);
+    let now = world.clock.age;
+    let toggles = world.torch_toggles.entry(position).or_default();
+    toggles.retain(|at| now - at < BURNOUT_TICKS);
+    if lit && off {
+        toggles.push(now);
+        let burnt_out = toggles.len() >= BURNOUT_TOGGLES;
+        world.update_block(
+            position,
+            block::state(UNLIT_REDSTONE_TORCH, block::meta(state)),
+            false,
+        );
+        if burnt_out {
+            play(world, position, Sound::RedstoneTorchBurnout, 0.5, 2.6);
+            world.schedule(position, UNLIT_REDSTONE_TORCH, BURNOUT_RECOVERY);
+        }
+    } else if !lit && !off && toggles.len() < BURNOUT_TOGGLES {
+        world.update_block(
+            position,
+            block::state(REDSTONE_TORCH, block::meta(state)),
+            false,
+        );
+    }
+    if world
+        .torch_toggles
+        .get(&position)
+        .is_some_and(Vec::is_empty)
+    {
+        world.torch_toggles.remove(&position);
+    }
+}
+
+/// The power going into the back of a repeater.
+fn repeater_input(world: &World, position: Position, state: BlockState) -> u8 {
+    let facing = repeater_facing(state);
+    let behind = position.offset(facing);
+    let behind_state = world.block_at(behind);
+    let power = power_from(world, behind, facing ^ 1, true);
+    if is_wire(behind_state) {
+        power.max(block::meta(behind_state))
+    } else {
+        power
+    }
+}
+/// A repeater is locked while a powered repeater points into its side.
+fn repeater_locked(world: &World, position: Position, state: BlockState) -> bool {
+    let side = rotate_clockwise(repeater_facing(state));
+    [side, side ^ 1].into_iter().any(|face| {
+        let other = world.block_at(position.offset(face));
+        block::id(other) == POWERED_REPEATER && repeater_facing(other) == face
+    })
+}
+
+/// Pistons extend when powered from any side but the front, or by what would power the
+/// block above them.
+fn should_extend(world: &World, position: Position, facing: u8) -> bool {
+    let above = position.offset(UP);
+    (0..6)
+        .filter(|face| *face != facing)
+        .any(|face| power_from(world, position.offset(face), face ^ 1, true) > 0)
+        || (0..6)
+            .filter(|face| *face != DOWN)
+            .any(|face| power_from(world, above.offset(face), face ^ 1, true) > 0)
+}
+fn check_piston(world: &mut World, position: Position, state: BlockState) {
+    let facing = block::meta(state) & 7;
+    let extend = should_extend(world, position, facing);
+    if extend == top_bit(state) || (extend && push_line(world, position, facing).is_none()) {
+        return;
+    }
+    if !world.piston_moves.contains(&(position, extend)) {
+        world.piston_moves.push((position, extend));
+    }
+}
+fn mobility(state: BlockState) -> Mobility {
+    let block = block::get(state);
+    match block::id(state) {
+        CHEST | STANDING_SIGN | WALL_SIGN | PISTON_HEAD => Mobility::Block,
+        _ if state == block::OBSIDIAN || block.hardness < 0.0 => Mobility::Block,
+        _ if is_piston(state) && top_bit(state) => Mobility::Block,
+        _ if !block.solid => Mobility::Destroy,
+        _ => Mobility::Normal,
+    }
+}
+/// Whether a block can be moved one block towards `direction` without leaving the world.
+fn in_bounds(position: Position, direction: u8) -> bool {
+    (0..256).contains(&position.y)
+        && !(direction == DOWN && position.y == 0)
+        && !(direction == UP && position.y == 255)
+}
+/// The blocks a piston would push, nearest first, and the ones it would break, or `None` if
+/// it can't extend.
+fn push_line(
+    world: &World,
+    position: Position,
+    facing: u8,
+) -> Option<(Vec<Position>, Vec<Position>)> {
+    let mut line = vec![];
+    let mut destroy = vec![];
+    let mut next = position.offset(facing);
+    if !in_bounds(next, 6) {
+        return None;
+    }
+    loop {
+        let state = world.block_at(next);
+        if state == block::AIR {
+            break;
+        }
+        match mobility(state) {
+            Mobility::Destroy => {
+                destroy.push(next);
+                break;
+            }
+            Mobility::Block => return None,
+            Mobility::Normal => {
+                if !in_bounds(next, facing) || line.len() >= PUSH_LIMIT {
+                    return None;
+                }
+                line.push(next);
+            }
+        }
+        next = next.offset(facing);
+        if !world.is_loaded(next.x, next.y, next.z) {
+            return None;
+        }
+    }
+    Some((line, destroy))
+}
+
+/// Runs the piston moves queued this tick, and any they set off in turn.
+pub fn run_piston_moves(world: &mut World) {
+    while !world.piston_moves.is_empty() {
+        for (position, extend) in std::mem::take(&mut world.piston_moves) {
+            move_piston(world, position, extend);
+        }
+    }
+}
+fn move_piston(world: &mut World, position: Position, extend: bool) {
+    let state = world.block_at(position);
+    let id = block::id(state);
+    let facing = block::meta(state) & 7;
+    if !is_piston(state)
+        || top_bit(state) == extend
+        || should_extend(world, position, facing) != extend
+    {
+        return;
+    }
+    let head = position.offset(facing);
+    let mut touched = vec![position, head];
+    if extend {
+        let Some((line, destroy)) = push_line(world, position, facing) else {
+            return;
+        };
+        world.events.push(BlockEvent::Action {
+            position,
+            action: 0,
+            param: facing,
+            block: id,
+        });
+        for broken in &destroy {
+            let state = world.block_at(*broken);
+            world.events.push(BlockEvent::Destroyed(*broken, state));
+            world.update_block(*broken, block::AIR, false);
+        }
+        let states: Vec<BlockState> = line.iter().map(|p| world.block_at(*p)).collect();
+        for (moved, state) in line.iter().zip(states).rev() {
+            world.update_block(moved.offset(facing), state, false);
+        }
+        let sticky = if id == STICKY_PISTON { 8 } else { 0 };
+        world.update_block(head, block::state(PISTON_HEAD, facing | sticky), false);
+        world.update_block(position, block::state(id, facing | 8), false);
+        touched.extend(destroy);
+        touched.extend(line.iter().map(|p| p.offset(facing)));
+        play(world, position, Sound::PistonExtend, 0.5, 0.7);
+    } else {
+        world.events.push(BlockEvent::Action {
+            position,
+            action: 1,
+            param: facing,
+            block: id,
+        });
+        // Unextended first so removing the head doesn't take the base with it.
+        world.update_block(position, block::state(id, facing), false);
+        let pulled_position = head.offset(facing);
+        let pulled = world.block_at(pulled_position);
+        if id == STICKY_PISTON
+            && pulled != block::AIR
+            && mobility(pulled) == Mobility::Normal
+            && in_bounds(pulled_position, facing ^ 1)
+        {
+            world.update_block(head, pulled, false);
+            world.update_block(pulled_position, block::AIR, false);
+            touched.push(pulled_position);
+        } else if block::id(world.block_at(head)) == PISTON_HEAD {
+            world.update_block(head, block::AIR, false);
+        }
+        play(world, position, Sound::PistonContract, 0.5, 0.6);
+    }
+    for position in touched {
+        world.notify_neighbors(position);
+    }
+}
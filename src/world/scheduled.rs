@@ -0,0 +1,56 @@
+//! Block updates scheduled for a later tick, like liquid spreading a few ticks after it
+//! arrives somewhere.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::packet::Position;
+
+/// Vanilla stops after this many scheduled updates in one tick and leaves the rest for later.
+const MAX_PER_TICK: usize = 65536;
+
+#[derive(Debug, Default)]
+pub struct ScheduledTicks {
+    /// By the world age they are due at, then in the order they were scheduled.
+    queue: BTreeMap<(i64, u64), (Position, u16)>,
+    /// Each block can only have one update pending at a position.
+    pending: HashSet<(Position, u16)>,
+    next_id: u64,
+}
+impl ScheduledTicks {
+    /// Schedules an update for the block with id `block` at `position`, unless one is already
+    /// pending.
+    pub fn schedule(&mut self, position: Position, block: u16, due: i64) {
+        if !self.pending.insert((position, block)) {
+            return;
+        }
+        self.queue.insert((due, self.next_id), (position, block));
+        self.next_id += 1;
+    }
+    pub fn is_pending(&self, position: Position, block: u16) -> bool {
+        self.pending.contains(&(position, block))
+    }
+    /// Removes and returns the updates due by `now`, oldest first.
+    pub fn take_due(&mut self, now: i64) -> Vec<(Position, u16)> {
+        let mut due = vec![];
+        while due.len() < MAX_PER_TICK {
+            let Some(entry) = self.queue.first_entry() else {
+                break;
+            };
+            if entry.key().0 > now {
+                break;
+            }
+            let update = entry.remove();
+            self.pending.remove(&update);
+            due.push(update);
+        }
+        due
+    }
+    /// The updates pending in a chunk column, with the world age they are due at.
+    pub fn in_chunk(&self, cx: i32, cz: i32) -> Vec<(Position, u16, i64)> {
+        self.queue
+            .iter()
+            .filter(|(_, (p, _))| p.x >> 4 == cx && p.z >> 4 == cz)
+            .map(|((due, _), (position, block))| (*position, *block, *due))
+            .collect()
+    }
+}
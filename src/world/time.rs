@@ -0,0 +1,97 @@
+//! The world clock and weather, advanced once per tick.
+
+use rand::Rng;
+
+pub const TICKS_PER_DAY: i64 = 24000;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Clock {
+    /// Ticks since the world was created, never affected by commands.
+    pub age: i64,
+    /// Ticks since the first sunrise, including passed days. `/time set` resets it.
+    pub day_time: i64,
+}
+impl Clock {
+    pub fn tick(&mut self, daylight_cycle: bool) {
+        self.age += 1;
+        if daylight_cycle {
+            self.day_time += 1;
+        }
+    }
+}
+
+/// Vanilla's weather cycle: rain and thunder each switch on and off after a random time, and
+/// `/weather clear` holds both off for a while.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Weather {
+    pub raining: bool,
+    pub thundering: bool,
+    /// Ticks until `raining` flips, 0 to pick a new random time.
+    pub rain_time: i32,
+    pub thunder_time: i32,
+    /// Ticks left of forced clear weather.
+    pub clear_time: i32,
+    /// Fade from 0 to 1, what the client actually shows.
+    pub rain_level: f32,
+    pub thunder_level: f32,
+}
+impl Weather {
+    pub fn tick(&mut self, weather_cycle: bool) {
+        if weather_cycle {
+            let mut rng = rand::thread_rng();
+            if self.clear_time > 0 {
+                self.clear_time -= 1;
+                self.rain_time = if self.raining { 0 } else { 1 };
+                self.thunder_time = if self.thundering { 0 } else { 1 };
+                self.raining = false;
+                self.thundering = false;
+            } else {
+                if self.thunder_time <= 0 {
+                    self.thunder_time = if self.thundering {
+                        rng.gen_range(3600..15600)
+                    } else {
+                        rng.gen_range(12000..180000)
+                    };
+                } else {
+                    self.thunder_time -= 1;
+                    if self.thunder_time == 0 {
+                        self.thundering = !self.thundering;
+                    }
+                }
+                if self.rain_time <= 0 {
+                    self.rain_time = if self.raining {
+                        rng.gen_range(12000..24000)
+                    } else {
+                        rng.gen_range(12000..180000)
+                    };
+                } else {
+                    self.rain_time -= 1;
+                    if self.rain_time == 0 {
+                        self.raining = !self.raining;
+                    }
+                }
+            }
+        }
+        let fade = |level: f32, on: bool| (level + if on { 0.01 } else { -0.01 }).clamp(0.0, 1.0);
+        self.rain_level = fade(self.rain_level, self.raining);
+        self.thunder_level = fade(self.thunder_level, self.thundering);
+    }
+    /// Whether clients should be showing rain, which vanilla decides by the fade level.
+    pub fn is_raining(&self) -> bool {
+        self.rain_level > 0.2
+    }
+    pub fn set_clear(&mut self, duration: i32) {
+        self.clear_time = duration;
+        self.rain_time = 0;
+        self.thunder_time = 0;
+        self.raining = false;
+        self.thundering = false;
+    }
+    pub fn set_rain(&mut self, duration: i32, thunder: bool) {
+        self.clear_time = 0;
+        self.rain_time = duration;
+        self.thunder_time = duration;
+        self.raining = true;
+        self.thundering = thunder;
+    }
+}